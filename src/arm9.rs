@@ -0,0 +1,146 @@
+use crate::{
+    builder::{split_nitro_footer, NITROCODE},
+    byte_types::int::U32,
+    compression::blz,
+    error::{Error, Result},
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// The part of the ARM9 binary that is never compressed, because the secure
+/// area and the startup code live there
+pub const UNCOMPRESSED_SIZE: usize = 0x4000;
+
+/// Parameters the NitroSDK stores in the ARM9 binary for its startup code
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct ModuleParams {
+    pub autoload_list_start: U32<LittleEndian>,
+    pub autoload_list_end: U32<LittleEndian>,
+    pub autoload_start: U32<LittleEndian>,
+    pub static_bss_start: U32<LittleEndian>,
+    pub static_bss_end: U32<LittleEndian>,
+    /// RAM address at which the compressed part of the binary ends, 0 if the
+    /// binary is not compressed
+    pub compressed_static_end: U32<LittleEndian>,
+    pub sdk_version: U32<LittleEndian>,
+    pub nitro_code_le: U32<LittleEndian>,
+    pub nitro_code_be: U32<LittleEndian>,
+}
+
+impl ModuleParams {
+    /// Finds the offset of the module parameters in an ARM9 binary.
+    ///
+    /// Uses the offset in the NitroSDK footer if `arm9` ends with one and
+    /// searches for the nitro codes otherwise.
+    pub fn find(arm9: &[u8]) -> Option<usize> {
+        let size = std::mem::size_of::<Self>();
+        let (code, footer) = split_nitro_footer(arm9);
+
+        let is_valid = |offset: usize| {
+            code.get(offset..(offset + size))
+                .and_then(LayoutVerified::<_, Self>::new)
+                .is_some_and(|params| params.has_nitro_codes())
+        };
+
+        if !footer.is_empty() {
+            let offset = u32::from_le_bytes(footer[4..8].try_into().unwrap()) as usize;
+            if is_valid(offset) {
+                return Some(offset);
+            }
+        }
+
+        let mut magic = [0; 8];
+        magic[..4].copy_from_slice(&NITROCODE.to_le_bytes());
+        magic[4..].copy_from_slice(&NITROCODE.swap_bytes().to_le_bytes());
+
+        code.windows(magic.len())
+            .position(|window| window == magic)
+            .and_then(|position| position.checked_sub(size - magic.len()))
+            .filter(|offset| is_valid(*offset))
+    }
+
+    pub fn read(arm9: &[u8]) -> Option<Self> {
+        let offset = Self::find(arm9)?;
+        LayoutVerified::<_, Self>::new(&arm9[offset..(offset + std::mem::size_of::<Self>())])
+            .map(|params| *params)
+    }
+
+    fn write(&self, arm9: &mut [u8], offset: usize) {
+        arm9[offset..(offset + std::mem::size_of::<Self>())].copy_from_slice(self.as_bytes());
+    }
+
+    pub fn has_nitro_codes(&self) -> bool {
+        self.nitro_code_le.get() == NITROCODE && self.nitro_code_be.get() == NITROCODE.swap_bytes()
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed_static_end.get() != 0
+    }
+}
+
+/// Whether the module parameters of `arm9` mark it as compressed
+pub fn is_compressed(arm9: &[u8]) -> bool {
+    ModuleParams::read(arm9).is_some_and(|params| params.is_compressed())
+}
+
+/// Decompresses an ARM9 binary loaded at `ram_address`, optionally followed by
+/// the NitroSDK footer.
+///
+/// The module parameters are updated to mark the result as uncompressed, so the
+/// startup code skips decompression. Binaries that are not compressed are
+/// returned as they are.
+pub fn decompress(arm9: &[u8], ram_address: u32) -> Result<Vec<u8>> {
+    let offset = ModuleParams::find(arm9).ok_or(Error::MissingModuleParams)?;
+    let mut params = ModuleParams::read(arm9).ok_or(Error::MissingModuleParams)?;
+    if !params.is_compressed() {
+        return Ok(arm9.to_vec());
+    }
+
+    let (code, footer) = split_nitro_footer(arm9);
+    let end =
+        (params.compressed_static_end.get().wrapping_sub(ram_address) as usize).min(code.len());
+
+    let mut result = blz::decompress(&code[..end])?;
+    result.extend_from_slice(&code[end..]);
+
+    params.compressed_static_end.set(0);
+    params.write(&mut result, offset);
+
+    result.extend_from_slice(footer);
+    Ok(result)
+}
+
+/// Compresses an ARM9 binary loaded at `ram_address`, optionally followed by
+/// the NitroSDK footer.
+///
+/// The first [UNCOMPRESSED_SIZE] bytes stay as they are and the module
+/// parameters are updated to point at the end of the compressed data.
+/// Binaries that are already compressed or don't get any smaller are returned
+/// as they are.
+pub fn compress(arm9: &[u8], ram_address: u32) -> Result<Vec<u8>> {
+    let offset = ModuleParams::find(arm9).ok_or(Error::MissingModuleParams)?;
+    let mut params = ModuleParams::read(arm9).ok_or(Error::MissingModuleParams)?;
+    if params.is_compressed() {
+        return Ok(arm9.to_vec());
+    }
+
+    let (code, footer) = split_nitro_footer(arm9);
+    let prefix = UNCOMPRESSED_SIZE.max(offset + std::mem::size_of::<ModuleParams>());
+
+    let mut result = blz::compress(code, prefix);
+    if !blz::is_compressed(&result) {
+        return Ok(arm9.to_vec());
+    }
+
+    params
+        .compressed_static_end
+        .set(ram_address.wrapping_add(result.len() as u32));
+    params.write(&mut result, offset);
+
+    result.extend_from_slice(footer);
+    Ok(result)
+}
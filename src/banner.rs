@@ -0,0 +1,194 @@
+use crate::{
+    byte_types::int::U16,
+    crc::{crc16, Checksum},
+    error::{Error, Result, Table},
+    graphics::{
+        color::Bgr555,
+        image::{Image, IndexedImage},
+        BitDepth,
+    },
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Formatter},
+    ops::Range,
+};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Languages of the banner titles, in the order they are stored
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub enum Language {
+    Japanese,
+    English,
+    French,
+    German,
+    Italian,
+    Spanish,
+    /// Only present from banner version 2
+    Chinese,
+    /// Only present from banner version 3
+    Korean,
+}
+
+impl Language {
+    pub const ALL: [Language; 8] = [
+        Language::Japanese,
+        Language::English,
+        Language::French,
+        Language::German,
+        Language::Italian,
+        Language::Spanish,
+        Language::Chinese,
+        Language::Korean,
+    ];
+}
+
+/// A title as stored in the banner: up to 128 UTF-16 code units, padded with
+/// zeros
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned)]
+pub struct BannerTitle(pub [U16<LittleEndian>; 0x80]);
+
+impl BannerTitle {
+    pub fn to_string_lossy(&self) -> String {
+        let units = self
+            .0
+            .iter()
+            .map(|unit| unit.get())
+            .take_while(|unit| *unit != 0);
+        char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    }
+}
+
+impl Debug for BannerTitle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+/// The icon/title banner that `icon_title_offset` points to.
+///
+/// Covers everything up to the end of the version 3 titles.
+/// Shorter banners are padded with zeros.
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative)]
+#[derivative(Debug)]
+pub struct Banner {
+    /// 1, 2 or 3 for the number of title languages, 0x103 for DSi banners
+    pub version: U16<LittleEndian>,
+    /// CRC16 over the regions in [Banner::CHECKSUM_RANGES]
+    pub checksums: [U16<LittleEndian>; 4],
+
+    #[derivative(Debug = "ignore")]
+    pub _reserved_0: [u8; 0x16],
+
+    /// 32x32 pixels in 4x4 tiles of 4bpp
+    #[derivative(Debug = "ignore")]
+    pub icon: [u8; 0x200],
+    /// Color 0 is transparent
+    pub palette: [Bgr555; 16],
+
+    pub titles: [BannerTitle; 8],
+}
+
+impl Banner {
+    /// Regions of the raw banner covered by each of the checksums
+    pub const CHECKSUM_RANGES: [Range<usize>; 4] =
+        [0x20..0x840, 0x20..0x940, 0x20..0xA40, 0x1240..0x23C0];
+
+    /// Size of the raw banner for a version
+    pub fn size_for_version(version: u16) -> usize {
+        match version {
+            0x0002 => 0x940,
+            0x0003 => 0xA40,
+            0x0103 => 0x23C0,
+            _ => 0x840,
+        }
+    }
+
+    /// Parses a raw banner, as returned by
+    /// [CartridgeHeader::read_banner_raw](crate::cartridge_header::CartridgeHeader::read_banner_raw)
+    pub fn read(raw: &[u8], offset: usize) -> Result<Self> {
+        let minimum = Self::size_for_version(1);
+        if raw.len() < minimum {
+            return Err(Error::OutOfBounds {
+                table: Table::Banner,
+                offset,
+                expected: minimum,
+                actual: raw.len(),
+            });
+        }
+
+        let mut buffer = [0; std::mem::size_of::<Banner>()];
+        let length = raw.len().min(buffer.len());
+        buffer[..length].copy_from_slice(&raw[..length]);
+
+        let banner = LayoutVerified::<_, Banner>::new(buffer.as_slice())
+            .expect("buffer has the size of a banner");
+        Ok(*banner)
+    }
+
+    /// Number of title languages this version has
+    pub fn language_count(&self) -> usize {
+        match self.version.get() {
+            0x0002 => 7,
+            0x0003 | 0x0103 => 8,
+            _ => 6,
+        }
+    }
+
+    pub fn title(&self, language: Language) -> Option<String> {
+        let index = language as usize;
+        if index < self.language_count() {
+            Some(self.titles[index].to_string_lossy())
+        } else {
+            None
+        }
+    }
+
+    /// All titles this version has
+    pub fn titles(&self) -> BTreeMap<Language, String> {
+        Language::ALL
+            .iter()
+            .filter_map(|language| Some((*language, self.title(*language)?)))
+            .collect()
+    }
+
+    pub fn icon(&self) -> IndexedImage {
+        IndexedImage::from_tiles(&self.icon, BitDepth::Bpp4, 4, 4)
+    }
+
+    pub fn icon_rgba(&self) -> Image {
+        self.icon().to_rgba(&self.palette, true)
+    }
+
+    /// Compares the stored checksums with ones computed from `raw`.
+    ///
+    /// Only checksums that apply to this version and are covered by `raw` are
+    /// returned.
+    pub fn checksums(&self, raw: &[u8]) -> Vec<Checksum> {
+        let used = match self.version.get() {
+            0x0002 => 2,
+            0x0003 => 3,
+            0x0103 => 4,
+            _ => 1,
+        };
+
+        Self::CHECKSUM_RANGES
+            .iter()
+            .zip(self.checksums.iter())
+            .take(used)
+            .filter_map(|(range, stored)| {
+                Some(Checksum {
+                    stored: stored.get(),
+                    computed: crc16(raw.get(range.clone())?),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{
+    cartridge_header::{CartridgeHeader, OffsetAndSize, HEADER_AREA_SIZE},
+    error::{Error, Result},
+    file::{filesystem::SerializedFilesystem, tree::Directory},
+    overlay::Overlay,
+};
+use byte_unit::KIBIBYTE;
+use zerocopy::AsBytes;
+
+/// Alignment of every binary, table and file in the ROM
+pub const ALIGNMENT: usize = 0x200;
+
+/// Marks the 12 byte footer that follows the ARM9 binary in ROMs built with the
+/// NitroSDK
+pub const NITROCODE: u32 = 0xDEC00621;
+
+/// Everything needed to lay out a ROM from scratch
+#[derive(Clone, Debug)]
+pub struct RomBuilder {
+    /// Offsets, sizes and checksums are overwritten by [RomBuilder::build]
+    pub header: CartridgeHeader,
+
+    /// The ARM9 binary, optionally followed by the NitroSDK footer
+    pub arm9: Vec<u8>,
+    pub arm7: Vec<u8>,
+
+    /// Overlays in table order.
+    /// File IDs and compressed sizes are updated during the build.
+    pub arm9_overlays: Vec<Overlay>,
+    pub arm7_overlays: Vec<Overlay>,
+
+    pub banner: Option<Vec<u8>>,
+
+    pub root: Directory,
+}
+
+/// Splits the NitroSDK footer off an ARM9 binary, if there is one
+pub fn split_nitro_footer(arm9: &[u8]) -> (&[u8], &[u8]) {
+    if arm9.len() >= 12 {
+        let (code, footer) = arm9.split_at(arm9.len() - 12);
+        if footer[..4] == NITROCODE.to_le_bytes() {
+            return (code, footer);
+        }
+    }
+
+    (arm9, &[])
+}
+
+/// The smallest `device_capacity_raw` that fits `size` bytes
+pub fn device_capacity_for(size: usize) -> Option<u8> {
+    (0..=0xC).find(|raw| (128 * KIBIBYTE as usize) << raw >= size)
+}
+
+struct Writer {
+    rom: Vec<u8>,
+}
+
+impl Writer {
+    fn align(&mut self) {
+        let aligned = self.rom.len().div_ceil(ALIGNMENT) * ALIGNMENT;
+        self.rom.resize(aligned, 0xFF);
+    }
+
+    /// Appends `data` at the next aligned offset and returns that offset
+    fn place(&mut self, data: &[u8]) -> usize {
+        self.align();
+        let offset = self.rom.len();
+        self.rom.extend_from_slice(data);
+        offset
+    }
+
+    fn place_region(&mut self, data: &[u8]) -> OffsetAndSize {
+        let offset = if data.is_empty() { 0 } else { self.place(data) };
+        OffsetAndSize {
+            offset: (offset as u32).into(),
+            size: (data.len() as u32).into(),
+        }
+    }
+
+    /// Places the overlay table, with file IDs counting up from
+    /// `first_file_id`. The overlays themselves are stored with the other
+    /// files.
+    fn place_overlay_table(&mut self, overlays: &[Overlay], first_file_id: usize) -> OffsetAndSize {
+        let table = overlays
+            .iter()
+            .enumerate()
+            .map(|(index, overlay)| {
+                let mut entry = overlay.entry;
+                entry.file_id.set((first_file_id + index) as u32);
+                if entry.is_compressed() {
+                    entry.set_compressed_size(overlay.data.len() as u32);
+                }
+                entry
+            })
+            .collect::<Vec<_>>();
+        self.place_region(table.as_bytes())
+    }
+}
+
+impl RomBuilder {
+    /// Lays out a new ROM.
+    ///
+    /// The ROM is not padded to the device capacity.
+    /// `secure_area_checksum` is kept as given, because it covers the
+    /// encrypted secure area.
+    pub fn build(&self) -> Result<Vec<u8>> {
+        let mut header = self.header;
+        let mut writer = Writer {
+            rom: vec![0; HEADER_AREA_SIZE],
+        };
+
+        let (arm9, _) = split_nitro_footer(&self.arm9);
+        header.arm9.rom_offset = (writer.place(&self.arm9) as u32).into();
+        header.arm9.size = (arm9.len() as u32).into();
+
+        header.arm9_overlay = writer.place_overlay_table(&self.arm9_overlays, 0);
+
+        header.arm7.rom_offset = (writer.place(&self.arm7) as u32).into();
+        header.arm7.size = (self.arm7.len() as u32).into();
+
+        header.arm7_overlay =
+            writer.place_overlay_table(&self.arm7_overlays, self.arm9_overlays.len());
+
+        // Overlays are the files without names. The files are laid out relative
+        // to an aligned offset here and moved once the tables before them are
+        // placed.
+        let overlays = self
+            .arm9_overlays
+            .iter()
+            .chain(&self.arm7_overlays)
+            .map(|overlay| overlay.data.as_slice())
+            .collect::<Vec<_>>();
+        let mut filesystem = SerializedFilesystem::new(&overlays, &self.root, 0, ALIGNMENT)?;
+
+        header.fnt = writer.place_region(&filesystem.fnt_bytes());
+        header.fat = writer.place_region(filesystem.fat_bytes());
+
+        header.icon_title_offset = match &self.banner {
+            Some(banner) => (writer.place(banner) as u32).into(),
+            None => 0.into(),
+        };
+
+        let data_offset = writer.place(&filesystem.data) as u32;
+        for entry in &mut filesystem.fat {
+            entry.start.set(entry.start.get() + data_offset);
+            entry.end.set(entry.end.get() + data_offset);
+        }
+
+        let mut rom = writer.rom;
+        let fat_offset = header.fat.offset.get() as usize;
+        let fat = filesystem.fat_bytes();
+        rom[fat_offset..(fat_offset + fat.len())].copy_from_slice(fat);
+
+        header.total_used_rom_size = (rom.len() as u32).into();
+        header.rom_header_size = (HEADER_AREA_SIZE as u32).into();
+        header.device_capacity_raw =
+            device_capacity_for(rom.len()).ok_or(Error::RomTooLarge { size: rom.len() })?;
+        header.update_checksums();
+
+        rom[..std::mem::size_of::<CartridgeHeader>()].copy_from_slice(header.as_bytes());
+
+        Ok(rom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file::{filesystem::Filesystem, FileId},
+        overlay::{Overlay, OverlayTableEntry},
+    };
+    use zerocopy::FromBytes;
+
+    fn overlay(id: u32, fill: u8) -> Overlay {
+        let mut entry = OverlayTableEntry::new_zeroed();
+        entry.overlay_id.set(id);
+        Overlay {
+            entry,
+            data: vec![fill; 0x20],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut root = Directory::default();
+        root.add_file("a", vec![0xA; 3]).unwrap();
+        root.add_file("dir/b", vec![0xB; 0x300]).unwrap();
+        let builder = RomBuilder {
+            header: CartridgeHeader::new_zeroed(),
+            arm9: vec![9; 0x10],
+            arm7: vec![7; 0x10],
+            arm9_overlays: vec![overlay(0, 0x90), overlay(1, 0x91)],
+            arm7_overlays: vec![overlay(0, 0x70)],
+            banner: None,
+            root,
+        };
+        let rom = builder.build().unwrap();
+
+        let header = CartridgeHeader::read_from_prefix(&rom[..]).unwrap();
+        assert!(header.checksums(&rom).header.is_valid());
+        let file_ids = header
+            .read_arm9_overlays(&rom)
+            .unwrap()
+            .iter()
+            .chain(header.read_arm7_overlays(&rom).unwrap().iter())
+            .map(|entry| entry.file_id.get())
+            .collect::<Vec<_>>();
+        assert_eq!(file_ids, [0, 1, 2]);
+
+        let files = header.read_files(&rom).unwrap();
+        assert_eq!(files.lookup("/a"), Some(FileId(3)));
+        let filesystem = Filesystem::read(&files).unwrap();
+        assert_eq!(
+            filesystem.unnamed_files,
+            [vec![0x90; 0x20], vec![0x91; 0x20], vec![0x70; 0x20]]
+        );
+
+        let rebuilt = RomBuilder {
+            root: filesystem.root,
+            ..builder
+        }
+        .build()
+        .unwrap();
+        assert_eq!(rebuilt, rom);
+    }
+}
@@ -0,0 +1,21 @@
+//! Serializes byte arrays of any length as hex strings, for use with
+//! `#[serde(with = "crate::byte_types::hex_array")]`
+
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S, const SIZE: usize>(bytes: &[u8; SIZE], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&hex::encode(bytes))
+}
+
+pub fn deserialize<'de, D, const SIZE: usize>(deserializer: D) -> Result<[u8; SIZE], D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let string = String::deserialize(deserializer)?;
+    let mut bytes = [0; SIZE];
+    hex::decode_to_slice(string, &mut bytes).map_err(D::Error::custom)?;
+    Ok(bytes)
+}
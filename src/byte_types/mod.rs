@@ -1,2 +1,3 @@
 pub mod embedded_string;
+pub mod hex_array;
 pub mod int;
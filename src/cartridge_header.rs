@@ -1,16 +1,22 @@
 use crate::{
+    banner::Banner,
     byte_types::{
         embedded_string::EmbeddedString,
         int::{U16, U32},
     },
+    crc::{crc16, Checksum},
+    error::{Error, Result, Table},
     file::{
         file_allocation_table::FileAllocationTableEntry, file_name_table::FileNameTable, Files,
     },
+    overlay::OverlayTableEntry,
+    secure_area::{self, KeyTable, State},
 };
-use byte_unit::{Byte, KIBIBYTE};
+use byte_unit::Byte;
 use byteorder::LittleEndian;
 use derivative::Derivative;
 use serde::{Deserialize, Serialize};
+use std::ops::Range;
 use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
 
 #[repr(C)]
@@ -20,8 +26,11 @@ pub struct CartridgeHeader {
     pub title: EmbeddedString<12>,
     pub game_code: EmbeddedString<4>,
     pub maker_code: EmbeddedString<2>,
+    #[serde(with = "crate::header_fields::unit_code")]
     pub unit_code: u8,
+    #[serde(with = "crate::header_fields::encryption_seed")]
     pub encryption_seed_select: u8,
+    #[serde(with = "crate::header_fields::device_capacity")]
     pub device_capacity_raw: u8,
 
     #[derivative(Debug = "ignore")]
@@ -31,9 +40,11 @@ pub struct CartridgeHeader {
     #[serde(skip, default)]
     pub _reserved_1: u8,
 
+    #[serde(with = "crate::header_fields::region")]
     pub region: u8,
     pub rom_version: u8,
 
+    #[serde(with = "crate::header_fields::autostart")]
     pub autostart: u8,
 
     pub arm9: CartridgeHeaderCodeInfo,
@@ -77,10 +88,10 @@ pub struct CartridgeHeader {
     pub fast_boot: EmbeddedString<0x10>,
 
     #[derivative(Debug = "ignore")]
-    #[serde(skip, default = "default_array")]
+    #[serde(skip, default = "default_nintendo_logo")]
     pub nintendo_logo: [u8; 0x9C],
     #[derivative(Debug = "ignore")]
-    #[serde(skip, default)]
+    #[serde(skip, default = "default_nintendo_logo_checksum")]
     pub nintendo_logo_checksum: U16<LittleEndian>,
 
     pub header_checksum: U16<LittleEndian>,
@@ -107,16 +118,41 @@ pub struct OffsetAndSize {
     pub size: U32<LittleEndian>,
 }
 
+/// Size of the header area at the start of the ROM, including the unused space
+/// up to the ARM9 binary
+pub const HEADER_AREA_SIZE: usize = 0x4000;
+
+/// Number of header bytes covered by `header_checksum`
+pub const HEADER_CHECKSUM_RANGE: usize = 0x15E;
+
+/// ROM region covered by `secure_area_checksum`
+pub const SECURE_AREA_CHECKSUM_RANGE: Range<usize> = 0x4000..0x8000;
+
+/// The logo every cartridge has to carry, checked by the BIOS
+pub const NINTENDO_LOGO: [u8; 0x9C] = [
+    0x24, 0xFF, 0xAE, 0x51, 0x69, 0x9A, 0xA2, 0x21, 0x3D, 0x84, 0x82, 0x0A, 0x84, 0xE4, 0x09, 0xAD,
+    0x11, 0x24, 0x8B, 0x98, 0xC0, 0x81, 0x7F, 0x21, 0xA3, 0x52, 0xBE, 0x19, 0x93, 0x09, 0xCE, 0x20,
+    0x10, 0x46, 0x4A, 0x4A, 0xF8, 0x27, 0x31, 0xEC, 0x58, 0xC7, 0xE8, 0x33, 0x82, 0xE3, 0xCE, 0xBF,
+    0x85, 0xF4, 0xDF, 0x94, 0xCE, 0x4B, 0x09, 0xC1, 0x94, 0x56, 0x8A, 0xC0, 0x13, 0x72, 0xA7, 0xFC,
+    0x9F, 0x84, 0x4D, 0x73, 0xA3, 0xCA, 0x9A, 0x61, 0x58, 0x97, 0xA3, 0x27, 0xFC, 0x03, 0x98, 0x76,
+    0x23, 0x1D, 0xC7, 0x61, 0x03, 0x04, 0xAE, 0x56, 0xBF, 0x38, 0x84, 0x00, 0x40, 0xA7, 0x0E, 0xFD,
+    0xFF, 0x52, 0xFE, 0x03, 0x6F, 0x95, 0x30, 0xF1, 0x97, 0xFB, 0xC0, 0x85, 0x60, 0xD6, 0x80, 0x25,
+    0xA9, 0x63, 0xBE, 0x03, 0x01, 0x4E, 0x38, 0xE2, 0xF9, 0xA2, 0x34, 0xFF, 0xBB, 0x3E, 0x03, 0x44,
+    0x78, 0x00, 0x90, 0xCB, 0x88, 0x11, 0x3A, 0x94, 0x65, 0xC0, 0x7C, 0x63, 0x87, 0xF0, 0x3C, 0xAF,
+    0xD6, 0x25, 0xE4, 0x8B, 0x38, 0x0A, 0xAC, 0x72, 0x21, 0xD4, 0xF8, 0x07,
+];
+
 impl CartridgeHeader {
-    pub fn device_capacity(&self) -> Byte {
-        Byte::from_bytes((128 << self.device_capacity_raw) * KIBIBYTE)
+    /// The chip size, or `None` if the capacity byte is not a known value
+    pub fn device_capacity(&self) -> Option<Byte> {
+        self.capacity().size()
     }
 
-    pub fn read_fnt(&self, rom: &[u8]) -> Option<FileNameTable> {
+    pub fn read_fnt(&self, rom: &[u8]) -> Result<FileNameTable> {
         let base = self.fnt.offset.get() as usize;
         let size = self.fnt.size.get();
         if size == 0 {
-            return Some(FileNameTable {
+            return Ok(FileNameTable {
                 main_table: vec![],
                 sub_tables: vec![],
             });
@@ -128,18 +164,139 @@ impl CartridgeHeader {
     pub fn read_fat<'lt>(
         &self,
         rom: &'lt [u8],
-    ) -> Option<LayoutVerified<&'lt [u8], [FileAllocationTableEntry]>> {
-        FileAllocationTableEntry::read_fat(self, &rom)
+    ) -> Result<LayoutVerified<&'lt [u8], [FileAllocationTableEntry]>> {
+        FileAllocationTableEntry::read_fat(self, rom)
     }
 
-    pub fn read_files<'lt>(&self, rom: &'lt [u8]) -> Option<Files<'lt>> {
+    pub fn read_files<'lt>(&self, rom: &'lt [u8]) -> Result<Files<'lt>> {
         Files::read(self, rom)
     }
+
+    pub fn read_arm9_overlays<'lt>(
+        &self,
+        rom: &'lt [u8],
+    ) -> Result<LayoutVerified<&'lt [u8], [OverlayTableEntry]>> {
+        OverlayTableEntry::read_table(rom, &self.arm9_overlay, Table::Arm9OverlayTable)
+    }
+
+    pub fn read_arm7_overlays<'lt>(
+        &self,
+        rom: &'lt [u8],
+    ) -> Result<LayoutVerified<&'lt [u8], [OverlayTableEntry]>> {
+        OverlayTableEntry::read_table(rom, &self.arm7_overlay, Table::Arm7OverlayTable)
+    }
+
+    /// Reads the raw icon/title banner, if there is one
+    pub fn read_banner_raw<'lt>(&self, rom: &'lt [u8]) -> Result<Option<&'lt [u8]>> {
+        let offset = self.icon_title_offset.get() as usize;
+        if offset == 0 {
+            return Ok(None);
+        }
+
+        let out_of_bounds = |expected| Error::OutOfBounds {
+            table: Table::Banner,
+            offset,
+            expected,
+            actual: rom.len().saturating_sub(offset),
+        };
+
+        let version = rom
+            .get(offset..(offset + 2))
+            .ok_or_else(|| out_of_bounds(2))?;
+        let size = Banner::size_for_version(u16::from_le_bytes([version[0], version[1]]));
+
+        rom.get(offset..(offset + size))
+            .map(Some)
+            .ok_or_else(|| out_of_bounds(size))
+    }
+
+    /// Reads and parses the icon/title banner, if there is one
+    pub fn read_banner(&self, rom: &[u8]) -> Result<Option<Banner>> {
+        self.read_banner_raw(rom)?
+            .map(|raw| Banner::read(raw, self.icon_title_offset.get() as usize))
+            .transpose()
+    }
+
+    /// Computes `nintendo_logo_checksum` and `header_checksum` from the current
+    /// contents of the header
+    pub fn update_checksums(&mut self) {
+        self.nintendo_logo_checksum.set(crc16(&self.nintendo_logo));
+        self.header_checksum
+            .set(crc16(&self.as_bytes()[..HEADER_CHECKSUM_RANGE]));
+    }
+
+    /// Like [CartridgeHeader::update_checksums], but also recomputes
+    /// `secure_area_checksum` if the secure area in `rom` is encrypted.
+    ///
+    /// The checksum covers the encrypted secure area, so it is kept for every
+    /// other [State]. Returns the state of the secure area.
+    pub fn update_all_checksums(&mut self, rom: &[u8], key: Option<&KeyTable>) -> State {
+        let state = secure_area::state(self, rom, key);
+        if state == State::Encrypted {
+            if let Some(secure_area) = secure_area_checksum(rom) {
+                self.secure_area_checksum.set(secure_area);
+            }
+        }
+        self.update_checksums();
+        state
+    }
+
+    /// Compares the stored checksums with the ones computed from the header and
+    /// `rom`
+    pub fn checksums(&self, rom: &[u8]) -> HeaderChecksums {
+        HeaderChecksums {
+            header: Checksum {
+                stored: self.header_checksum.get(),
+                computed: crc16(&self.as_bytes()[..HEADER_CHECKSUM_RANGE]),
+            },
+            nintendo_logo: Checksum {
+                stored: self.nintendo_logo_checksum.get(),
+                computed: crc16(&self.nintendo_logo),
+            },
+            secure_area: secure_area_checksum(rom).map(|computed| Checksum {
+                stored: self.secure_area_checksum.get(),
+                computed,
+            }),
+        }
+    }
+}
+
+/// Status of the checksums in a [CartridgeHeader]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct HeaderChecksums {
+    pub header: Checksum,
+    pub nintendo_logo: Checksum,
+    /// Covers the secure area as it is stored in the ROM, which normally means
+    /// encrypted.
+    /// `None` if the ROM is too short to contain a secure area.
+    pub secure_area: Option<Checksum>,
+}
+
+impl HeaderChecksums {
+    pub fn is_valid(&self) -> bool {
+        self.header.is_valid()
+            && self.nintendo_logo.is_valid()
+            && self.secure_area.map_or(true, |c| c.is_valid())
+    }
+}
+
+/// Computes the checksum over the secure area, if `rom` is long enough to have
+/// one
+pub fn secure_area_checksum(rom: &[u8]) -> Option<u16> {
+    rom.get(SECURE_AREA_CHECKSUM_RANGE).map(crc16)
 }
 
-fn default_array<T, const SIZE: usize>() -> [T; SIZE]
+pub(crate) fn default_array<T, const SIZE: usize>() -> [T; SIZE]
 where
     T: Default + Copy,
 {
     [T::default(); SIZE]
 }
+
+fn default_nintendo_logo() -> [u8; 0x9C] {
+    NINTENDO_LOGO
+}
+
+fn default_nintendo_logo_checksum() -> U16<LittleEndian> {
+    crc16(&NINTENDO_LOGO).into()
+}
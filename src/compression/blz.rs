@@ -0,0 +1,272 @@
+//! Backwards LZ compression from the NitroSDK.
+//!
+//! The data is decompressed in place starting at the end, so a compressed
+//! binary is an uncompressed prefix followed by the compressed part and a
+//! footer:
+//!
+//! - `u32`: bits 0-23 size of the compressed part including the footer, bits
+//!   24-31 size of the footer
+//! - `u32`: how many bytes longer the decompressed data is
+
+use crate::{
+    compression::{Format, MatchFinder},
+    error::{Error, Result},
+};
+
+const MIN_LENGTH: usize = 3;
+const MAX_LENGTH: usize = 0x12;
+const MIN_DISPLACEMENT: usize = 3;
+const MAX_DISPLACEMENT: usize = 0x1002;
+
+fn invalid(reason: &'static str) -> Error {
+    Error::InvalidCompressedData {
+        format: Format::Blz,
+        reason,
+    }
+}
+
+/// Whether `data` ends in a plausible footer
+pub fn is_compressed(data: &[u8]) -> bool {
+    footer(data).is_some_and(|(_, _, extra)| extra != 0)
+}
+
+/// Returns (compressed size, footer size, extra size)
+fn footer(data: &[u8]) -> Option<(usize, usize, usize)> {
+    let length = data.len();
+    if length < 8 {
+        return None;
+    }
+
+    let word =
+        |offset: usize| u32::from_le_bytes(data[offset..(offset + 4)].try_into().unwrap()) as usize;
+    let extra = word(length - 4);
+    let footer_size = data[length - 5] as usize;
+    let compressed_size = word(length - 8) & 0x00FF_FFFF;
+
+    if !(8..=11).contains(&footer_size) || compressed_size < footer_size || compressed_size > length
+    {
+        return None;
+    }
+
+    Some((compressed_size, footer_size, extra))
+}
+
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() >= 4 && data[(data.len() - 4)..] == [0; 4] {
+        // Marked as stored without compression
+        return Ok(data[..(data.len() - 4)].to_vec());
+    }
+    let (compressed_size, footer_size, extra) = match footer(data) {
+        Some(footer) => footer,
+        None => return Err(invalid("the footer is malformed")),
+    };
+
+    let prefix = data.len() - compressed_size;
+    let mut input = data[prefix..(data.len() - footer_size)].iter().rev();
+
+    let output_size = compressed_size + extra;
+    let mut output = Vec::with_capacity(output_size);
+
+    'outer: while output.len() < output_size {
+        let flags = match input.next() {
+            Some(flags) => *flags,
+            None => break,
+        };
+
+        for bit in (0..8).rev() {
+            if output.len() >= output_size {
+                break 'outer;
+            }
+
+            if flags & (1 << bit) == 0 {
+                match input.next() {
+                    Some(byte) => output.push(*byte),
+                    None => break 'outer,
+                }
+            } else {
+                let (high, low) = match (input.next(), input.next()) {
+                    (Some(high), Some(low)) => (*high as usize, *low as usize),
+                    _ => return Err(invalid("a reference is truncated")),
+                };
+                let value = (high << 8) | low;
+                let length = ((value >> 12) + MIN_LENGTH).min(output_size - output.len());
+                let displacement = (value & 0xFFF) + MIN_DISPLACEMENT;
+
+                if displacement > output.len() {
+                    return Err(invalid("a reference points before the start of the data"));
+                }
+                for _ in 0..length {
+                    output.push(output[output.len() - displacement]);
+                }
+            }
+        }
+    }
+
+    if output.len() != output_size {
+        return Err(invalid("the compressed data ends early"));
+    }
+
+    output.reverse();
+    let mut result = Vec::with_capacity(prefix + output_size);
+    result.extend_from_slice(&data[..prefix]);
+    result.extend(output);
+    Ok(result)
+}
+
+/// `data` followed by a footer of zeros, which marks it as uncompressed
+fn stored(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len() + 4);
+    result.extend_from_slice(data);
+    result.extend_from_slice(&[0; 4]);
+    result
+}
+
+/// Compresses `data`, leaving at least the first `uncompressed_prefix` bytes
+/// as they are.
+///
+/// Only as much of the data is compressed as is safe to decompress in place.
+/// If compression doesn't save any space, the data is stored with a footer
+/// that marks it as uncompressed.
+pub fn compress(data: &[u8], uncompressed_prefix: usize) -> Vec<u8> {
+    let uncompressed_prefix = uncompressed_prefix.min(data.len());
+
+    // Work on the reversed data, so references point backwards as usual
+    let reversed = data.iter().rev().copied().collect::<Vec<_>>();
+    let end = data.len() - uncompressed_prefix;
+
+    let mut finder = MatchFinder::new(
+        reversed.len(),
+        MIN_DISPLACEMENT,
+        MAX_DISPLACEMENT,
+        MAX_LENGTH,
+    );
+    let mut packed = Vec::with_capacity(end + end / 8 + 1);
+    let mut flag_position = 0;
+    let mut flag_bit = 0;
+
+    // The best place to stop compressing: (packed bytes, consumed bytes, saved
+    // bytes)
+    let mut best_split = (0, 0, 0);
+
+    let mut position = 0;
+    while position < end {
+        if flag_bit == 0 {
+            flag_position = packed.len();
+            packed.push(0);
+            flag_bit = 0x80;
+        }
+
+        let (length, displacement) = finder.find(&reversed, position, end);
+        if length >= MIN_LENGTH {
+            packed[flag_position] |= flag_bit;
+            let value = ((length - MIN_LENGTH) << 12) | (displacement - MIN_DISPLACEMENT);
+            packed.push((value >> 8) as u8);
+            packed.push(value as u8);
+
+            finder.insert_range(&reversed, position, length);
+            position += length;
+        } else {
+            packed.push(reversed[position]);
+            finder.insert(&reversed, position);
+            position += 1;
+        }
+        flag_bit >>= 1;
+
+        // Stopping where the savings are largest also guarantees that the
+        // decompressor never overwrites input it hasn't read yet
+        let saved = position as isize - packed.len() as isize;
+        if saved > best_split.2 {
+            best_split = (packed.len(), position, saved);
+        }
+    }
+
+    let (packed_size, consumed, _) = best_split;
+    let raw_size = data.len() - consumed;
+    let compressed_total = (raw_size + packed_size).div_ceil(4) * 4 + 8;
+
+    if packed_size == 0 || compressed_total >= data.len() {
+        return stored(data);
+    }
+
+    let mut result = Vec::with_capacity(compressed_total);
+    result.extend_from_slice(&data[..raw_size]);
+    result.extend(packed[..packed_size].iter().rev());
+
+    let mut footer_size = 8;
+    while result.len() % 4 != 0 {
+        result.push(0xFF);
+        footer_size += 1;
+    }
+
+    let compressed_size = packed_size + footer_size;
+    let extra = match data.len().checked_sub(result.len() + 8) {
+        Some(extra) if extra > 0 => extra,
+        _ => return stored(data),
+    };
+    result.extend_from_slice(
+        &((compressed_size as u32) | ((footer_size as u32) << 24)).to_le_bytes(),
+    );
+    result.extend_from_slice(&(extra as u32).to_le_bytes());
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::tests::{random, repetitive, samples};
+
+    fn round_trip(data: &[u8], uncompressed_prefix: usize) {
+        let compressed = compress(data, uncompressed_prefix);
+        assert_eq!(decompress(&compressed).unwrap(), data);
+        if is_compressed(&compressed) {
+            assert!(compressed.len() < data.len());
+            assert_eq!(compressed.len() % 4, 0);
+            let prefix = uncompressed_prefix.min(data.len());
+            assert_eq!(compressed[..prefix], data[..prefix]);
+        }
+    }
+
+    #[test]
+    fn samples_round_trip() {
+        for data in samples() {
+            for uncompressed_prefix in [0, 1, 33, 0x4000] {
+                round_trip(&data, uncompressed_prefix);
+            }
+        }
+    }
+
+    #[test]
+    fn barely_compressible() {
+        // Savings of only a few bytes used to underflow the extra size
+        for seed in 1..3000 {
+            let length = 8 + seed as usize % 120;
+            let data = repetitive(seed, length, 4 + seed % 29);
+            round_trip(&data, seed as usize % 40);
+        }
+    }
+
+    #[test]
+    fn stored() {
+        let data = random(3, 57);
+        let compressed = compress(&data, 0);
+        assert!(!is_compressed(&compressed));
+        assert_eq!(compressed[..data.len()], data);
+        assert_eq!(compressed[data.len()..], [0; 4]);
+        assert_eq!(decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(decompress(&[1, 2, 3]).is_err());
+        for seed in 1..500 {
+            let mut data = random(seed, 8 + seed as usize % 64);
+            let length = data.len();
+            // A plausible footer in front of garbage
+            data[length - 8..length - 5].copy_from_slice(&[length as u8, 0, 0]);
+            data[length - 5] = 8;
+            data[length - 4..].copy_from_slice(&[seed as u8, 0, 0, 0]);
+            let _ = decompress(&data);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! Huffman coding of 4 or 8 bit symbols.
+//!
+//! The header is followed by the tree and the bitstream:
+//!
+//! - `u8`: size of the tree in 16 bit units - 1, this byte included
+//! - the nodes, starting with the root. The children of a node at address `a`
+//!   are at `(a & !1) + offset * 2 + 2` and the following address. Bits 0-5 are
+//!   the offset, bit 7 marks the first child as a leaf and bit 6 the second.
+//!   Leaves are just the symbol.
+//! - the bitstream in `u32` units, read from the most significant bit. A 0
+//!   selects the first child.
+//!
+//! 4 bit symbols are stored low nibble first.
+
+use crate::{
+    compression::{output_buffer, Format, Header, Input},
+    error::Result,
+};
+use std::{cmp::Reverse, collections::BinaryHeap, io::Read};
+
+const ROOT: usize = 1;
+const OFFSET_MASK: u8 = 0x3F;
+const MAX_OFFSET: usize = OFFSET_MASK as usize;
+const LEAF_0: u8 = 0x80;
+const LEAF_1: u8 = 0x40;
+
+/// Size of the symbols the data is split into
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SymbolSize {
+    Bits4,
+    Bits8,
+}
+
+impl SymbolSize {
+    pub fn bits(self) -> u8 {
+        match self {
+            SymbolSize::Bits4 => 4,
+            SymbolSize::Bits8 => 8,
+        }
+    }
+
+    pub fn format(self) -> Format {
+        match self {
+            SymbolSize::Bits4 => Format::Huffman4,
+            SymbolSize::Bits8 => Format::Huffman8,
+        }
+    }
+}
+
+pub(crate) fn decode<R: Read>(
+    input: &mut Input<R>,
+    size: usize,
+    symbols: SymbolSize,
+) -> Result<Vec<u8>> {
+    let tree_size = input.u8()?;
+    let mut tree = vec![tree_size];
+    for _ in 1..((tree_size as usize + 1) * 2) {
+        tree.push(input.u8()?);
+    }
+
+    let mut output = output_buffer(size);
+    let mut half = None;
+    let mut position = ROOT;
+
+    while output.len() < size {
+        let word = input.u32()?;
+
+        for bit in (0..32).rev() {
+            let direction = ((word >> bit) & 1) as usize;
+            let node = tree[position];
+            let child = (position & !1) + (node & OFFSET_MASK) as usize * 2 + 2 + direction;
+            let symbol = *tree
+                .get(child)
+                .ok_or_else(|| input.invalid("a node points past the end of the tree"))?;
+
+            if node & (LEAF_0 >> direction) == 0 {
+                position = child;
+                continue;
+            }
+            position = ROOT;
+
+            match (symbols, half) {
+                (SymbolSize::Bits8, _) => output.push(symbol),
+                (_, None) => half = Some(symbol & 0xF),
+                (_, Some(low)) => {
+                    output.push(low | (symbol << 4));
+                    half = None;
+                },
+            }
+
+            if output.len() >= size {
+                break;
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+enum Node {
+    Leaf(u8),
+    Internal(usize, usize),
+}
+
+struct Tree {
+    nodes: Vec<Node>,
+    root: usize,
+}
+
+impl Tree {
+    /// Builds the tree for the symbols with non-zero `frequencies`.
+    ///
+    /// At least two leaves are created, so every symbol has a code.
+    fn build(frequencies: &[u64]) -> Self {
+        let mut nodes = Vec::new();
+        let mut queue = BinaryHeap::new();
+
+        for (symbol, frequency) in frequencies.iter().enumerate() {
+            if *frequency != 0 {
+                queue.push(Reverse((*frequency, nodes.len())));
+                nodes.push(Node::Leaf(symbol as u8));
+            }
+        }
+        for (symbol, frequency) in frequencies.iter().enumerate().take(2) {
+            if queue.len() < 2 && *frequency == 0 {
+                queue.push(Reverse((0, nodes.len())));
+                nodes.push(Node::Leaf(symbol as u8));
+            }
+        }
+
+        while queue.len() > 1 {
+            let Reverse((frequency_0, node_0)) = queue.pop().unwrap();
+            let Reverse((frequency_1, node_1)) = queue.pop().unwrap();
+            queue.push(Reverse((frequency_0 + frequency_1, nodes.len())));
+            nodes.push(Node::Internal(node_0, node_1));
+        }
+
+        let Reverse((_, root)) = queue.pop().unwrap();
+        Self { nodes, root }
+    }
+
+    /// Returns the code of every symbol as a list of bits
+    fn codes(&self) -> Vec<Vec<bool>> {
+        let mut codes = vec![Vec::new(); 256];
+        let mut stack = vec![(self.root, Vec::new())];
+
+        while let Some((node, code)) = stack.pop() {
+            match self.nodes[node] {
+                Node::Leaf(symbol) => codes[symbol as usize] = code,
+                Node::Internal(child_0, child_1) => {
+                    let mut code_1 = code.clone();
+                    code_1.push(true);
+                    stack.push((child_1, code_1));
+
+                    let mut code_0 = code;
+                    code_0.push(false);
+                    stack.push((child_0, code_0));
+                },
+            }
+        }
+
+        codes
+    }
+
+    /// Lays out the tree as the decompressor expects it.
+    ///
+    /// Children have to be placed at most [MAX_OFFSET] pairs after their
+    /// parent. Subtrees are placed depth first to keep the number of nodes
+    /// waiting for their children small, unless one of them is about to run out
+    /// of room. Returns `None` if some offset is too large anyway.
+    fn layout(&self) -> Option<Vec<u8>> {
+        let mut table = vec![0; 2];
+        // Nodes whose children still have to be placed, with their address
+        let mut pending = vec![(self.root, ROOT)];
+
+        while !pending.is_empty() {
+            let next_pair = table.len() / 2;
+            let last_pair = |address: usize| address / 2 + 1 + MAX_OFFSET;
+
+            let (urgent, (_, address)) = pending
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (_, address))| last_pair(*address))
+                .unwrap();
+            let index = if last_pair(*address) < next_pair + pending.len() {
+                urgent
+            } else {
+                pending.len() - 1
+            };
+
+            let (node, address) = pending.remove(index);
+            let offset = next_pair.checked_sub(address / 2 + 1)?;
+            if offset > MAX_OFFSET {
+                return None;
+            }
+
+            let (child_0, child_1) = match self.nodes[node] {
+                Node::Internal(child_0, child_1) => (child_0, child_1),
+                Node::Leaf(_) => unreachable!("only internal nodes are pending"),
+            };
+
+            let mut flags = offset as u8;
+            for (child, leaf_flag) in [(child_0, LEAF_0), (child_1, LEAF_1)] {
+                let child_address = table.len();
+                match self.nodes[child] {
+                    Node::Leaf(symbol) => {
+                        flags |= leaf_flag;
+                        table.push(symbol);
+                    },
+                    Node::Internal(..) => {
+                        pending.push((child, child_address));
+                        table.push(0);
+                    },
+                }
+            }
+            table[address] = flags;
+        }
+
+        // The bitstream has to be aligned to 4 bytes, just like the header
+        if table.len() % 4 != 0 {
+            table.resize(table.len() + 2, 0);
+        }
+        table[0] = (table.len() / 2 - 1) as u8;
+
+        Some(table)
+    }
+}
+
+fn symbols(data: &[u8], size: SymbolSize) -> impl Iterator<Item = u8> + '_ {
+    data.iter().flat_map(move |byte| {
+        if size == SymbolSize::Bits8 {
+            [Some(*byte), None]
+        } else {
+            [Some(byte & 0xF), Some(byte >> 4)]
+        }
+        .into_iter()
+        .flatten()
+    })
+}
+
+/// Compresses `data` split into symbols of `size`
+pub fn compress(data: &[u8], size: SymbolSize) -> Vec<u8> {
+    let mut frequencies = vec![0; 1 << size.bits()];
+    for symbol in symbols(data, size) {
+        frequencies[symbol as usize] += 1;
+    }
+
+    // Flattening the frequencies makes the tree more balanced until it fits
+    let (tree, table) = loop {
+        let tree = Tree::build(&frequencies);
+        match tree.layout() {
+            Some(table) => break (tree, table),
+            None => frequencies
+                .iter_mut()
+                .filter(|frequency| **frequency != 0)
+                .for_each(|frequency| *frequency = *frequency / 2 + 1),
+        }
+    };
+    let codes = tree.codes();
+
+    let mut out = Vec::with_capacity(8 + table.len() + data.len());
+    Header {
+        format: size.format(),
+        decompressed_size: data.len(),
+    }
+    .write(&mut out);
+    out.extend_from_slice(&table);
+
+    let mut word = 0u32;
+    let mut used = 0;
+    for symbol in symbols(data, size) {
+        for bit in &codes[symbol as usize] {
+            word = (word << 1) | *bit as u32;
+            used += 1;
+            if used == 32 {
+                out.extend_from_slice(&word.to_le_bytes());
+                word = 0;
+                used = 0;
+            }
+        }
+    }
+    if used != 0 {
+        out.extend_from_slice(&(word << (32 - used)).to_le_bytes());
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::{tests, Format};
+
+    #[test]
+    fn round_trip() {
+        tests::round_trip(Format::Huffman4);
+        tests::round_trip(Format::Huffman8);
+    }
+
+    #[test]
+    fn malformed() {
+        tests::malformed(Format::Huffman4);
+        tests::malformed(Format::Huffman8);
+    }
+}
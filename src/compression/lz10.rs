@@ -0,0 +1,96 @@
+//! LZ77 with 12 bit displacements and lengths of 3 to 18 bytes.
+//!
+//! Every flag byte is followed by 8 tokens, starting with the most significant
+//! bit. Set bits mark a reference of two bytes:
+//!
+//! - bits 12-15: length - 3
+//! - bits 0-11: displacement - 1
+
+use crate::{
+    compression::{copy_back, output_buffer, pad, FlagWriter, Format, Header, Input, MatchFinder},
+    error::Result,
+};
+use std::io::Read;
+
+const MIN_LENGTH: usize = 3;
+const MAX_LENGTH: usize = 0x12;
+/// References to the previous byte can't be decompressed to VRAM, which is
+/// written 16 bits at a time, so the encoder never uses them
+const MIN_DISPLACEMENT: usize = 2;
+const MAX_DISPLACEMENT: usize = 0x1000;
+
+pub(crate) fn decode<R: Read>(input: &mut Input<R>, size: usize) -> Result<Vec<u8>> {
+    let mut output = output_buffer(size);
+
+    while output.len() < size {
+        let flags = input.u8()?;
+
+        for bit in (0..8).rev() {
+            if output.len() >= size {
+                break;
+            }
+
+            if flags & (1 << bit) == 0 {
+                output.push(input.u8()?);
+            } else {
+                let value = u16::from_be_bytes(input.bytes()?) as usize;
+                let length = (value >> 12) + MIN_LENGTH;
+                let displacement = (value & 0xFFF) + 1;
+
+                if !copy_back(&mut output, displacement, length, size) {
+                    return Err(input.invalid("a reference points before the start of the data"));
+                }
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 8 + 8);
+    Header {
+        format: Format::Lz10,
+        decompressed_size: data.len(),
+    }
+    .write(&mut out);
+
+    let mut finder = MatchFinder::new(data.len(), MIN_DISPLACEMENT, MAX_DISPLACEMENT, MAX_LENGTH);
+    let mut writer = FlagWriter::new(out);
+
+    let mut position = 0;
+    while position < data.len() {
+        let (length, displacement) = finder.find(data, position, data.len());
+        if length >= MIN_LENGTH {
+            writer.token(true);
+            let value = ((length - MIN_LENGTH) << 12) | (displacement - 1);
+            writer.out.extend_from_slice(&(value as u16).to_be_bytes());
+
+            finder.insert_range(data, position, length);
+            position += length;
+        } else {
+            writer.token(false);
+            writer.out.push(data[position]);
+
+            finder.insert(data, position);
+            position += 1;
+        }
+    }
+
+    pad(writer.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::{tests, Format};
+
+    #[test]
+    fn round_trip() {
+        tests::round_trip(Format::Lz10);
+    }
+
+    #[test]
+    fn malformed() {
+        tests::malformed(Format::Lz10);
+    }
+}
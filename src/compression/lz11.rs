@@ -0,0 +1,118 @@
+//! LZ77 with 12 bit displacements and lengths of up to 0x10110 bytes.
+//!
+//! Tokens are grouped behind flag bytes as in [lz10](super::lz10), but
+//! references come in three sizes, selected by the top 4 bits of the first
+//! byte:
+//!
+//! - 0: 8 bit length - 0x11, 12 bit displacement - 1
+//! - 1: 16 bit length - 0x111, 12 bit displacement - 1
+//! - otherwise: those 4 bits are length - 1, followed by a 12 bit displacement
+//!   - 1
+
+use crate::{
+    compression::{copy_back, output_buffer, pad, FlagWriter, Format, Header, Input, MatchFinder},
+    error::Result,
+};
+use std::io::Read;
+
+const MIN_LENGTH: usize = 3;
+const MAX_LENGTH: usize = 0xFFFF + 0x111;
+const MAX_DISPLACEMENT: usize = 0x1000;
+
+pub(crate) fn decode<R: Read>(input: &mut Input<R>, size: usize) -> Result<Vec<u8>> {
+    let mut output = output_buffer(size);
+
+    while output.len() < size {
+        let flags = input.u8()?;
+
+        for bit in (0..8).rev() {
+            if output.len() >= size {
+                break;
+            }
+
+            if flags & (1 << bit) == 0 {
+                output.push(input.u8()?);
+                continue;
+            }
+
+            let first = input.u8()? as usize;
+            let (length, displacement) = match first >> 4 {
+                0 => {
+                    let [b1, b2] = input.bytes()?.map(usize::from);
+                    let length = (((first & 0xF) << 4) | (b1 >> 4)) + 0x11;
+                    (length, ((b1 & 0xF) << 8) | b2)
+                },
+                1 => {
+                    let [b1, b2, b3] = input.bytes()?.map(usize::from);
+                    let length = (((first & 0xF) << 12) | (b1 << 4) | (b2 >> 4)) + 0x111;
+                    (length, ((b2 & 0xF) << 8) | b3)
+                },
+                short => {
+                    let b1 = input.u8()? as usize;
+                    (short + 1, ((first & 0xF) << 8) | b1)
+                },
+            };
+
+            if !copy_back(&mut output, displacement + 1, length, size) {
+                return Err(input.invalid("a reference points before the start of the data"));
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 8 + 8);
+    Header {
+        format: Format::Lz11,
+        decompressed_size: data.len(),
+    }
+    .write(&mut out);
+
+    let mut finder = MatchFinder::new(data.len(), 1, MAX_DISPLACEMENT, MAX_LENGTH);
+    let mut writer = FlagWriter::new(out);
+
+    let mut position = 0;
+    while position < data.len() {
+        let (length, displacement) = finder.find(data, position, data.len());
+        if length >= MIN_LENGTH {
+            writer.token(true);
+            let displacement = displacement - 1;
+            let (reference, size) = match length {
+                0..=0x10 => (((length - 1) << 12) | displacement, 2),
+                0x11..=0x110 => (((length - 0x11) << 12) | displacement, 3),
+                _ => ((1 << 28) | ((length - 0x111) << 12) | displacement, 4),
+            };
+
+            let bytes = (reference as u32).to_be_bytes();
+            writer.out.extend_from_slice(&bytes[(4 - size)..]);
+
+            finder.insert_range(data, position, length);
+            position += length;
+        } else {
+            writer.token(false);
+            writer.out.push(data[position]);
+
+            finder.insert(data, position);
+            position += 1;
+        }
+    }
+
+    pad(writer.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::{tests, Format};
+
+    #[test]
+    fn round_trip() {
+        tests::round_trip(Format::Lz11);
+    }
+
+    #[test]
+    fn malformed() {
+        tests::malformed(Format::Lz11);
+    }
+}
@@ -0,0 +1,496 @@
+//! Compression formats used by the DS.
+//!
+//! Except for [blz], all formats are the ones the BIOS can decompress.
+//! Their data starts with a header:
+//!
+//! - bits 0-3: format specific, the symbol size for Huffman
+//! - bits 4-7: the format
+//! - bits 8-31: decompressed size, if it is 0 another `u32` with the size
+//!   follows
+
+pub mod blz;
+pub mod huffman;
+pub mod lz10;
+pub mod lz11;
+pub mod rle;
+
+use crate::{
+    compression::huffman::SymbolSize,
+    error::{Error, Result},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{Display, Formatter},
+    io::{ErrorKind, Read},
+};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Format {
+    /// Backwards LZ as used for ARM9 binaries and overlays
+    Blz,
+    Lz10,
+    Lz11,
+    /// Huffman coding of 4 bit symbols
+    Huffman4,
+    /// Huffman coding of 8 bit symbols
+    Huffman8,
+    Rle,
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Format::Blz => "backwards LZ",
+            Format::Lz10 => "LZ10",
+            Format::Lz11 => "LZ11",
+            Format::Huffman4 => "4 bit Huffman",
+            Format::Huffman8 => "8 bit Huffman",
+            Format::Rle => "RLE",
+        })
+    }
+}
+
+impl Format {
+    /// Formats that start with a header and can be detected
+    pub const WITH_HEADER: [Format; 5] = [
+        Format::Lz10,
+        Format::Lz11,
+        Format::Huffman4,
+        Format::Huffman8,
+        Format::Rle,
+    ];
+
+    /// The first byte of the header, [Format::Blz] has none
+    pub fn type_byte(self) -> Option<u8> {
+        match self {
+            Format::Blz => None,
+            Format::Lz10 => Some(0x10),
+            Format::Lz11 => Some(0x11),
+            Format::Huffman4 => Some(0x24),
+            Format::Huffman8 => Some(0x28),
+            Format::Rle => Some(0x30),
+        }
+    }
+
+    pub fn from_type_byte(type_byte: u8) -> Option<Self> {
+        Self::WITH_HEADER
+            .into_iter()
+            .find(|format| format.type_byte() == Some(type_byte))
+    }
+}
+
+/// Start of the compressed data
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Header {
+    pub format: Format,
+    pub decompressed_size: usize,
+}
+
+impl Header {
+    /// Decompressed sizes above this need the extended header
+    const MAX_SHORT_SIZE: usize = 0x00FF_FFFF;
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut word = [0; 4];
+        reader.read_exact(&mut word).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => Error::UnknownCompression { type_byte: None },
+            _ => e.into(),
+        })?;
+
+        let format = Format::from_type_byte(word[0]).ok_or(Error::UnknownCompression {
+            type_byte: Some(word[0]),
+        })?;
+
+        let mut decompressed_size = (u32::from_le_bytes(word) >> 8) as usize;
+        if decompressed_size == 0 {
+            let mut input = Input::new(reader, format);
+            decompressed_size = input.u32()? as usize;
+        }
+
+        Ok(Self {
+            format,
+            decompressed_size,
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let type_byte = self.format.type_byte().expect("format has a header") as u32;
+        if self.decompressed_size == 0 || self.decompressed_size > Self::MAX_SHORT_SIZE {
+            out.extend_from_slice(&type_byte.to_le_bytes());
+            out.extend_from_slice(&(self.decompressed_size as u32).to_le_bytes());
+        } else {
+            out.extend_from_slice(
+                &(type_byte | ((self.decompressed_size as u32) << 8)).to_le_bytes(),
+            );
+        }
+    }
+}
+
+/// Reads compressed data, reporting the end of the input as invalid data
+pub(crate) struct Input<R> {
+    reader: R,
+    format: Format,
+}
+
+impl<R: Read> Input<R> {
+    pub(crate) fn new(reader: R, format: Format) -> Self {
+        Self { reader, format }
+    }
+
+    pub(crate) fn invalid(&self, reason: &'static str) -> Error {
+        Error::InvalidCompressedData {
+            format: self.format,
+            reason,
+        }
+    }
+
+    pub(crate) fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buffer = [0; N];
+        match self.reader.read_exact(&mut buffer) {
+            Ok(()) => Ok(buffer),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                Err(self.invalid("the compressed data ends early"))
+            },
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+}
+
+/// Creates the buffer for decompressed data without trusting the size from the
+/// header too much
+pub(crate) fn output_buffer(size: usize) -> Vec<u8> {
+    Vec::with_capacity(size.min(0x100_0000))
+}
+
+/// Appends `length` bytes starting `displacement` bytes before the end of
+/// `output`, without growing it past `size`.
+///
+/// Returns false if the reference points before the start of the output.
+pub(crate) fn copy_back(
+    output: &mut Vec<u8>,
+    displacement: usize,
+    length: usize,
+    size: usize,
+) -> bool {
+    if displacement == 0 || displacement > output.len() {
+        return false;
+    }
+
+    let length = length.min(size - output.len());
+    for _ in 0..length {
+        output.push(output[output.len() - displacement]);
+    }
+    true
+}
+
+/// Decompresses data that starts with a [Header].
+///
+/// The input is read one byte at a time, so readers that are not in memory
+/// should be buffered.
+pub fn decompress<R: Read>(mut reader: R) -> Result<Vec<u8>> {
+    let header = Header::read(&mut reader)?;
+    decompress_with_header(&mut Input::new(reader, header.format), header)
+}
+
+fn decompress_with_header<R: Read>(input: &mut Input<R>, header: Header) -> Result<Vec<u8>> {
+    let size = header.decompressed_size;
+    match header.format {
+        Format::Blz => unreachable!("backwards LZ has no header"),
+        Format::Lz10 => lz10::decode(input, size),
+        Format::Lz11 => lz11::decode(input, size),
+        Format::Huffman4 => huffman::decode(input, size, SymbolSize::Bits4),
+        Format::Huffman8 => huffman::decode(input, size, SymbolSize::Bits8),
+        Format::Rle => rle::decode(input, size),
+    }
+}
+
+pub fn compress(data: &[u8], format: Format) -> Vec<u8> {
+    match format {
+        Format::Blz => blz::compress(data, 0),
+        Format::Lz10 => lz10::compress(data),
+        Format::Lz11 => lz11::compress(data),
+        Format::Huffman4 => huffman::compress(data, SymbolSize::Bits4),
+        Format::Huffman8 => huffman::compress(data, SymbolSize::Bits8),
+        Format::Rle => rle::compress(data),
+    }
+}
+
+/// Checks whether `data` is compressed in one of the formats with a header and
+/// decompresses it if so.
+///
+/// Because the header is only a single byte plus the size, the data only
+/// counts as compressed if it decompresses without errors and not much input
+/// is left over afterwards.
+pub fn detect(data: &[u8]) -> Option<(Format, Vec<u8>)> {
+    const MAX_TRAILING: usize = 0x20;
+    // Anything larger is almost certainly a misdetected header
+    const MAX_SIZE: usize = 0x400_0000;
+
+    let mut reader = data;
+    let header = Header::read(&mut reader).ok()?;
+    if header.decompressed_size == 0 || header.decompressed_size > MAX_SIZE {
+        return None;
+    }
+
+    let mut input = Input::new(reader, header.format);
+    let decompressed = decompress_with_header(&mut input, header).ok()?;
+    let trailing = input.reader.len();
+
+    (trailing < MAX_TRAILING).then_some((header.format, decompressed))
+}
+
+/// Finds earlier occurrences through chains of positions with the same three
+/// byte prefix
+pub(crate) struct MatchFinder {
+    head: Vec<usize>,
+    previous: Vec<usize>,
+    min_displacement: usize,
+    max_displacement: usize,
+    max_length: usize,
+}
+
+impl MatchFinder {
+    const CHAIN_LIMIT: usize = 256;
+    pub(crate) const MIN_LENGTH: usize = 3;
+    const NONE: usize = usize::MAX;
+
+    pub(crate) fn new(
+        length: usize,
+        min_displacement: usize,
+        max_displacement: usize,
+        max_length: usize,
+    ) -> Self {
+        Self {
+            head: vec![Self::NONE; 1 << 16],
+            previous: vec![Self::NONE; length],
+            min_displacement,
+            max_displacement,
+            max_length,
+        }
+    }
+
+    fn hash(data: &[u8], position: usize) -> usize {
+        let a = data[position] as usize;
+        let b = data[position + 1] as usize;
+        let c = data[position + 2] as usize;
+        ((a << 8) ^ (b << 4) ^ c ^ (a >> 3)) & 0xFFFF
+    }
+
+    pub(crate) fn insert(&mut self, data: &[u8], position: usize) {
+        if position + Self::MIN_LENGTH <= data.len() {
+            let hash = Self::hash(data, position);
+            self.previous[position] = self.head[hash];
+            self.head[hash] = position;
+        }
+    }
+
+    pub(crate) fn insert_range(&mut self, data: &[u8], position: usize, length: usize) {
+        for p in position..(position + length) {
+            self.insert(data, p);
+        }
+    }
+
+    /// Returns (length, displacement) of the longest match for `position` that
+    /// doesn't extend past `end`
+    pub(crate) fn find(&self, data: &[u8], position: usize, end: usize) -> (usize, usize) {
+        let max_length = self.max_length.min(end - position);
+        let mut best = (0, 0);
+        if max_length < Self::MIN_LENGTH {
+            return best;
+        }
+
+        let mut candidate = self.head[Self::hash(data, position)];
+        let mut steps = 0;
+        while candidate != Self::NONE && steps < Self::CHAIN_LIMIT {
+            let displacement = position - candidate;
+            if displacement > self.max_displacement {
+                break;
+            }
+            if displacement >= self.min_displacement {
+                let length = (0..max_length)
+                    .take_while(|i| data[candidate + i] == data[position + i])
+                    .count();
+                if length > best.0 {
+                    best = (length, displacement);
+                    if length == max_length {
+                        break;
+                    }
+                }
+            }
+
+            candidate = self.previous[candidate];
+            steps += 1;
+        }
+
+        best
+    }
+}
+
+/// Groups tokens behind flag bytes, most significant bit first, as all LZ
+/// formats do
+pub(crate) struct FlagWriter {
+    pub(crate) out: Vec<u8>,
+    flag_position: usize,
+    flag_bit: u8,
+}
+
+impl FlagWriter {
+    pub(crate) fn new(out: Vec<u8>) -> Self {
+        Self {
+            out,
+            flag_position: 0,
+            flag_bit: 0,
+        }
+    }
+
+    /// Reserves space for a new flag byte if needed and sets the bit for the
+    /// next token
+    pub(crate) fn token(&mut self, flag: bool) {
+        if self.flag_bit == 0 {
+            self.flag_position = self.out.len();
+            self.out.push(0);
+            self.flag_bit = 0x80;
+        }
+        if flag {
+            self.out[self.flag_position] |= self.flag_bit;
+        }
+        self.flag_bit >>= 1;
+    }
+}
+
+/// Pads compressed data to a multiple of 4 bytes
+pub(crate) fn pad(mut out: Vec<u8>) -> Vec<u8> {
+    out.resize(out.len().div_ceil(4) * 4, 0);
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Deterministic bytes from a xorshift generator
+    pub(crate) fn random(seed: u32, length: usize) -> Vec<u8> {
+        let mut state = seed.max(1);
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    /// Random bytes where roughly one in `repeat` positions starts a copy of
+    /// earlier data, so that they compress a little or a lot
+    pub(crate) fn repetitive(seed: u32, length: usize, repeat: u32) -> Vec<u8> {
+        let noise = random(seed, length * 2);
+        let mut data = Vec::with_capacity(length);
+        let mut index = 0;
+        while data.len() < length {
+            let byte = noise[index] as u32;
+            index = (index + 2) % noise.len();
+            if data.len() > 8 && byte % repeat == 0 {
+                let back = 1 + noise[index] as usize % data.len().min(64);
+                let count = 3 + noise[index + 1] as usize % 8;
+                for _ in 0..count.min(length - data.len()) {
+                    data.push(data[data.len() - back]);
+                }
+            } else {
+                data.push(byte as u8);
+            }
+        }
+        data
+    }
+
+    /// Inputs that are short, don't compress, barely compress or compress well
+    pub(crate) fn samples() -> Vec<Vec<u8>> {
+        let mut samples = vec![
+            vec![],
+            vec![0x42],
+            vec![1, 2],
+            vec![7; 3],
+            vec![0; 0x1000],
+            // Longer than the longest LZ11 reference
+            vec![0; 0x11000],
+            b"abcabcabcabcabcabcabcabcabc".to_vec(),
+            (0..=255).collect(),
+            random(1, 17),
+            random(2, 0x400),
+            vec![0xAA; 0x300],
+        ];
+        for seed in 1..40 {
+            samples.push(repetitive(seed, 10 + seed as usize * 13, 2 + seed % 12));
+        }
+        samples
+    }
+
+    /// Compresses every sample and checks that it decompresses to the same
+    /// bytes, and that truncated data is rejected without panicking
+    pub(crate) fn round_trip(format: Format) {
+        for data in samples() {
+            let compressed = compress(&data, format);
+            assert_eq!(compressed.len() % 4, 0, "{format} output is not padded");
+            assert_eq!(decompress(compressed.as_slice()).unwrap(), data);
+            if !data.is_empty() {
+                assert_eq!(detect(&compressed), Some((format, data.clone())));
+            }
+
+            for length in (0..compressed.len()).step_by(compressed.len() / 64 + 1) {
+                let _ = decompress(&compressed[..length]);
+            }
+            if !data.is_empty() {
+                assert!(decompress(&compressed[..4]).is_err());
+            }
+        }
+    }
+
+    /// Decompressing garbage behind a valid header must fail or succeed, but
+    /// not panic
+    pub(crate) fn malformed(format: Format) {
+        let type_byte = format.type_byte().unwrap();
+        for seed in 1..200 {
+            let mut data = random(seed, 4 + seed as usize % 64);
+            data[0] = type_byte;
+            // Keep the size small so the output stays bounded
+            data[2] = 0;
+            data[3] = 0;
+            let _ = decompress(data.as_slice());
+        }
+    }
+
+    #[test]
+    fn header_sizes() {
+        for decompressed_size in [1, Header::MAX_SHORT_SIZE, Header::MAX_SHORT_SIZE + 1] {
+            let header = Header {
+                format: Format::Lz10,
+                decompressed_size,
+            };
+            let mut out = vec![];
+            header.write(&mut out);
+            assert_eq!(Header::read(&mut out.as_slice()).unwrap(), header);
+        }
+    }
+
+    #[test]
+    fn unknown_type() {
+        assert!(matches!(
+            decompress([0x50, 1, 0, 0].as_slice()),
+            Err(Error::UnknownCompression {
+                type_byte: Some(0x50)
+            })
+        ));
+        assert!(matches!(
+            decompress([].as_slice()),
+            Err(Error::UnknownCompression { type_byte: None })
+        ));
+    }
+}
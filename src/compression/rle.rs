@@ -0,0 +1,92 @@
+//! Run length encoding.
+//!
+//! Each block starts with a flag byte. If bit 7 is set, the next byte is
+//! repeated (bits 0-6) + 3 times, otherwise (bits 0-6) + 1 bytes are copied.
+
+use crate::{
+    compression::{output_buffer, pad, Format, Header, Input},
+    error::Result,
+};
+use std::io::Read;
+
+const MIN_RUN: usize = 3;
+const MAX_RUN: usize = 0x7F + MIN_RUN;
+const MAX_LITERALS: usize = 0x80;
+
+pub(crate) fn decode<R: Read>(input: &mut Input<R>, size: usize) -> Result<Vec<u8>> {
+    let mut output = output_buffer(size);
+
+    while output.len() < size {
+        let flag = input.u8()? as usize;
+        let remaining = size - output.len();
+
+        if flag & 0x80 != 0 {
+            let byte = input.u8()?;
+            let length = ((flag & 0x7F) + MIN_RUN).min(remaining);
+            output.resize(output.len() + length, byte);
+        } else {
+            for _ in 0..((flag & 0x7F) + 1).min(remaining) {
+                output.push(input.u8()?);
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+fn run_length(data: &[u8]) -> usize {
+    data.iter()
+        .take(MAX_RUN)
+        .take_while(|byte| **byte == data[0])
+        .count()
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_LITERALS + 8);
+    Header {
+        format: Format::Rle,
+        decompressed_size: data.len(),
+    }
+    .write(&mut out);
+
+    let mut literals_start = 0;
+    let mut position = 0;
+    let flush_literals = |out: &mut Vec<u8>, literals: &[u8]| {
+        for chunk in literals.chunks(MAX_LITERALS) {
+            out.push((chunk.len() - 1) as u8);
+            out.extend_from_slice(chunk);
+        }
+    };
+
+    while position < data.len() {
+        let run = run_length(&data[position..]);
+        if run >= MIN_RUN {
+            flush_literals(&mut out, &data[literals_start..position]);
+            out.push(0x80 | (run - MIN_RUN) as u8);
+            out.push(data[position]);
+
+            position += run;
+            literals_start = position;
+        } else {
+            position += run;
+        }
+    }
+    flush_literals(&mut out, &data[literals_start..]);
+
+    pad(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::{tests, Format};
+
+    #[test]
+    fn round_trip() {
+        tests::round_trip(Format::Rle);
+    }
+
+    #[test]
+    fn malformed() {
+        tests::malformed(Format::Rle);
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// CRC-16 as used by the DS BIOS (CRC-16/MODBUS: reflected polynomial 0xA001,
+/// initial value 0xFFFF)
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |mut crc, byte| {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+        crc
+    })
+}
+
+/// A checksum as stored in the ROM next to the value computed from the data it
+/// covers
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Checksum {
+    pub stored: u16,
+    pub computed: u16,
+}
+
+impl Checksum {
+    pub fn is_valid(&self) -> bool {
+        self.stored == self.computed
+    }
+}
@@ -0,0 +1,186 @@
+use crate::compression::Format;
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+/// The structure that was being read when an [Error] occurred
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Table {
+    Header,
+    FileNameTable,
+    DirectoryMainTable,
+    SubTable,
+    FileAllocationTable,
+    Arm9OverlayTable,
+    Arm7OverlayTable,
+    Banner,
+    TwlHeader,
+    Arm9i,
+    Arm7i,
+    File,
+    Narc,
+    Nclr,
+    Ncgr,
+    Nscr,
+    Ncer,
+    Nanr,
+    Nmcr,
+    Nmar,
+    Sdat,
+    Swav,
+    Swar,
+    Strm,
+    Sseq,
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Table::Header => "cartridge header",
+            Table::FileNameTable => "file name table",
+            Table::DirectoryMainTable => "directory main table",
+            Table::SubTable => "directory sub-table",
+            Table::FileAllocationTable => "file allocation table",
+            Table::Arm9OverlayTable => "ARM9 overlay table",
+            Table::Arm7OverlayTable => "ARM7 overlay table",
+            Table::Banner => "icon/title banner",
+            Table::TwlHeader => "DSi extended header",
+            Table::Arm9i => "ARM9i binary",
+            Table::Arm7i => "ARM7i binary",
+            Table::File => "file",
+            Table::Narc => "NARC archive",
+            Table::Nclr => "NCLR palette",
+            Table::Ncgr => "NCGR graphics",
+            Table::Nscr => "NSCR screen",
+            Table::Ncer => "NCER cell bank",
+            Table::Nanr => "NANR cell animation",
+            Table::Nmcr => "NMCR multi-cell bank",
+            Table::Nmar => "NMAR multi-cell animation",
+            Table::Sdat => "SDAT sound archive",
+            Table::Swav => "SWAV sample",
+            Table::Swar => "SWAR wave archive",
+            Table::Strm => "STRM stream",
+            Table::Sseq => "SSEQ sequence",
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(
+        "{table} at {offset:#x} needs {expected:#x} bytes, but only {actual:#x} are available"
+    )]
+    OutOfBounds {
+        table: Table,
+        offset: usize,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("{table} at {offset:#x} is {size:#x} bytes long, which is not a multiple of the entry size {entry_size:#x}")]
+    Misaligned {
+        table: Table,
+        offset: usize,
+        size: usize,
+        entry_size: usize,
+    },
+
+    #[error("{table} entry at {offset:#x} has a name of length {length}, which exceeds the remaining {actual} bytes")]
+    InvalidNameLength {
+        table: Table,
+        offset: usize,
+        length: usize,
+        actual: usize,
+    },
+
+    #[error("{table} entry at {offset:#x} references directory {directory_id:#x}, but only {count} directories exist")]
+    InvalidDirectory {
+        table: Table,
+        offset: usize,
+        directory_id: u16,
+        count: usize,
+    },
+
+    #[error("{table} has {count} entries, so file ID {file_id} is invalid")]
+    InvalidFileId {
+        table: Table,
+        file_id: usize,
+        count: usize,
+    },
+
+    #[error("file ID {file_id} comes after the first named file, but has no name")]
+    UnnamedFile { file_id: usize },
+
+    #[error("no file named {path:?}")]
+    FileNotFound { path: String },
+
+    #[error("{path:?} already exists")]
+    AlreadyExists { path: String },
+
+    #[error("{path:?} is not a directory")]
+    NotADirectory { path: String },
+
+    #[error("{path:?} does not name an entry")]
+    InvalidPath { path: String },
+
+    #[error("the name {name:?} is longer than 127 bytes")]
+    NameTooLong { name: String },
+
+    #[error("the filesystem contains {count} files, but file IDs only go up to 0xEFFF")]
+    TooManyFiles { count: usize },
+
+    #[error("the filesystem contains {count} directories, but at most 0x1000 are possible")]
+    TooManyDirectories { count: usize },
+
+    #[error("the ROM would be {size:#x} bytes long, which exceeds the largest cartridge")]
+    RomTooLarge { size: usize },
+
+    #[error("invalid {format} data: {reason}")]
+    InvalidCompressedData {
+        format: Format,
+        reason: &'static str,
+    },
+
+    #[error("{table} uses the unsupported format {value:#x}")]
+    UnsupportedFormat { table: Table, value: u32 },
+
+    #[error("the ARM9 binary does not contain the NitroSDK module parameters")]
+    MissingModuleParams,
+
+    #[error("a KEY1 key table is {:#x} bytes long, or {:#x} as part of the ARM7 BIOS, but got {size:#x} bytes", crate::secure_area::KEY_TABLE_SIZE, 0x4000)]
+    InvalidKeyTable { size: usize },
+
+    #[error("invalid secure area: {reason}")]
+    InvalidSecureArea { reason: &'static str },
+
+    #[error("the ROM contains data at {offset:#x}, after its used size")]
+    UnexpectedData { offset: usize },
+
+    #[error("unknown device capacity {raw:#04x}")]
+    UnknownDeviceCapacity { raw: u8 },
+
+    #[error("{}", match type_byte {
+        Some(type_byte) => format!("unknown compression type {:#04x}", type_byte),
+        None => "the data is too short for a compression header".to_string(),
+    })]
+    UnknownCompression { type_byte: Option<u8> },
+
+    #[error(
+        "{table} at {offset:#x} should start with {:?}, but starts with {:?}",
+        String::from_utf8_lossy(expected),
+        String::from_utf8_lossy(actual)
+    )]
+    InvalidMagic {
+        table: Table,
+        offset: usize,
+        expected: [u8; 4],
+        actual: [u8; 4],
+    },
+
+    #[error("{table} has no {:?} section", String::from_utf8_lossy(magic))]
+    MissingSection { table: Table, magic: [u8; 4] },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -1,9 +1,12 @@
-use crate::{byte_types::int::U32, cartridge_header::CartridgeHeader};
+use crate::{
+    byte_types::int::U32,
+    cartridge_header::CartridgeHeader,
+    error::{Error, Result, Table},
+};
 use byteorder::LittleEndian;
 use serde::{Deserialize, Serialize};
 use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
 
-
 #[repr(C)]
 #[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Debug, Serialize, Deserialize)]
 pub struct FileAllocationTableEntry {
@@ -15,22 +18,64 @@ impl FileAllocationTableEntry {
     pub fn read_fat<'lt>(
         header: &CartridgeHeader,
         rom: &'lt [u8],
-    ) -> Option<LayoutVerified<&'lt [u8], [FileAllocationTableEntry]>> {
+    ) -> Result<LayoutVerified<&'lt [u8], [FileAllocationTableEntry]>> {
         let base = header.fat.offset.get() as usize;
         let size = header.fat.size.get() as usize;
 
         static DUMMY: &[u8] = &[];
         if size == 0 {
-            return LayoutVerified::new_slice(DUMMY);
+            return Ok(LayoutVerified::new_slice(DUMMY).unwrap());
         }
 
-        let fat_raw = rom.get(base..(base + size))?;
+        let fat_raw = rom.get(base..(base + size)).ok_or(Error::OutOfBounds {
+            table: Table::FileAllocationTable,
+            offset: base,
+            expected: size,
+            actual: rom.len().saturating_sub(base),
+        })?;
+
+        Self::parse_fat(fat_raw, base)
+    }
 
-        let fat = LayoutVerified::<_, [FileAllocationTableEntry]>::new_slice(fat_raw)?;
-        Some(fat)
+    /// Interprets `fat_raw`, read from `base`, as a FAT
+    pub(crate) fn parse_fat(
+        fat_raw: &[u8],
+        base: usize,
+    ) -> Result<LayoutVerified<&[u8], [FileAllocationTableEntry]>> {
+        LayoutVerified::<_, [FileAllocationTableEntry]>::new_slice(fat_raw).ok_or(
+            Error::Misaligned {
+                table: Table::FileAllocationTable,
+                offset: base,
+                size: fat_raw.len(),
+                entry_size: std::mem::size_of::<FileAllocationTableEntry>(),
+            },
+        )
     }
 
     pub fn get_file(self, rom: &[u8]) -> Option<&[u8]> {
         rom.get((self.start.get() as usize)..(self.end.get() as usize))
     }
+
+    /// Looks up the entry for `file_id` in `fat` and returns its data
+    pub fn read_file<'lt>(
+        fat: &[FileAllocationTableEntry],
+        file_id: usize,
+        rom: &'lt [u8],
+    ) -> Result<&'lt [u8]> {
+        let entry = fat.get(file_id).ok_or(Error::InvalidFileId {
+            table: Table::FileAllocationTable,
+            file_id,
+            count: fat.len(),
+        })?;
+
+        entry.get_file(rom).ok_or_else(|| {
+            let start = entry.start.get() as usize;
+            Error::OutOfBounds {
+                table: Table::File,
+                offset: start,
+                expected: (entry.end.get() as usize).saturating_sub(start),
+                actual: rom.len().saturating_sub(start),
+            }
+        })
+    }
 }
@@ -1,6 +1,13 @@
-use crate::byte_types::{
-    embedded_string::{DynamicEmbeddedString, EmbeddedStringMake},
-    int::{U16, U32},
+use crate::{
+    byte_types::{
+        embedded_string::{DynamicEmbeddedString, EmbeddedStringCommon, EmbeddedStringMake},
+        int::{U16, U32},
+    },
+    error::{Error, Result, Table},
+    file::{
+        traversal::{Entries, Order},
+        DirId, FileId,
+    },
 };
 use byteorder::LittleEndian;
 use derivative::Derivative;
@@ -26,14 +33,27 @@ pub struct DirectoryMainTableEntry {
 pub struct DirectoryMainTable<'lt>(pub &'lt [DirectoryMainTableEntry]);
 
 impl<'lt> DirectoryMainTable<'lt> {
-    pub fn wrap(slice: &'lt [u8]) -> Option<Self> {
-        let (head, _) = LayoutVerified::<_, DirectoryMainTableEntry>::new_from_prefix(slice)?;
+    /// Wraps the main table starting at `start` in `file`
+    pub fn wrap(file: &'lt [u8], start: usize) -> Result<Self> {
+        const ENTRY_SIZE: usize = std::mem::size_of::<DirectoryMainTableEntry>();
+
+        let out_of_bounds = |expected| Error::OutOfBounds {
+            table: Table::DirectoryMainTable,
+            offset: start,
+            expected,
+            actual: file.len().saturating_sub(start),
+        };
+
+        let slice = file.get(start..).ok_or_else(|| out_of_bounds(ENTRY_SIZE))?;
+        let (head, _) = LayoutVerified::<_, DirectoryMainTableEntry>::new_from_prefix(slice)
+            .ok_or_else(|| out_of_bounds(ENTRY_SIZE))?;
         let total = head.total_or_parent.get() as usize;
         let values =
             LayoutVerified::<_, [DirectoryMainTableEntry]>::new_slice_from_prefix(slice, total)
-                .map(|(values, _)| values.into_slice())?;
+                .map(|(values, _)| values.into_slice())
+                .ok_or_else(|| out_of_bounds(total * ENTRY_SIZE))?;
 
-        Some(Self(values))
+        Ok(Self(values))
     }
 }
 
@@ -50,26 +70,49 @@ pub enum SubTableEntry {
 }
 
 impl SubTableEntry {
-    pub fn parse(slice: &[u8]) -> Option<(Self, &[u8])> {
+    /// Parses the entry at the start of `slice`, which lies at `offset` in the
+    /// ROM.
+    ///
+    /// Returns `None` at the end of the sub-table.
+    pub fn parse(slice: &[u8], offset: usize) -> Result<Option<(Self, &[u8])>> {
         const DIRECTORY_BIT: u8 = 0x80;
 
-        let marker = *slice.get(0)?;
+        let marker = *slice.first().ok_or(Error::OutOfBounds {
+            table: Table::SubTable,
+            offset,
+            expected: 1,
+            actual: 0,
+        })?;
 
         let directory = marker & DIRECTORY_BIT != 0;
         let length = (marker & !DIRECTORY_BIT) as usize;
 
         if length == 0 {
-            return None;
+            return Ok(None);
         }
 
-        let slice = slice.get(1..)?;
-        let name = slice.get(..length)?;
-        let name = DynamicEmbeddedString::from_slice(name)?;
-        let slice = slice.get(length..)?;
+        let slice = &slice[1..];
+        let name = slice
+            .get(..length)
+            .and_then(DynamicEmbeddedString::from_slice)
+            .ok_or(Error::InvalidNameLength {
+                table: Table::SubTable,
+                offset,
+                length,
+                actual: slice.len(),
+            })?;
+        let slice = &slice[length..];
 
-        Some(if directory {
-            let (directory_id, slice) =
-                LayoutVerified::<_, U16<LittleEndian>>::new_from_prefix(slice)?;
+        Ok(Some(if directory {
+            let (directory_id, slice) = LayoutVerified::<_, U16<LittleEndian>>::new_from_prefix(
+                slice,
+            )
+            .ok_or(Error::OutOfBounds {
+                table: Table::SubTable,
+                offset,
+                expected: 1 + length + 2,
+                actual: 1 + length + slice.len(),
+            })?;
 
             (
                 Self::DirectoryEntry {
@@ -80,7 +123,29 @@ impl SubTableEntry {
             )
         } else {
             (Self::FileEntry { name }, slice)
-        })
+        }))
+    }
+
+    /// Size of this entry in bytes
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            SubTableEntry::FileEntry { name } => 1 + name.len(),
+            SubTableEntry::DirectoryEntry { name, .. } => 1 + name.len() + 2,
+        }
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            SubTableEntry::FileEntry { name } => {
+                out.push(name.len() as u8);
+                out.extend_from_slice(name.data());
+            },
+            SubTableEntry::DirectoryEntry { name, directory_id } => {
+                out.push(name.len() as u8 | 0x80);
+                out.extend_from_slice(name.data());
+                out.extend_from_slice(directory_id.as_bytes());
+            },
+        }
     }
 }
 
@@ -91,36 +156,74 @@ pub struct FileNameTable {
 }
 
 impl FileNameTable {
-    pub fn read(file: &[u8], start: usize) -> Option<Self> {
-        let main_table = DirectoryMainTable::wrap(file.get(start..)?)?.0.to_vec();
+    pub fn read(file: &[u8], start: usize) -> Result<Self> {
+        let main_table = DirectoryMainTable::wrap(file, start)?.0.to_vec();
 
         let mut sub_tables = Vec::with_capacity(main_table.len());
 
         for entry in &main_table {
-            let mut sub_table_slice =
-                file.get((start + (entry.offset_to_sub_table.get() as usize))..)?;
+            let mut offset = start + (entry.offset_to_sub_table.get() as usize);
+            let mut sub_table_slice = file.get(offset..).ok_or(Error::OutOfBounds {
+                table: Table::SubTable,
+                offset,
+                expected: 1,
+                actual: 0,
+            })?;
             let mut sub_table = Vec::new();
 
-            loop {
-                match SubTableEntry::parse(sub_table_slice) {
-                    Some((sub_entry, tail)) => {
-                        sub_table.push(sub_entry);
-                        sub_table_slice = tail;
-                    },
-                    None => break,
+            while let Some((sub_entry, tail)) = SubTableEntry::parse(sub_table_slice, offset)? {
+                if let SubTableEntry::DirectoryEntry { directory_id, .. } = &sub_entry {
+                    let index = directory_id.get().wrapping_sub(0xF000) as usize;
+                    if index >= main_table.len() {
+                        return Err(Error::InvalidDirectory {
+                            table: Table::SubTable,
+                            offset,
+                            directory_id: directory_id.get(),
+                            count: main_table.len(),
+                        });
+                    }
                 }
+
+                offset += sub_entry.encoded_len();
+                sub_table.push(sub_entry);
+                sub_table_slice = tail;
             }
 
             sub_table.shrink_to_fit();
             sub_tables.push(sub_table);
         }
 
-        Some(Self {
+        Ok(Self {
             main_table,
             sub_tables,
         })
     }
 
+    /// Serializes the table, recomputing the sub-table offsets
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let main_table_size =
+            self.main_table.len() * std::mem::size_of::<DirectoryMainTableEntry>();
+
+        let mut main_table = Vec::with_capacity(main_table_size);
+        let mut sub_tables = Vec::new();
+
+        for (entry, sub_table) in self.main_table.iter().zip(&self.sub_tables) {
+            let mut entry = *entry;
+            entry
+                .offset_to_sub_table
+                .set((main_table_size + sub_tables.len()) as u32);
+            main_table.extend_from_slice(entry.as_bytes());
+
+            for sub_entry in sub_table {
+                sub_entry.write(&mut sub_tables);
+            }
+            sub_tables.push(0);
+        }
+
+        main_table.extend(sub_tables);
+        main_table
+    }
+
     fn walk_directory<'lt, F>(
         &'lt self,
         function: &mut F,
@@ -154,6 +257,107 @@ impl FileNameTable {
         }
     }
 
+    /// Finds the file at `path`, e.g. `/data/sound/sound_data.sdat`.
+    ///
+    /// Empty components are ignored, so the leading slash is optional.
+    pub fn lookup(&self, path: &str) -> Option<FileId> {
+        let components = path
+            .split('/')
+            .filter(|component| !component.is_empty())
+            .collect::<Vec<_>>();
+        let (file_name, directories) = components.split_last()?;
+
+        let mut directory = 0;
+        for component in directories {
+            directory = self
+                .sub_tables
+                .get(directory)?
+                .iter()
+                .find_map(|entry| match entry {
+                    SubTableEntry::DirectoryEntry { name, directory_id }
+                        if name.data() == component.as_bytes() =>
+                    {
+                        Some(DirId(directory_id.get()).index())
+                    },
+                    _ => None,
+                })?;
+        }
+
+        let mut file_id = self.main_table.get(directory)?.id_of_first_file.get();
+        for entry in self.sub_tables.get(directory)? {
+            if let SubTableEntry::FileEntry { name } = entry {
+                if name.data() == file_name.as_bytes() {
+                    return Some(FileId(file_id));
+                }
+                file_id = file_id.wrapping_add(1);
+            }
+        }
+
+        None
+    }
+
+    /// Returns the path of a file, e.g. `/data/sound/sound_data.sdat`
+    pub fn path_of(&self, file_id: FileId) -> Option<String> {
+        let mut names = Vec::new();
+        let mut directory_id = None;
+
+        for (directory, (meta, sub_table)) in
+            self.main_table.iter().zip(&self.sub_tables).enumerate()
+        {
+            let index = match file_id.0.checked_sub(meta.id_of_first_file.get()) {
+                Some(index) => index as usize,
+                None => continue,
+            };
+            let name = sub_table
+                .iter()
+                .filter_map(|entry| match entry {
+                    SubTableEntry::FileEntry { name } => Some(name),
+                    SubTableEntry::DirectoryEntry { .. } => None,
+                })
+                .nth(index);
+
+            if let Some(name) = name {
+                names.push(name);
+                directory_id = Some(directory);
+                break;
+            }
+        }
+
+        let mut directory = directory_id?;
+        // Parent links could form a cycle in a corrupted table
+        for _ in 0..self.main_table.len() {
+            if directory == 0 {
+                break;
+            }
+
+            let parent = DirId(self.main_table[directory].total_or_parent.get()).index();
+            let name = self
+                .sub_tables
+                .get(parent)?
+                .iter()
+                .find_map(|entry| match entry {
+                    SubTableEntry::DirectoryEntry { name, directory_id }
+                        if DirId(directory_id.get()).index() == directory =>
+                    {
+                        Some(name)
+                    },
+                    _ => None,
+                })?;
+
+            names.push(name);
+            directory = parent;
+        }
+
+        Some(names.iter().rev().fold(String::new(), |path, name| {
+            path + "/" + &name.as_str_lossy()
+        }))
+    }
+
+    /// Iterates over all files and directories below the root
+    pub fn entries(&self, order: Order) -> Entries<'_> {
+        Entries::new(self, order)
+    }
+
     /// Lists files and directories with their id
     pub fn walk<F>(&self, mut function: F)
     where
@@ -165,3 +369,57 @@ impl FileNameTable {
         self.walk_directory(&mut function, 0, &mut vec![])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::tree::Directory;
+
+    /// `/a`, `/dir/b` and `/dir/sub/c` after two unnamed files, read back
+    /// from bytes
+    fn fnt() -> FileNameTable {
+        let mut root = Directory::default();
+        root.add_file("a", vec![]).unwrap();
+        root.add_file("dir/b", vec![]).unwrap();
+        root.add_file("dir/sub/c", vec![]).unwrap();
+        let (fnt, _) = root.to_fnt(2).unwrap();
+        FileNameTable::read(&fnt.to_bytes(), 0).unwrap()
+    }
+
+    #[test]
+    fn lookup() {
+        let fnt = fnt();
+
+        assert_eq!(fnt.lookup("/a"), Some(FileId(2)));
+        assert_eq!(fnt.lookup("/dir/b"), Some(FileId(3)));
+        assert_eq!(fnt.lookup("dir//sub/c"), Some(FileId(4)));
+        assert_eq!(fnt.lookup("/dir"), None);
+        assert_eq!(fnt.lookup("/missing"), None);
+        assert_eq!(fnt.lookup("/dir/sub/missing"), None);
+        assert_eq!(fnt.lookup("/a/b"), None);
+        assert_eq!(fnt.lookup(""), None);
+    }
+
+    #[test]
+    fn path_of() {
+        let fnt = fnt();
+
+        for path in ["/a", "/dir/b", "/dir/sub/c"] {
+            assert_eq!(
+                fnt.path_of(fnt.lookup(path).unwrap()).as_deref(),
+                Some(path)
+            );
+        }
+        assert_eq!(fnt.path_of(FileId(1)), None);
+        assert_eq!(fnt.path_of(FileId(5)), None);
+    }
+
+    #[test]
+    fn path_of_wrong_parent() {
+        let mut fnt = fnt();
+        // `/dir/sub` does not list `/dir`
+        fnt.main_table[1].total_or_parent = 0xF002.into();
+
+        assert_eq!(fnt.path_of(FileId(4)), None);
+    }
+}
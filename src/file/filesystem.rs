@@ -0,0 +1,345 @@
+use crate::{
+    error::{Error, Result, Table},
+    file::{
+        file_allocation_table::FileAllocationTableEntry,
+        file_name_table::FileNameTable,
+        tree::{Directory, Node},
+        FileId, Files,
+    },
+};
+use zerocopy::AsBytes;
+
+/// An editable copy of a filesystem.
+///
+/// Files without a name, usually overlays, keep their IDs because code refers
+/// to them by ID. Named files are numbered after them in tree order, so their
+/// IDs change when entries are added, removed or moved.
+#[derive(Clone, Debug, Default)]
+pub struct Filesystem {
+    /// Contents of the files with IDs below the first named file
+    pub unnamed_files: Vec<Vec<u8>>,
+    pub root: Directory,
+}
+
+/// The tables and data of a [Filesystem], see [Filesystem::serialize]
+#[derive(Clone, Debug)]
+pub struct SerializedFilesystem {
+    pub fnt: FileNameTable,
+    pub fat: Vec<FileAllocationTableEntry>,
+    /// The contents of all files, starting at the `data_offset` given to
+    /// [Filesystem::serialize]
+    pub data: Vec<u8>,
+}
+
+impl SerializedFilesystem {
+    /// Builds the FNT and FAT for `unnamed_files` followed by the files in
+    /// `root`, and lays out all files in ID order.
+    ///
+    /// The first file starts at `data_offset`, every file is aligned to
+    /// `alignment` within the ROM or archive, and the gaps are filled with
+    /// 0xFF.
+    pub fn new(
+        unnamed_files: &[&[u8]],
+        root: &Directory,
+        data_offset: usize,
+        alignment: usize,
+    ) -> Result<Self> {
+        let (fnt, named_files) = root.to_fnt(unnamed_files.len() as u16)?;
+
+        let mut data = Vec::new();
+        let mut fat = Vec::with_capacity(unnamed_files.len() + named_files.len());
+        for file in unnamed_files.iter().copied().chain(named_files) {
+            let start = (data_offset + data.len()).div_ceil(alignment) * alignment;
+            data.resize(start - data_offset, 0xFF);
+            data.extend_from_slice(file);
+            fat.push(FileAllocationTableEntry {
+                start: (start as u32).into(),
+                end: ((start + file.len()) as u32).into(),
+            });
+        }
+
+        Ok(Self { fnt, fat, data })
+    }
+
+    pub fn fnt_bytes(&self) -> Vec<u8> {
+        self.fnt.to_bytes()
+    }
+
+    pub fn fat_bytes(&self) -> &[u8] {
+        self.fat.as_bytes()
+    }
+}
+
+impl Filesystem {
+    /// Copies all files out of `files`
+    pub fn read(files: &Files) -> Result<Self> {
+        let first_named = files
+            .fnt
+            .main_table
+            .iter()
+            .map(|directory| directory.id_of_first_file.get() as usize)
+            .min()
+            .unwrap_or(files.fat.len())
+            .min(files.fat.len());
+
+        let unnamed_files = (0..first_named)
+            .map(|id| files.file(FileId(id as u16)).map(<[u8]>::to_vec))
+            .collect::<Result<_>>()?;
+        let mut named = vec![false; files.fat.len()];
+        let root = Directory::from_fnt(&files.fnt, |id| {
+            let data = files.file(id)?.to_vec();
+            named[id.0 as usize] = true;
+            Ok(data)
+        })?;
+
+        // Named files are renumbered, so these couldn't keep their IDs
+        if let Some(file_id) = (first_named..files.fat.len()).find(|id| !named[*id]) {
+            return Err(Error::UnnamedFile { file_id });
+        }
+
+        Ok(Self {
+            unnamed_files,
+            root,
+        })
+    }
+
+    /// See [Directory::get]
+    pub fn get(&self, path: &str) -> Result<&Node> {
+        self.root.get(path)
+    }
+
+    /// See [Directory::add_file]
+    pub fn add_file(&mut self, path: &str, data: Vec<u8>) -> Result<()> {
+        self.root.add_file(path, data)
+    }
+
+    /// See [Directory::add_directory]
+    pub fn add_directory(&mut self, path: &str) -> Result<()> {
+        self.root.add_directory(path)
+    }
+
+    /// See [Directory::remove]
+    pub fn remove(&mut self, path: &str) -> Result<Node> {
+        self.root.remove(path)
+    }
+
+    /// See [Directory::rename]
+    pub fn rename(&mut self, path: &str, new_name: &str) -> Result<()> {
+        self.root.rename(path, new_name)
+    }
+
+    /// See [Directory::move_entry]
+    pub fn move_entry(&mut self, from: &str, to: &str) -> Result<()> {
+        self.root.move_entry(from, to)
+    }
+
+    /// See [Directory::replace]
+    pub fn replace(&mut self, path: &str, data: Vec<u8>) -> Result<Vec<u8>> {
+        self.root.replace(path, data)
+    }
+
+    /// Replaces the contents of an unnamed file and returns the old ones
+    pub fn replace_unnamed(&mut self, file_id: FileId, data: Vec<u8>) -> Result<Vec<u8>> {
+        let count = self.unnamed_files.len();
+        let file = self
+            .unnamed_files
+            .get_mut(file_id.0 as usize)
+            .ok_or(Error::InvalidFileId {
+                table: Table::FileAllocationTable,
+                file_id: file_id.0 as usize,
+                count,
+            })?;
+        Ok(std::mem::replace(file, data))
+    }
+
+    /// Builds the FNT and FAT and lays out all files in ID order, see
+    /// [SerializedFilesystem::new]
+    pub fn serialize(&self, data_offset: usize, alignment: usize) -> Result<SerializedFilesystem> {
+        let unnamed_files = self
+            .unnamed_files
+            .iter()
+            .map(Vec::as_slice)
+            .collect::<Vec<_>>();
+        SerializedFilesystem::new(&unnamed_files, &self.root, data_offset, alignment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::traversal::{Entry, Order};
+    use zerocopy::LayoutVerified;
+
+    const DATA_OFFSET: usize = 0x200;
+
+    /// Two overlays followed by `/a`, `/dir/b` and `/dir/sub/c`
+    fn filesystem() -> Filesystem {
+        let mut filesystem = Filesystem {
+            unnamed_files: vec![vec![0; 3], vec![1; 5]],
+            root: Directory::default(),
+        };
+        filesystem.add_file("/a", vec![0xA; 7]).unwrap();
+        filesystem.add_file("/dir/b", vec![0xB; 2]).unwrap();
+        filesystem.add_file("/dir/sub/c", vec![0xC; 9]).unwrap();
+        filesystem
+    }
+
+    /// The FNT followed by the file data, like in a ROM or archive
+    fn layout(serialized: &SerializedFilesystem) -> Vec<u8> {
+        let mut rom = serialized.fnt_bytes();
+        assert!(rom.len() <= DATA_OFFSET);
+        rom.resize(DATA_OFFSET, 0xFF);
+        rom.extend_from_slice(&serialized.data);
+        rom
+    }
+
+    fn read(serialized: &SerializedFilesystem, rom: &[u8]) -> Result<Filesystem> {
+        let files = Files {
+            fnt: FileNameTable::read(rom, 0)?,
+            fat: LayoutVerified::new_slice(serialized.fat_bytes()).unwrap(),
+            rom,
+        };
+        Filesystem::read(&files)
+    }
+
+    /// Paths of the named files in ID order
+    fn ids(filesystem: &Filesystem) -> Vec<(String, u16)> {
+        let serialized = filesystem.serialize(DATA_OFFSET, 4).unwrap();
+        let mut ids = serialized
+            .fnt
+            .entries(Order::DepthFirst)
+            .filter_map(|entry| match entry {
+                Entry::File { id, path } => Some((path, id.0)),
+                Entry::Directory { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        ids.sort_by_key(|(_, id)| *id);
+        ids
+    }
+
+    fn file<'lt>(filesystem: &'lt Filesystem, path: &str) -> &'lt [u8] {
+        match filesystem.get(path).unwrap() {
+            Node::File(data) => data,
+            Node::Directory(_) => panic!("{} is a directory", path),
+        }
+    }
+
+    #[test]
+    fn serialize() {
+        let serialized = filesystem().serialize(DATA_OFFSET, 0x20).unwrap();
+
+        let ranges = serialized
+            .fat
+            .iter()
+            .map(|entry| (entry.start.get(), entry.end.get()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            [
+                (0x200, 0x203),
+                (0x220, 0x225),
+                (0x240, 0x247),
+                (0x260, 0x262),
+                (0x280, 0x289)
+            ]
+        );
+        assert_eq!(serialized.data.len(), 0x89);
+        assert!(serialized.data[0x3..0x20].iter().all(|&byte| byte == 0xFF));
+        assert_eq!(serialized.fnt.main_table[0].id_of_first_file.get(), 2);
+        assert_eq!(serialized.fnt.lookup("/dir/sub/c"), Some(FileId(4)));
+    }
+
+    #[test]
+    fn round_trip() {
+        let original = filesystem();
+        let serialized = original.serialize(DATA_OFFSET, 4).unwrap();
+        let rom = layout(&serialized);
+
+        let filesystem = read(&serialized, &rom).unwrap();
+
+        assert_eq!(filesystem.unnamed_files, original.unnamed_files);
+        assert_eq!(ids(&filesystem), ids(&original));
+        assert_eq!(file(&filesystem, "/a"), [0xA; 7]);
+        assert_eq!(file(&filesystem, "/dir/b"), [0xB; 2]);
+        assert_eq!(file(&filesystem, "/dir/sub/c"), [0xC; 9]);
+
+        let again = filesystem.serialize(DATA_OFFSET, 4).unwrap();
+        assert_eq!(again.fnt_bytes(), serialized.fnt_bytes());
+        assert_eq!(again.fat_bytes(), serialized.fat_bytes());
+        assert_eq!(again.data, serialized.data);
+    }
+
+    #[test]
+    fn unnamed_file_after_named() {
+        let mut serialized = filesystem().serialize(DATA_OFFSET, 4).unwrap();
+        let last = *serialized.fat.last().unwrap();
+        serialized.fat.push(last);
+        let rom = layout(&serialized);
+
+        assert!(matches!(
+            read(&serialized, &rom),
+            Err(Error::UnnamedFile { file_id: 5 })
+        ));
+    }
+
+    #[test]
+    fn renumbering() {
+        let mut filesystem = filesystem();
+
+        filesystem.add_file("/dir/d", vec![]).unwrap();
+        assert_eq!(
+            ids(&filesystem),
+            [
+                ("/a".to_string(), 2),
+                ("/dir/b".to_string(), 3),
+                ("/dir/d".to_string(), 4),
+                ("/dir/sub/c".to_string(), 5),
+            ]
+        );
+
+        filesystem.remove("/a").unwrap();
+        assert_eq!(
+            ids(&filesystem),
+            [
+                ("/dir/b".to_string(), 2),
+                ("/dir/d".to_string(), 3),
+                ("/dir/sub/c".to_string(), 4),
+            ]
+        );
+
+        filesystem.move_entry("/dir/sub/c", "/c").unwrap();
+        filesystem.rename("/dir/b", "e").unwrap();
+        assert_eq!(
+            ids(&filesystem),
+            [
+                ("/c".to_string(), 2),
+                ("/dir/e".to_string(), 3),
+                ("/dir/d".to_string(), 4),
+            ]
+        );
+        assert_eq!(file(&filesystem, "/c"), [0xC; 9]);
+
+        let old = filesystem.replace("/dir/e", vec![1]).unwrap();
+        assert_eq!(old, [0xB; 2]);
+        assert_eq!(file(&filesystem, "/dir/e"), [1]);
+        let old = filesystem.replace_unnamed(FileId(1), vec![2]).unwrap();
+        assert_eq!(old, [1; 5]);
+        assert_eq!(filesystem.unnamed_files[1], [2]);
+        assert!(filesystem.replace_unnamed(FileId(2), vec![]).is_err());
+    }
+
+    #[test]
+    fn move_into_own_subtree() {
+        let mut filesystem = filesystem();
+
+        assert!(matches!(
+            filesystem.move_entry("/dir", "/dir/sub/dir"),
+            Err(Error::InvalidPath { .. })
+        ));
+        assert!(matches!(
+            filesystem.move_entry("/dir", "/a"),
+            Err(Error::AlreadyExists { .. })
+        ));
+        assert_eq!(ids(&filesystem), ids(&self::filesystem()));
+    }
+}
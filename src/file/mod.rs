@@ -1,11 +1,57 @@
 use crate::{
     cartridge_header::CartridgeHeader,
-    file::{file_allocation_table::FileAllocationTableEntry, file_name_table::FileNameTable},
+    error::{Error, Result},
+    file::{
+        file_allocation_table::FileAllocationTableEntry,
+        file_name_table::FileNameTable,
+        traversal::{Entries, Order},
+    },
 };
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
 use zerocopy::LayoutVerified;
 
 pub mod file_allocation_table;
 pub mod file_name_table;
+pub mod filesystem;
+pub mod narc;
+pub mod traversal;
+pub mod tree;
+
+/// Index of a file in the FAT
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct FileId(pub u16);
+
+impl Display for FileId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// ID of a directory, 0xF000 plus its index in the main table
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct DirId(pub u16);
+
+impl DirId {
+    pub const ROOT: DirId = DirId(0xF000);
+
+    /// The ID of the directory at `index` in the main table, or `None` past
+    /// the 0x1000 directories that IDs can refer to
+    pub fn from_index(index: usize) -> Option<Self> {
+        (index < 0x1000).then(|| Self(0xF000 + index as u16))
+    }
+
+    /// Index in the main table
+    pub fn index(self) -> usize {
+        self.0.wrapping_sub(0xF000) as usize
+    }
+}
+
+impl Display for DirId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#06x}", self.0)
+    }
+}
 
 pub struct Files<'lt> {
     pub fnt: FileNameTable,
@@ -14,10 +60,77 @@ pub struct Files<'lt> {
 }
 
 impl<'lt> Files<'lt> {
-    pub fn read(header: &CartridgeHeader, rom: &'lt [u8]) -> Option<Self> {
+    pub fn read(header: &CartridgeHeader, rom: &'lt [u8]) -> Result<Self> {
         let fnt = header.read_fnt(rom)?;
         let fat = header.read_fat(rom)?;
 
-        Some(Self { fnt, fat, rom })
+        Ok(Self { fnt, fat, rom })
+    }
+
+    /// See [FileNameTable::entries]
+    pub fn entries(&self, order: Order) -> Entries<'_> {
+        self.fnt.entries(order)
+    }
+
+    /// See [FileNameTable::lookup]
+    pub fn lookup(&self, path: &str) -> Option<FileId> {
+        self.fnt.lookup(path)
+    }
+
+    /// See [FileNameTable::path_of]
+    pub fn path_of(&self, file_id: FileId) -> Option<String> {
+        self.fnt.path_of(file_id)
+    }
+
+    pub fn file(&self, file_id: FileId) -> Result<&'lt [u8]> {
+        FileAllocationTableEntry::read_file(&self.fat, file_id.0 as usize, self.rom)
+    }
+
+    /// Returns the contents of the file at `path`
+    pub fn open(&self, path: &str) -> Result<&'lt [u8]> {
+        let file_id = self.lookup(path).ok_or_else(|| Error::FileNotFound {
+            path: path.to_string(),
+        })?;
+        self.file(file_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::filesystem::SerializedFilesystem;
+
+    #[test]
+    fn dir_id() {
+        assert_eq!(DirId::from_index(0), Some(DirId::ROOT));
+        assert_eq!(DirId::from_index(0xFFF), Some(DirId(0xFFFF)));
+        assert_eq!(DirId::from_index(0x1000), None);
+        assert_eq!(DirId::from_index(0x1_0000), None);
+        assert_eq!(DirId(0xF123).index(), 0x123);
+    }
+
+    #[test]
+    fn open() {
+        let mut root = tree::Directory::default();
+        root.add_file("a", vec![0xA; 3]).unwrap();
+        root.add_file("dir/b", vec![0xB; 2]).unwrap();
+        let serialized = SerializedFilesystem::new(&[], &root, 0, 4).unwrap();
+        let files = Files {
+            fnt: serialized.fnt.clone(),
+            fat: LayoutVerified::new_slice(serialized.fat_bytes()).unwrap(),
+            rom: &serialized.data,
+        };
+
+        assert_eq!(files.open("/dir/b").unwrap(), [0xB; 2]);
+        assert_eq!(files.open("a").unwrap(), [0xA; 3]);
+        assert_eq!(files.path_of(FileId(1)).as_deref(), Some("/dir/b"));
+        assert!(matches!(
+            files.open("/dir/a"),
+            Err(Error::FileNotFound { path }) if path == "/dir/a"
+        ));
+        assert!(matches!(
+            files.open("/dir"),
+            Err(Error::FileNotFound { .. })
+        ));
     }
 }
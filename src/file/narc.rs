@@ -0,0 +1,218 @@
+use crate::{
+    byte_types::int::U16,
+    error::{Error, Result, Table},
+    file::{
+        file_allocation_table::FileAllocationTableEntry,
+        file_name_table::{DirectoryMainTableEntry, FileNameTable},
+        traversal::{Entry, Order},
+        FileId, Files,
+    },
+    nitro_file::{NitroFile, NitroHeader},
+};
+use byteorder::LittleEndian;
+use zerocopy::LayoutVerified;
+
+/// An archive of files with the same FAT and FNT as the ROM filesystem.
+///
+/// File offsets are relative to the start of the GMIF data, which
+/// `files.rom` points to.
+pub struct Narc<'lt> {
+    pub header: NitroHeader,
+    pub files: Files<'lt>,
+}
+
+impl<'lt> Narc<'lt> {
+    pub const DATA_MAGIC: [u8; 4] = *b"GMIF";
+    pub const FAT_MAGIC: [u8; 4] = *b"BTAF";
+    pub const FNT_MAGIC: [u8; 4] = *b"BTNF";
+    pub const MAGIC: [u8; 4] = *b"NARC";
+
+    pub fn read(data: &'lt [u8]) -> Result<Self> {
+        let file = NitroFile::read(data, Self::MAGIC, Table::Narc)?;
+
+        let fat_section = file.require_section(Self::FAT_MAGIC, Table::Narc)?;
+        let (count, entries) = LayoutVerified::<_, U16<LittleEndian>>::new_from_prefix(
+            fat_section.data,
+        )
+        .ok_or(Error::OutOfBounds {
+            table: Table::FileAllocationTable,
+            offset: fat_section.offset,
+            expected: 4,
+            actual: fat_section.data.len(),
+        })?;
+        let count = count.get() as usize;
+        let entries = entries.get(2..).unwrap_or_default();
+        let (fat, _) =
+            LayoutVerified::new_slice_from_prefix(entries, count).ok_or(Error::OutOfBounds {
+                table: Table::FileAllocationTable,
+                offset: fat_section.offset,
+                expected: 4 + count * std::mem::size_of::<FileAllocationTableEntry>(),
+                actual: fat_section.data.len(),
+            })?;
+
+        let fnt_section = file.require_section(Self::FNT_MAGIC, Table::Narc)?;
+        let fnt = Self::read_fnt(fnt_section.data)?;
+
+        let gmif = file.require_section(Self::DATA_MAGIC, Table::Narc)?;
+
+        Ok(Self {
+            header: file.header,
+            files: Files {
+                fnt,
+                fat,
+                rom: gmif.data,
+            },
+        })
+    }
+
+    /// Anonymous archives often only store the root entry of the main table,
+    /// with a sub-table offset that points past the end of the section
+    fn read_fnt(data: &[u8]) -> Result<FileNameTable> {
+        if let Some((root, _)) = LayoutVerified::<_, DirectoryMainTableEntry>::new_from_prefix(data)
+        {
+            if root.total_or_parent.get() <= 1
+                && root.offset_to_sub_table.get() as usize >= data.len()
+            {
+                return Ok(FileNameTable {
+                    main_table: vec![*root],
+                    sub_tables: vec![vec![]],
+                });
+            }
+        }
+
+        FileNameTable::read(data, 0)
+    }
+
+    /// Whether the archive stores no file names
+    pub fn is_anonymous(&self) -> bool {
+        self.files.fnt.sub_tables.iter().all(Vec::is_empty)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.fat.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.fat.is_empty()
+    }
+
+    pub fn file(&self, file_id: FileId) -> Result<&'lt [u8]> {
+        self.files.file(file_id)
+    }
+
+    /// Paths and IDs of all files.
+    ///
+    /// Files of anonymous archives are named after their ID, e.g. `/0003.bin`.
+    pub fn paths(&self) -> Vec<(String, FileId)> {
+        if self.is_anonymous() {
+            return (0..self.len())
+                .map(|id| (format!("/{:04}.bin", id), FileId(id as u16)))
+                .collect();
+        }
+
+        self.files
+            .entries(Order::DepthFirst)
+            .filter_map(|entry| match entry {
+                Entry::File { id, path } => Some((path, id)),
+                Entry::Directory { .. } => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file::{filesystem::SerializedFilesystem, tree::Directory},
+        nitro_file::tests::build,
+    };
+    use zerocopy::AsBytes;
+
+    fn narc(fat: &[FileAllocationTableEntry], fnt: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut btaf = (fat.len() as u32).to_le_bytes().to_vec();
+        btaf.extend_from_slice(fat.as_bytes());
+        build(
+            Narc::MAGIC,
+            &[
+                (Narc::FAT_MAGIC, &btaf),
+                (Narc::FNT_MAGIC, fnt),
+                (Narc::DATA_MAGIC, data),
+            ],
+        )
+    }
+
+    /// `/a`, `/dir/b` and `/dir/sub/c`
+    fn named() -> Vec<u8> {
+        let mut root = Directory::default();
+        root.add_file("a", vec![0xA; 3]).unwrap();
+        root.add_file("dir/b", vec![0xB; 2]).unwrap();
+        root.add_file("dir/sub/c", vec![0xC; 5]).unwrap();
+        let serialized = SerializedFilesystem::new(&[], &root, 0, 4).unwrap();
+        narc(&serialized.fat, &serialized.fnt_bytes(), &serialized.data)
+    }
+
+    #[test]
+    fn read() {
+        let data = named();
+        let narc = Narc::read(&data).unwrap();
+
+        assert!(!narc.is_anonymous());
+        assert_eq!(narc.len(), 3);
+        assert_eq!(
+            narc.paths(),
+            [
+                ("/a".to_string(), FileId(0)),
+                ("/dir/b".to_string(), FileId(1)),
+                ("/dir/sub/c".to_string(), FileId(2)),
+            ]
+        );
+        assert_eq!(narc.file(FileId(2)).unwrap(), [0xC; 5]);
+        assert_eq!(narc.files.open("/dir/b").unwrap(), [0xB; 2]);
+        assert_eq!(narc.files.lookup("/dir/missing"), None);
+        assert!(matches!(
+            narc.file(FileId(3)),
+            Err(Error::InvalidFileId { .. })
+        ));
+    }
+
+    #[test]
+    fn anonymous() {
+        let fat = [0..2, 4..7].map(|range| FileAllocationTableEntry {
+            start: range.start.into(),
+            end: range.end.into(),
+        });
+        // Only the root entry, its sub-table would start after the section
+        let root = DirectoryMainTableEntry {
+            offset_to_sub_table: 8.into(),
+            id_of_first_file: 0.into(),
+            total_or_parent: 1.into(),
+        };
+        let data = narc(&fat, root.as_bytes(), &[1, 1, 0xFF, 0xFF, 2, 2, 2]);
+        let narc = Narc::read(&data).unwrap();
+
+        assert!(narc.is_anonymous());
+        assert_eq!(
+            narc.paths(),
+            [
+                ("/0000.bin".to_string(), FileId(0)),
+                ("/0001.bin".to_string(), FileId(1)),
+            ]
+        );
+        assert_eq!(narc.file(FileId(1)).unwrap(), [2, 2, 2]);
+    }
+
+    #[test]
+    fn truncated_fat() {
+        let mut data = named();
+        // The file count of the BTAF section
+        data[0x18] = 4;
+        assert!(matches!(
+            Narc::read(&data),
+            Err(Error::OutOfBounds {
+                table: Table::FileAllocationTable,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{
+    byte_types::embedded_string::EmbeddedStringCommon,
+    file::{
+        file_name_table::{FileNameTable, SubTableEntry},
+        DirId, FileId,
+    },
+};
+use std::collections::VecDeque;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Entry {
+    File {
+        id: FileId,
+        path: String,
+    },
+    Directory {
+        id: DirId,
+        path: String,
+        parent: DirId,
+    },
+}
+
+impl Entry {
+    /// Full path, e.g. `/data/sound/sound_data.sdat`
+    pub fn path(&self) -> &str {
+        match self {
+            Entry::File { path, .. } | Entry::Directory { path, .. } => path,
+        }
+    }
+
+    pub fn file_id(&self) -> Option<FileId> {
+        match self {
+            Entry::File { id, .. } => Some(*id),
+            Entry::Directory { .. } => None,
+        }
+    }
+
+    pub fn is_directory(&self) -> bool {
+        matches!(self, Entry::Directory { .. })
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Order {
+    /// The contents of a directory follow right after it, like `walk`
+    DepthFirst,
+    /// All entries of a directory come before those of its subdirectories
+    BreadthFirst,
+}
+
+/// Position in one directory's sub-table
+struct Cursor {
+    directory: DirId,
+    path: String,
+    position: usize,
+    next_file_id: u16,
+}
+
+/// Iterator over all files and directories below the root, see
+/// [FileNameTable::entries]
+pub struct Entries<'lt> {
+    fnt: &'lt FileNameTable,
+    order: Order,
+    cursors: VecDeque<Cursor>,
+    /// Guards against directory cycles in corrupted tables
+    visited: Vec<bool>,
+}
+
+impl<'lt> Entries<'lt> {
+    pub(crate) fn new(fnt: &'lt FileNameTable, order: Order) -> Self {
+        let mut entries = Self {
+            fnt,
+            order,
+            cursors: VecDeque::new(),
+            visited: vec![false; fnt.main_table.len()],
+        };
+        entries.enter(DirId::ROOT, String::new());
+        entries
+    }
+
+    /// Queues `directory` unless it was visited before or is missing from
+    /// either table
+    fn enter(&mut self, directory: DirId, path: String) {
+        let index = directory.index();
+        if let (Some(meta), Some(_), Some(false)) = (
+            self.fnt.main_table.get(index),
+            self.fnt.sub_tables.get(index),
+            self.visited.get(index),
+        ) {
+            self.visited[index] = true;
+            self.cursors.push_back(Cursor {
+                directory,
+                path,
+                position: 0,
+                next_file_id: meta.id_of_first_file.get(),
+            });
+        }
+    }
+
+    fn current(&mut self) -> Option<&mut Cursor> {
+        match self.order {
+            Order::DepthFirst => self.cursors.back_mut(),
+            Order::BreadthFirst => self.cursors.front_mut(),
+        }
+    }
+
+    fn finish_current(&mut self) {
+        match self.order {
+            Order::DepthFirst => self.cursors.pop_back(),
+            Order::BreadthFirst => self.cursors.pop_front(),
+        };
+    }
+}
+
+impl<'lt> Iterator for Entries<'lt> {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        loop {
+            let fnt = self.fnt;
+            let cursor = self.current()?;
+            let entry = match fnt
+                .sub_tables
+                .get(cursor.directory.index())
+                .and_then(|sub_table| sub_table.get(cursor.position))
+            {
+                Some(entry) => entry,
+                None => {
+                    self.finish_current();
+                    continue;
+                },
+            };
+            cursor.position += 1;
+
+            match entry {
+                SubTableEntry::FileEntry { name } => {
+                    let id = FileId(cursor.next_file_id);
+                    cursor.next_file_id = cursor.next_file_id.wrapping_add(1);
+
+                    return Some(Entry::File {
+                        id,
+                        path: format!("{}/{}", cursor.path, name.as_str_lossy()),
+                    });
+                },
+                SubTableEntry::DirectoryEntry { name, directory_id } => {
+                    let id = DirId(directory_id.get());
+                    let parent = cursor.directory;
+                    let path = format!("{}/{}", cursor.path, name.as_str_lossy());
+
+                    self.enter(id, path.clone());
+                    return Some(Entry::Directory { id, path, parent });
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::tree::{make_name, Directory};
+
+    /// `/a`, `/dir/b`, `/dir/sub/c` and `/d`
+    fn fnt() -> FileNameTable {
+        let mut root = Directory::default();
+        for path in ["a", "dir/b", "dir/sub/c", "d"] {
+            root.add_file(path, vec![]).unwrap();
+        }
+        root.to_fnt(0).unwrap().0
+    }
+
+    fn paths(entries: Entries) -> Vec<String> {
+        entries.map(|entry| entry.path().to_string()).collect()
+    }
+
+    #[test]
+    fn orders() {
+        let fnt = fnt();
+
+        assert_eq!(
+            paths(fnt.entries(Order::DepthFirst)),
+            ["/a", "/dir", "/dir/b", "/dir/sub", "/dir/sub/c", "/d"]
+        );
+        assert_eq!(
+            paths(fnt.entries(Order::BreadthFirst)),
+            ["/a", "/dir", "/d", "/dir/b", "/dir/sub", "/dir/sub/c"]
+        );
+        assert_eq!(
+            fnt.entries(Order::DepthFirst)
+                .filter_map(|entry| entry.file_id())
+                .map(|id| id.0)
+                .collect::<Vec<_>>(),
+            [0, 2, 3, 1]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let mut short = fnt();
+        short.sub_tables.truncate(2);
+        assert_eq!(
+            paths(short.entries(Order::DepthFirst)),
+            ["/a", "/dir", "/dir/b", "/dir/sub", "/d"]
+        );
+
+        short.sub_tables.clear();
+        assert!(short.entries(Order::BreadthFirst).next().is_none());
+
+        let mut past_end = fnt();
+        past_end.sub_tables[0].push(SubTableEntry::DirectoryEntry {
+            name: make_name("x").unwrap(),
+            directory_id: 0xF009.into(),
+        });
+        assert_eq!(past_end.entries(Order::DepthFirst).count(), 7);
+    }
+}
@@ -0,0 +1,533 @@
+use crate::{
+    byte_types::{
+        embedded_string::{DynamicEmbeddedString, EmbeddedStringCommon, EmbeddedStringMake},
+        int::{U16, U32},
+    },
+    error::{Error, Result, Table},
+    file::{
+        file_name_table::{DirectoryMainTableEntry, FileNameTable, SubTableEntry},
+        DirId, FileId,
+    },
+};
+use byteorder::LittleEndian;
+use std::collections::HashMap;
+
+pub type Name = DynamicEmbeddedString<127>;
+
+/// A file or directory in a [Directory] tree
+#[derive(Clone, Debug)]
+pub enum Node {
+    File(Vec<u8>),
+    Directory(Directory),
+}
+
+/// An owned directory tree that can be turned into a [FileNameTable] and the
+/// matching file contents
+#[derive(Clone, Debug, Default)]
+pub struct Directory {
+    /// Entries in the order they appear in the sub-table
+    pub entries: Vec<(Name, Node)>,
+}
+
+pub fn make_name(name: &str) -> Result<Name> {
+    Name::from_slice(name.as_bytes()).ok_or_else(|| Error::NameTooLong {
+        name: name.to_string(),
+    })
+}
+
+impl Directory {
+    /// Reorders entries to match the order in `fnt`, which keeps file IDs
+    /// stable when repacking an extracted ROM.
+    /// Entries that don't appear in `fnt` are moved to the end.
+    pub fn order_like(&mut self, fnt: &FileNameTable) {
+        if !fnt.sub_tables.is_empty() {
+            self.order_like_directory(fnt, 0);
+        }
+    }
+
+    fn order_like_directory(&mut self, fnt: &FileNameTable, directory: usize) {
+        let sub_table = match fnt.sub_tables.get(directory) {
+            Some(sub_table) => sub_table,
+            None => return,
+        };
+        let positions = sub_table
+            .iter()
+            .enumerate()
+            .map(|(position, entry)| match entry {
+                SubTableEntry::FileEntry { name } => (name.data(), (position, None)),
+                SubTableEntry::DirectoryEntry { name, directory_id } => (
+                    name.data(),
+                    (
+                        position,
+                        Some(directory_id.get().wrapping_sub(0xF000) as usize),
+                    ),
+                ),
+            })
+            .collect::<HashMap<_, _>>();
+
+        self.entries.sort_by_key(|(name, _)| {
+            positions
+                .get(name.data())
+                .map(|(position, _)| *position)
+                .unwrap_or(usize::MAX)
+        });
+
+        for (name, node) in &mut self.entries {
+            if let (Node::Directory(directory), Some((_, Some(index)))) =
+                (node, positions.get(name.data()))
+            {
+                directory.order_like_directory(fnt, *index);
+            }
+        }
+    }
+
+    /// Builds the file name table for this tree.
+    ///
+    /// File IDs are assigned in order starting at `first_file_id`.
+    /// Returns the table and the file contents in file ID order.
+    pub fn to_fnt(&self, first_file_id: u16) -> Result<(FileNameTable, Vec<&[u8]>)> {
+        let mut fnt = FileNameTable {
+            main_table: vec![],
+            sub_tables: vec![],
+        };
+        let mut files = Vec::new();
+
+        self.add_to_fnt(&mut fnt, &mut files, first_file_id as usize, 0xF000)?;
+
+        let directory_count = fnt.main_table.len();
+        fnt.main_table[0]
+            .total_or_parent
+            .set(directory_count as u16);
+
+        Ok((fnt, files))
+    }
+
+    fn add_to_fnt<'lt>(
+        &'lt self,
+        fnt: &mut FileNameTable,
+        files: &mut Vec<&'lt [u8]>,
+        first_file_id: usize,
+        parent: u16,
+    ) -> Result<u16> {
+        let index = fnt.main_table.len();
+        let DirId(directory_id) =
+            DirId::from_index(index).ok_or(Error::TooManyDirectories { count: index + 1 })?;
+
+        let id_of_first_file = first_file_id + files.len();
+        files.extend(self.entries.iter().filter_map(|(_, node)| match node {
+            Node::File(data) => Some(data.as_slice()),
+            Node::Directory(_) => None,
+        }));
+        if first_file_id + files.len() > 0xF000 {
+            return Err(Error::TooManyFiles {
+                count: first_file_id + files.len(),
+            });
+        }
+
+        fnt.main_table.push(DirectoryMainTableEntry {
+            offset_to_sub_table: U32::default(),
+            id_of_first_file: U16::<LittleEndian>::from(id_of_first_file as u16),
+            total_or_parent: parent.into(),
+        });
+        fnt.sub_tables.push(vec![]);
+
+        let mut sub_table = Vec::with_capacity(self.entries.len());
+        for (name, node) in &self.entries {
+            sub_table.push(match node {
+                Node::File(_) => SubTableEntry::FileEntry { name: *name },
+                Node::Directory(directory) => SubTableEntry::DirectoryEntry {
+                    name: *name,
+                    directory_id: directory
+                        .add_to_fnt(fnt, files, first_file_id, directory_id)?
+                        .into(),
+                },
+            });
+        }
+        fnt.sub_tables[index] = sub_table;
+
+        Ok(directory_id)
+    }
+}
+
+/// Splits `path` into the names of its parent directories and its own name
+fn split_path(path: &str) -> Result<(Vec<&str>, &str)> {
+    let mut components = path
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>();
+    let name = components.pop().ok_or_else(|| Error::InvalidPath {
+        path: path.to_string(),
+    })?;
+    Ok((components, name))
+}
+
+impl Directory {
+    /// Reads the tree below the root of `fnt`, calling `file` for the contents
+    /// of every file
+    pub fn from_fnt<F>(fnt: &FileNameTable, mut file: F) -> Result<Self>
+    where
+        F: FnMut(FileId) -> Result<Vec<u8>>,
+    {
+        if fnt.main_table.is_empty() {
+            return Ok(Self::default());
+        }
+
+        let mut visited = vec![false; fnt.main_table.len()];
+        Self::from_fnt_directory(fnt, DirId::ROOT, &mut file, &mut visited)
+    }
+
+    fn from_fnt_directory<F>(
+        fnt: &FileNameTable,
+        id: DirId,
+        file: &mut F,
+        visited: &mut [bool],
+    ) -> Result<Self>
+    where
+        F: FnMut(FileId) -> Result<Vec<u8>>,
+    {
+        let index = id.index();
+        let invalid = || Error::InvalidDirectory {
+            table: Table::SubTable,
+            offset: 0,
+            directory_id: id.0,
+            count: fnt.main_table.len().min(fnt.sub_tables.len()),
+        };
+
+        // Rejects cycles as well as IDs past the end of either table
+        let (Some(meta), Some(sub_table), Some(visited_here)) = (
+            fnt.main_table.get(index),
+            fnt.sub_tables.get(index),
+            visited.get_mut(index),
+        ) else {
+            return Err(invalid());
+        };
+        if std::mem::replace(visited_here, true) {
+            return Err(invalid());
+        }
+
+        let mut next_file_id = meta.id_of_first_file.get();
+        let mut entries = Vec::with_capacity(sub_table.len());
+        for entry in sub_table {
+            entries.push(match entry {
+                SubTableEntry::FileEntry { name } => {
+                    let data = file(FileId(next_file_id))?;
+                    next_file_id = next_file_id.wrapping_add(1);
+                    (*name, Node::File(data))
+                },
+                SubTableEntry::DirectoryEntry { name, directory_id } => (
+                    *name,
+                    Node::Directory(Self::from_fnt_directory(
+                        fnt,
+                        DirId(directory_id.get()),
+                        file,
+                        visited,
+                    )?),
+                ),
+            });
+        }
+
+        Ok(Self { entries })
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|(entry_name, _)| entry_name.data() == name.as_bytes())
+    }
+
+    /// Follows `components` from this directory
+    fn directory(&self, path: &str, components: &[&str]) -> Result<&Directory> {
+        let mut directory = self;
+        for component in components {
+            directory = match directory
+                .position(component)
+                .map(|i| &directory.entries[i].1)
+            {
+                Some(Node::Directory(next)) => next,
+                Some(Node::File(_)) => {
+                    return Err(Error::NotADirectory {
+                        path: path.to_string(),
+                    })
+                },
+                None => {
+                    return Err(Error::FileNotFound {
+                        path: path.to_string(),
+                    })
+                },
+            };
+        }
+        Ok(directory)
+    }
+
+    /// Like [Directory::directory], but creates missing directories if
+    /// `create` is set
+    fn directory_mut(
+        &mut self,
+        path: &str,
+        components: &[&str],
+        create: bool,
+    ) -> Result<&mut Directory> {
+        let mut directory = self;
+        for component in components {
+            let index = match directory.position(component) {
+                Some(index) => index,
+                None if create => {
+                    let name = make_name(component)?;
+                    directory
+                        .entries
+                        .push((name, Node::Directory(Directory::default())));
+                    directory.entries.len() - 1
+                },
+                None => {
+                    return Err(Error::FileNotFound {
+                        path: path.to_string(),
+                    })
+                },
+            };
+
+            directory = match &mut directory.entries[index].1 {
+                Node::Directory(next) => next,
+                Node::File(_) => {
+                    return Err(Error::NotADirectory {
+                        path: path.to_string(),
+                    })
+                },
+            };
+        }
+        Ok(directory)
+    }
+
+    /// The entry at `path`, relative to this directory
+    pub fn get(&self, path: &str) -> Result<&Node> {
+        let (parents, name) = split_path(path)?;
+        let directory = self.directory(path, &parents)?;
+        directory
+            .position(name)
+            .map(|index| &directory.entries[index].1)
+            .ok_or_else(|| Error::FileNotFound {
+                path: path.to_string(),
+            })
+    }
+
+    pub fn get_mut(&mut self, path: &str) -> Result<&mut Node> {
+        let (parents, name) = split_path(path)?;
+        let directory = self.directory_mut(path, &parents, false)?;
+        match directory.position(name) {
+            Some(index) => Ok(&mut directory.entries[index].1),
+            None => Err(Error::FileNotFound {
+                path: path.to_string(),
+            }),
+        }
+    }
+
+    /// Adds `node` at `path`, creating missing parent directories.
+    /// New entries go to the end of their directory.
+    pub fn insert(&mut self, path: &str, node: Node) -> Result<()> {
+        let (parents, name) = split_path(path)?;
+        let name_bytes = make_name(name)?;
+        let directory = self.directory_mut(path, &parents, true)?;
+        if directory.position(name).is_some() {
+            return Err(Error::AlreadyExists {
+                path: path.to_string(),
+            });
+        }
+
+        directory.entries.push((name_bytes, node));
+        Ok(())
+    }
+
+    pub fn add_file(&mut self, path: &str, data: Vec<u8>) -> Result<()> {
+        self.insert(path, Node::File(data))
+    }
+
+    pub fn add_directory(&mut self, path: &str) -> Result<()> {
+        self.insert(path, Node::Directory(Directory::default()))
+    }
+
+    /// Removes the entry at `path` and returns it
+    pub fn remove(&mut self, path: &str) -> Result<Node> {
+        let (parents, name) = split_path(path)?;
+        let directory = self.directory_mut(path, &parents, false)?;
+        let index = directory
+            .position(name)
+            .ok_or_else(|| Error::FileNotFound {
+                path: path.to_string(),
+            })?;
+        Ok(directory.entries.remove(index).1)
+    }
+
+    /// Renames the entry at `path`, keeping its position in the directory
+    pub fn rename(&mut self, path: &str, new_name: &str) -> Result<()> {
+        let (parents, name) = split_path(path)?;
+        let directory = self.directory_mut(path, &parents, false)?;
+        let index = directory
+            .position(name)
+            .ok_or_else(|| Error::FileNotFound {
+                path: path.to_string(),
+            })?;
+
+        if new_name.is_empty() || new_name.contains('/') {
+            return Err(Error::InvalidPath {
+                path: new_name.to_string(),
+            });
+        }
+        let new_name_bytes = make_name(new_name)?;
+        if name == new_name {
+            return Ok(());
+        }
+        if directory.position(new_name).is_some() {
+            return Err(Error::AlreadyExists {
+                path: new_name.to_string(),
+            });
+        }
+
+        directory.entries[index].0 = new_name_bytes;
+        Ok(())
+    }
+
+    /// Moves the entry at `from` to `to`, which must not exist yet
+    pub fn move_entry(&mut self, from: &str, to: &str) -> Result<()> {
+        // Moving a directory into itself would detach it from the tree
+        let from_trimmed = from.trim_matches('/');
+        if to
+            .trim_matches('/')
+            .starts_with(&format!("{}/", from_trimmed))
+        {
+            return Err(Error::InvalidPath {
+                path: to.to_string(),
+            });
+        }
+        if self.get(to).is_ok() {
+            return Err(Error::AlreadyExists {
+                path: to.to_string(),
+            });
+        }
+
+        // Check everything insert could fail on, so the entry is never lost
+        let (parents, name) = split_path(to)?;
+        make_name(name)?;
+        let mut directory = Some(&*self);
+        for component in parents {
+            make_name(component)?;
+            directory = match directory.and_then(|d| d.position(component).map(|i| &d.entries[i].1))
+            {
+                Some(Node::Directory(next)) => Some(next),
+                Some(Node::File(_)) => {
+                    return Err(Error::NotADirectory {
+                        path: to.to_string(),
+                    })
+                },
+                None => None,
+            };
+        }
+
+        let node = self.remove(from)?;
+        self.insert(to, node)
+    }
+
+    /// Replaces the contents of the file at `path` and returns the old ones
+    pub fn replace(&mut self, path: &str, data: Vec<u8>) -> Result<Vec<u8>> {
+        match self.get_mut(path)? {
+            Node::File(old) => Ok(std::mem::replace(old, data)),
+            Node::Directory(_) => Err(Error::InvalidPath {
+                path: path.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Directory {
+        let mut root = Directory::default();
+        root.add_file("a", vec![0xA]).unwrap();
+        root.add_file("dir/b", vec![0xB]).unwrap();
+        root
+    }
+
+    fn names(directory: &Directory) -> Vec<&str> {
+        directory
+            .entries
+            .iter()
+            .map(|(name, _)| std::str::from_utf8(name.data()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn rename() {
+        let mut root = tree();
+
+        root.rename("/a", "c").unwrap();
+        assert_eq!(names(&root), ["c", "dir"]);
+        root.rename("/c", "c").unwrap();
+
+        assert!(matches!(
+            root.rename("/c", "dir"),
+            Err(Error::AlreadyExists { .. })
+        ));
+        assert!(matches!(
+            root.rename("/c", "dir/c"),
+            Err(Error::InvalidPath { .. })
+        ));
+        assert!(matches!(
+            root.rename("/missing", "missing"),
+            Err(Error::FileNotFound { .. })
+        ));
+        assert_eq!(names(&root), ["c", "dir"]);
+    }
+
+    #[test]
+    fn from_fnt() {
+        let tree = tree();
+        let (fnt, files) = tree.to_fnt(1).unwrap();
+
+        let root = Directory::from_fnt(&fnt, |id| Ok(files[id.0 as usize - 1].to_vec())).unwrap();
+
+        assert_eq!(names(&root), ["a", "dir"]);
+        assert!(matches!(root.get("/dir/b"), Ok(Node::File(data)) if data == &[0xB]));
+    }
+
+    #[test]
+    fn from_fnt_malformed() {
+        let (fnt, _) = tree().to_fnt(0).unwrap();
+        let read = |fnt: &FileNameTable| Directory::from_fnt(fnt, |_| Ok(vec![]));
+
+        let mut short = fnt.clone();
+        short.sub_tables.pop();
+        assert!(matches!(
+            read(&short),
+            Err(Error::InvalidDirectory {
+                directory_id: 0xF001,
+                ..
+            })
+        ));
+
+        let mut past_end = fnt.clone();
+        past_end.sub_tables[0].push(SubTableEntry::DirectoryEntry {
+            name: make_name("x").unwrap(),
+            directory_id: 0xF005.into(),
+        });
+        assert!(matches!(
+            read(&past_end),
+            Err(Error::InvalidDirectory {
+                directory_id: 0xF005,
+                ..
+            })
+        ));
+
+        let mut cycle = fnt;
+        cycle.sub_tables[1].push(SubTableEntry::DirectoryEntry {
+            name: make_name("up").unwrap(),
+            directory_id: 0xF000.into(),
+        });
+        assert!(matches!(
+            read(&cycle),
+            Err(Error::InvalidDirectory {
+                directory_id: 0xF000,
+                ..
+            })
+        ));
+    }
+}
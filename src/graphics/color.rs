@@ -0,0 +1,43 @@
+use crate::byte_types::int::U16;
+use byteorder::LittleEndian;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+use zerocopy::{AsBytes, FromBytes, Unaligned};
+
+/// A 15 bit color as used by the DS: 5 bits each of red, green and blue, from
+/// least to most significant
+#[repr(C)]
+#[derive(Copy, Clone, Default, FromBytes, AsBytes, Unaligned, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bgr555(pub U16<LittleEndian>);
+
+impl Bgr555 {
+    pub fn red(self) -> u8 {
+        (self.0.get() & 0x1F) as u8
+    }
+
+    pub fn green(self) -> u8 {
+        ((self.0.get() >> 5) & 0x1F) as u8
+    }
+
+    pub fn blue(self) -> u8 {
+        ((self.0.get() >> 10) & 0x1F) as u8
+    }
+
+    /// Expands the color to 8 bits per channel
+    pub fn to_rgba(self, alpha: u8) -> [u8; 4] {
+        let expand = |c: u8| (c << 3) | (c >> 2);
+        [
+            expand(self.red()),
+            expand(self.green()),
+            expand(self.blue()),
+            alpha,
+        ]
+    }
+}
+
+impl Debug for Bgr555 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Bgr555({:#06x})", self.0.get())
+    }
+}
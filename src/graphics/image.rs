@@ -0,0 +1,206 @@
+use crate::graphics::{color::Bgr555, BitDepth};
+use std::io::Write;
+
+/// An image of palette indices
+#[derive(Clone, Debug)]
+pub struct IndexedImage {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major palette indices
+    pub pixels: Vec<u8>,
+}
+
+/// An 8 bit RGBA image
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major pixels
+    pub pixels: Vec<[u8; 4]>,
+}
+
+/// Decodes the palette indices of a single 8x8 tile into `out`, row by row
+pub fn decode_tile(tile: &[u8], depth: BitDepth, out: &mut [u8; 64]) {
+    match depth {
+        BitDepth::Bpp4 => {
+            for (index, byte) in tile.iter().take(32).enumerate() {
+                out[index * 2] = byte & 0xF;
+                out[index * 2 + 1] = byte >> 4;
+            }
+        },
+        BitDepth::Bpp8 => {
+            let length = tile.len().min(64);
+            out[..length].copy_from_slice(&tile[..length]);
+        },
+    }
+}
+
+/// Mirrors a decoded 8x8 tile horizontally and/or vertically
+pub fn flip_tile(tile: &mut [u8; 64], h_flip: bool, v_flip: bool) {
+    if h_flip {
+        for row in tile.chunks_exact_mut(8) {
+            row.reverse();
+        }
+    }
+    if v_flip {
+        for row in 0..4 {
+            for column in 0..8 {
+                tile.swap(row * 8 + column, (7 - row) * 8 + column);
+            }
+        }
+    }
+}
+
+impl IndexedImage {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    /// Decodes 8x8 tiles that are stored left to right, top to bottom.
+    ///
+    /// Missing data is treated as index 0.
+    pub fn from_tiles(
+        data: &[u8],
+        depth: BitDepth,
+        width_tiles: usize,
+        height_tiles: usize,
+    ) -> Self {
+        let mut image = Self::new(width_tiles * 8, height_tiles * 8);
+
+        for (index, tile_data) in data
+            .chunks(depth.tile_size())
+            .take(width_tiles * height_tiles)
+            .enumerate()
+        {
+            let mut tile = [0; 64];
+            decode_tile(tile_data, depth, &mut tile);
+            image.blit_tile(&tile, (index % width_tiles) * 8, (index / width_tiles) * 8);
+        }
+
+        image
+    }
+
+    /// Decodes pixels that are stored row by row without tiling
+    pub fn from_linear(data: &[u8], depth: BitDepth, width: usize, height: usize) -> Self {
+        let mut image = Self::new(width, height);
+
+        match depth {
+            BitDepth::Bpp4 => {
+                for (index, byte) in data.iter().take(width * height / 2).enumerate() {
+                    image.pixels[index * 2] = byte & 0xF;
+                    image.pixels[index * 2 + 1] = byte >> 4;
+                }
+            },
+            BitDepth::Bpp8 => {
+                let length = data.len().min(width * height);
+                image.pixels[..length].copy_from_slice(&data[..length]);
+            },
+        }
+
+        image
+    }
+
+    /// Copies a decoded 8x8 tile to (`x`, `y`), clipping at the borders
+    pub fn blit_tile(&mut self, tile: &[u8; 64], x: usize, y: usize) {
+        for row in 0..8 {
+            for column in 0..8 {
+                let (px, py) = (x + column, y + row);
+                if px < self.width && py < self.height {
+                    self.pixels[py * self.width + px] = tile[row * 8 + column];
+                }
+            }
+        }
+    }
+
+    /// Resolves the indices with `palette`.
+    ///
+    /// Index 0 is fully transparent if `transparent_zero` is set, indices
+    /// outside of the palette are black.
+    pub fn to_rgba(&self, palette: &[Bgr555], transparent_zero: bool) -> Image {
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .pixels
+                .iter()
+                .map(|&index| {
+                    if index == 0 && transparent_zero {
+                        [0; 4]
+                    } else {
+                        palette
+                            .get(index as usize)
+                            .map(|color| color.to_rgba(0xFF))
+                            .unwrap_or([0, 0, 0, 0xFF])
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0; 4]; width * height],
+        }
+    }
+
+    pub fn write_png<W>(&self, writer: W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.pixels.concat().as_slice())?;
+        writer.finish()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let mut tile = [0; 64];
+        let data = (0..32).map(|i| i as u8 | 0xF0).collect::<Vec<_>>();
+        decode_tile(&data, BitDepth::Bpp4, &mut tile);
+        assert_eq!(tile[..4], [0x0, 0xF, 0x1, 0xF]);
+        assert_eq!(tile[62..], [0xF, 0xF]);
+
+        let data = (0..64).collect::<Vec<_>>();
+        decode_tile(&data, BitDepth::Bpp8, &mut tile);
+        assert_eq!(tile.to_vec(), data);
+    }
+
+    #[test]
+    fn flip() {
+        let original = std::array::from_fn::<u8, 64, _>(|i| i as u8);
+
+        let mut tile = original;
+        flip_tile(&mut tile, true, false);
+        assert_eq!(tile[..8], [7, 6, 5, 4, 3, 2, 1, 0]);
+        assert_eq!(tile[56], 63);
+
+        let mut tile = original;
+        flip_tile(&mut tile, false, true);
+        assert_eq!(tile[..8], [56, 57, 58, 59, 60, 61, 62, 63]);
+        assert_eq!(tile[63], 7);
+
+        let mut tile = original;
+        flip_tile(&mut tile, true, true);
+        assert_eq!(tile[0], 63);
+        assert_eq!(tile[63], 0);
+    }
+}
@@ -0,0 +1,36 @@
+pub mod color;
+pub mod image;
+pub mod nanr;
+pub mod ncer;
+pub mod ncgr;
+pub mod nclr;
+pub mod nmcr;
+pub mod nscr;
+
+use serde::{Deserialize, Serialize};
+
+/// Bits per pixel of palette indexed graphics
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum BitDepth {
+    Bpp4,
+    Bpp8,
+}
+
+impl BitDepth {
+    pub fn bits(self) -> usize {
+        match self {
+            BitDepth::Bpp4 => 4,
+            BitDepth::Bpp8 => 8,
+        }
+    }
+
+    /// Number of colors a single palette has at this depth
+    pub fn colors(self) -> usize {
+        1 << self.bits()
+    }
+
+    /// Size of an 8x8 tile in bytes
+    pub fn tile_size(self) -> usize {
+        8 * self.bits()
+    }
+}
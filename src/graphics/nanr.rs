@@ -0,0 +1,377 @@
+use crate::{
+    byte_types::int::{I16, I32, U16, U32},
+    error::{Error, Result, Table},
+    nitro_file::NitroFile,
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Start of the KNBA section
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct AnimationBankHeader {
+    pub sequence_count: U16<LittleEndian>,
+    pub frame_count: U16<LittleEndian>,
+    /// Relative to the start of this header
+    pub sequences_offset: U32<LittleEndian>,
+    /// Relative to the start of this header
+    pub frames_offset: U32<LittleEndian>,
+    /// Relative to the start of this header
+    pub elements_offset: U32<LittleEndian>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct SequenceHeader {
+    pub frame_count: U16<LittleEndian>,
+    pub loop_start: U16<LittleEndian>,
+    /// 0 for [ElementIndex], 1 for [ElementSrt], 2 for [ElementTranslation]
+    pub element_type: U16<LittleEndian>,
+    /// 1 for cells, 2 for multi-cells
+    pub kind: U16<LittleEndian>,
+    pub playback: U32<LittleEndian>,
+    /// Relative to the start of the frame array
+    pub frames_offset: U32<LittleEndian>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct FrameHeader {
+    /// Relative to the start of the element array
+    pub element_offset: U32<LittleEndian>,
+    /// In video frames at 60 Hz
+    pub duration: U16<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip)]
+    pub _padding: U16<LittleEndian>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct ElementIndex {
+    pub index: U16<LittleEndian>,
+}
+
+/// An element with scale, rotation and translation
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct ElementSrt {
+    pub index: U16<LittleEndian>,
+    /// 0x10000 is a full turn
+    pub rotation: U16<LittleEndian>,
+    /// 20.12 fixed point
+    pub scale_x: I32<LittleEndian>,
+    pub scale_y: I32<LittleEndian>,
+    pub x: I16<LittleEndian>,
+    pub y: I16<LittleEndian>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct ElementTranslation {
+    pub index: U16<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip)]
+    pub _padding: U16<LittleEndian>,
+    pub x: I16<LittleEndian>,
+    pub y: I16<LittleEndian>,
+}
+
+/// How a sequence is played back
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum PlaybackMode {
+    Forward,
+    /// Continues at the loop start after the last frame
+    ForwardLoop,
+    /// Plays forward, then backward
+    PingPong,
+    PingPongLoop,
+    Unknown(u32),
+}
+
+impl From<u32> for PlaybackMode {
+    fn from(raw: u32) -> Self {
+        match raw {
+            1 => PlaybackMode::Forward,
+            2 => PlaybackMode::ForwardLoop,
+            3 => PlaybackMode::PingPong,
+            4 => PlaybackMode::PingPongLoop,
+            raw => PlaybackMode::Unknown(raw),
+        }
+    }
+}
+
+/// One step of a sequence
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Frame {
+    /// The cell or multi-cell shown
+    pub index: u16,
+    /// In video frames at 60 Hz
+    pub duration: u16,
+    /// Offset of the cell in pixels
+    pub translation: Option<(i16, i16)>,
+    /// 0x10000 is a full turn
+    pub rotation: Option<u16>,
+    /// 20.12 fixed point
+    pub scale: Option<(i32, i32)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sequence {
+    pub playback: PlaybackMode,
+    /// Frame to continue at when looping
+    pub loop_start: u16,
+    pub frames: Vec<Frame>,
+}
+
+/// Animations of the cells of an NCER, or of the multi-cells of an NMCR for
+/// NMAR files, which share the layout
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Nanr {
+    pub sequences: Vec<Sequence>,
+}
+
+impl Nanr {
+    pub const ANIMATION_BANK_MAGIC: [u8; 4] = *b"KNBA";
+    pub const MAGIC: [u8; 4] = *b"RNAN";
+    pub const MULTI_CELL_MAGIC: [u8; 4] = *b"RNAM";
+
+    pub fn read(data: &[u8]) -> Result<Self> {
+        Self::read_bank(data, Self::MAGIC, Table::Nanr)
+    }
+
+    /// Reads an NMAR file
+    pub fn read_multi_cell(data: &[u8]) -> Result<Self> {
+        Self::read_bank(data, Self::MULTI_CELL_MAGIC, Table::Nmar)
+    }
+
+    fn read_bank(data: &[u8], magic: [u8; 4], table: Table) -> Result<Self> {
+        let file = NitroFile::read(data, magic, table)?;
+
+        let section = file.require_section(Self::ANIMATION_BANK_MAGIC, table)?;
+        let data = section.data;
+        let out_of_bounds = |offset: usize, expected: usize| Error::OutOfBounds {
+            table,
+            offset: section.offset + 8 + offset,
+            expected,
+            actual: data.len().saturating_sub(offset),
+        };
+
+        let (header, _) = LayoutVerified::<_, AnimationBankHeader>::new_from_prefix(data)
+            .ok_or_else(|| out_of_bounds(0, std::mem::size_of::<AnimationBankHeader>()))?;
+        let frames_start = header.frames_offset.get() as usize;
+        let elements_start = header.elements_offset.get() as usize;
+
+        let count = header.sequence_count.get() as usize;
+        let offset = header.sequences_offset.get() as usize;
+        let (sequence_headers, _) = data
+            .get(offset..)
+            .and_then(|data| {
+                LayoutVerified::<_, [SequenceHeader]>::new_slice_from_prefix(data, count)
+            })
+            .ok_or_else(|| out_of_bounds(offset, count * std::mem::size_of::<SequenceHeader>()))?;
+
+        let mut sequences = Vec::with_capacity(count);
+        for sequence in sequence_headers.iter() {
+            let count = sequence.frame_count.get() as usize;
+            let offset = frames_start + sequence.frames_offset.get() as usize;
+            let (frame_headers, _) = data
+                .get(offset..)
+                .and_then(|data| {
+                    LayoutVerified::<_, [FrameHeader]>::new_slice_from_prefix(data, count)
+                })
+                .ok_or_else(|| out_of_bounds(offset, count * std::mem::size_of::<FrameHeader>()))?;
+
+            let mut frames = Vec::with_capacity(count);
+            for frame in frame_headers.iter() {
+                let offset = elements_start + frame.element_offset.get() as usize;
+                let element = data.get(offset..).unwrap_or_default();
+                let frame = match sequence.element_type.get() {
+                    0 => LayoutVerified::<_, ElementIndex>::new_from_prefix(element)
+                        .map(|(element, _)| Frame {
+                            index: element.index.get(),
+                            duration: frame.duration.get(),
+                            translation: None,
+                            rotation: None,
+                            scale: None,
+                        })
+                        .ok_or_else(|| {
+                            out_of_bounds(offset, std::mem::size_of::<ElementIndex>())
+                        })?,
+                    1 => LayoutVerified::<_, ElementSrt>::new_from_prefix(element)
+                        .map(|(element, _)| Frame {
+                            index: element.index.get(),
+                            duration: frame.duration.get(),
+                            translation: Some((element.x.get(), element.y.get())),
+                            rotation: Some(element.rotation.get()),
+                            scale: Some((element.scale_x.get(), element.scale_y.get())),
+                        })
+                        .ok_or_else(|| out_of_bounds(offset, std::mem::size_of::<ElementSrt>()))?,
+                    2 => LayoutVerified::<_, ElementTranslation>::new_from_prefix(element)
+                        .map(|(element, _)| Frame {
+                            index: element.index.get(),
+                            duration: frame.duration.get(),
+                            translation: Some((element.x.get(), element.y.get())),
+                            rotation: None,
+                            scale: None,
+                        })
+                        .ok_or_else(|| {
+                            out_of_bounds(offset, std::mem::size_of::<ElementTranslation>())
+                        })?,
+                    value => {
+                        return Err(Error::UnsupportedFormat {
+                            table,
+                            value: value as u32,
+                        })
+                    },
+                };
+                frames.push(frame);
+            }
+
+            sequences.push(Sequence {
+                playback: sequence.playback.get().into(),
+                loop_start: sequence.loop_start.get(),
+                frames,
+            });
+        }
+
+        Ok(Self { sequences })
+    }
+}
+
+impl Sequence {
+    /// Length of one pass in video frames
+    pub fn duration(&self) -> u32 {
+        self.frames.iter().map(|frame| frame.duration as u32).sum()
+    }
+
+    /// The frame shown `time` video frames after the sequence started.
+    /// Ping-pong sequences repeat each frame when turning around.
+    pub fn frame_at(&self, time: u32) -> Option<&Frame> {
+        let forward = self.frames.iter();
+        let frames = match self.playback {
+            PlaybackMode::PingPong | PlaybackMode::PingPongLoop => {
+                forward.chain(self.frames.iter().rev()).collect::<Vec<_>>()
+            },
+            _ => forward.collect(),
+        };
+        let total = frames
+            .iter()
+            .map(|frame| frame.duration as u32)
+            .sum::<u32>();
+        let loop_start = match self.playback {
+            PlaybackMode::ForwardLoop => self
+                .frames
+                .iter()
+                .take(self.loop_start as usize)
+                .map(|frame| frame.duration as u32)
+                .sum(),
+            _ => 0,
+        };
+
+        let time = match self.playback {
+            _ if total == 0 => 0,
+            PlaybackMode::ForwardLoop | PlaybackMode::PingPongLoop if time >= total => {
+                loop_start + (time - total) % (total - loop_start).max(1)
+            },
+            _ => time,
+        };
+
+        let mut end = 0;
+        for frame in &frames {
+            end += frame.duration as u32;
+            if time < end {
+                return Some(frame);
+            }
+        }
+        // Non-looping sequences stop at their last frame
+        frames.last().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames 0, 1 and 2, shown for 2, 3 and 4 video frames
+    fn sequence(playback: PlaybackMode, loop_start: u16) -> Sequence {
+        Sequence {
+            playback,
+            loop_start,
+            frames: [2, 3, 4]
+                .into_iter()
+                .enumerate()
+                .map(|(index, duration)| Frame {
+                    index: index as u16,
+                    duration,
+                    translation: None,
+                    rotation: None,
+                    scale: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn frames(sequence: &Sequence, times: &[u32]) -> Vec<u16> {
+        times
+            .iter()
+            .map(|&time| sequence.frame_at(time).unwrap().index)
+            .collect()
+    }
+
+    #[test]
+    fn forward() {
+        let sequence = sequence(PlaybackMode::Forward, 0);
+        assert_eq!(sequence.duration(), 9);
+        assert_eq!(
+            frames(&sequence, &[0, 1, 2, 4, 5, 8, 9, 100]),
+            [0, 0, 1, 1, 2, 2, 2, 2]
+        );
+    }
+
+    #[test]
+    fn forward_loop() {
+        let sequence = sequence(PlaybackMode::ForwardLoop, 1);
+        // After the first pass, only frames 1 and 2 repeat
+        assert_eq!(
+            frames(&sequence, &[8, 9, 11, 12, 15, 16]),
+            [2, 1, 1, 2, 2, 1]
+        );
+
+        let sequence = self::sequence(PlaybackMode::ForwardLoop, 0);
+        assert_eq!(frames(&sequence, &[9, 11, 18]), [0, 1, 0]);
+    }
+
+    #[test]
+    fn ping_pong() {
+        let sequence = sequence(PlaybackMode::PingPong, 0);
+        assert_eq!(
+            frames(&sequence, &[0, 5, 9, 12, 13, 16, 17, 18, 100]),
+            [0, 2, 2, 2, 1, 0, 0, 0, 0]
+        );
+
+        let sequence = self::sequence(PlaybackMode::PingPongLoop, 0);
+        assert_eq!(frames(&sequence, &[16, 18, 20, 27]), [0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn empty() {
+        let mut sequence = sequence(PlaybackMode::ForwardLoop, 0);
+        sequence
+            .frames
+            .iter_mut()
+            .for_each(|frame| frame.duration = 0);
+        assert_eq!(sequence.frame_at(10).unwrap().index, 2);
+
+        sequence.frames.clear();
+        assert!(sequence.frame_at(0).is_none());
+    }
+}
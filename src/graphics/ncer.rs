@@ -0,0 +1,500 @@
+use crate::{
+    byte_types::int::{U16, U32},
+    error::{Error, Result, Table},
+    graphics::{
+        image::Image,
+        nanr::{Frame, Sequence},
+        ncgr::Ncgr,
+        nclr::Nclr,
+        BitDepth,
+    },
+    nitro_file::NitroFile,
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Start of the KBEC section
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct CellBankHeader {
+    pub cell_count: U16<LittleEndian>,
+    /// Bit 0 set if every cell is followed by its bounding rectangle
+    pub attributes: U16<LittleEndian>,
+    /// Relative to the start of this header
+    pub cells_offset: U32<LittleEndian>,
+    pub mapping: U32<LittleEndian>,
+    pub vram_transfer_offset: U32<LittleEndian>,
+    pub string_bank_offset: U32<LittleEndian>,
+    pub extended_offset: U32<LittleEndian>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct CellHeader {
+    pub oam_count: U16<LittleEndian>,
+    pub attributes: U16<LittleEndian>,
+    /// Relative to the end of the cell array
+    pub oam_offset: U32<LittleEndian>,
+}
+
+/// How the tile number of an object selects its tiles
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Mapping {
+    /// Tiles follow each other, the tile number counts `32 << shift` bytes
+    OneDimensional { shift: u8 },
+    /// Tiles lie in a 32 tile wide grid
+    TwoDimensional,
+}
+
+impl Mapping {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0..=3 => Some(Mapping::OneDimensional { shift: raw as u8 }),
+            4 => Some(Mapping::TwoDimensional),
+            _ => None,
+        }
+    }
+}
+
+/// A rectangle of pixels, `max` is exclusive
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Rect {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+}
+
+impl Rect {
+    pub fn width(self) -> usize {
+        (self.max_x - self.min_x).max(0) as usize
+    }
+
+    pub fn height(self) -> usize {
+        (self.max_y - self.min_y).max(0) as usize
+    }
+
+    pub fn union(self, other: Rect) -> Rect {
+        Rect {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    pub fn translate(self, x: i32, y: i32) -> Rect {
+        Rect {
+            min_x: self.min_x + x,
+            min_y: self.min_y + y,
+            max_x: self.max_x + x,
+            max_y: self.max_y + y,
+        }
+    }
+
+    /// The smallest rectangle containing all of `rects`
+    pub fn bounding(rects: impl IntoIterator<Item = Rect>) -> Option<Rect> {
+        rects.into_iter().reduce(Rect::union)
+    }
+}
+
+/// The three attribute words of a hardware sprite
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative)]
+#[derivative(Debug)]
+pub struct Oam {
+    pub attribute_0: U16<LittleEndian>,
+    pub attribute_1: U16<LittleEndian>,
+    pub attribute_2: U16<LittleEndian>,
+}
+
+impl Oam {
+    pub fn x(&self) -> i32 {
+        let x = (self.attribute_1.get() & 0x1FF) as i32;
+        if x >= 0x100 {
+            x - 0x200
+        } else {
+            x
+        }
+    }
+
+    pub fn y(&self) -> i32 {
+        (self.attribute_0.get() & 0xFF) as u8 as i8 as i32
+    }
+
+    pub fn is_affine(&self) -> bool {
+        self.attribute_0.get() & 0x100 != 0
+    }
+
+    /// Affine objects can take up twice their size
+    pub fn is_double_size(&self) -> bool {
+        self.is_affine() && self.attribute_0.get() & 0x200 != 0
+    }
+
+    pub fn depth(&self) -> BitDepth {
+        if self.attribute_0.get() & 0x2000 != 0 {
+            BitDepth::Bpp8
+        } else {
+            BitDepth::Bpp4
+        }
+    }
+
+    pub fn h_flip(&self) -> bool {
+        !self.is_affine() && self.attribute_1.get() & 0x1000 != 0
+    }
+
+    pub fn v_flip(&self) -> bool {
+        !self.is_affine() && self.attribute_1.get() & 0x2000 != 0
+    }
+
+    pub fn tile(&self) -> usize {
+        (self.attribute_2.get() & 0x3FF) as usize
+    }
+
+    /// 0 is drawn in front of everything else
+    pub fn priority(&self) -> u8 {
+        ((self.attribute_2.get() >> 10) & 3) as u8
+    }
+
+    pub fn palette(&self) -> u8 {
+        (self.attribute_2.get() >> 12) as u8
+    }
+
+    /// Width and height in pixels from the shape and size bits
+    pub fn size(&self) -> (usize, usize) {
+        let shape = self.attribute_0.get() >> 14;
+        let size = (self.attribute_1.get() >> 14) as usize;
+        match shape {
+            0 => [(8, 8), (16, 16), (32, 32), (64, 64)][size],
+            1 => [(16, 8), (32, 8), (32, 16), (64, 32)][size],
+            2 => [(8, 16), (8, 32), (16, 32), (32, 64)][size],
+            // Prohibited shape
+            _ => (8, 8),
+        }
+    }
+
+    /// The area the object covers, relative to the cell
+    pub fn bounds(&self) -> Rect {
+        let (width, height) = self.size();
+        let scale = if self.is_double_size() { 2 } else { 1 };
+        Rect {
+            min_x: self.x(),
+            min_y: self.y(),
+            max_x: self.x() + (width * scale) as i32,
+            max_y: self.y() + (height * scale) as i32,
+        }
+    }
+}
+
+/// A sprite assembled from objects
+#[derive(Clone, Debug)]
+pub struct Cell {
+    pub attributes: u16,
+    pub oams: Vec<Oam>,
+}
+
+impl Cell {
+    pub fn bounds(&self) -> Option<Rect> {
+        Rect::bounding(self.oams.iter().map(Oam::bounds))
+    }
+}
+
+/// A bank of sprite cells
+#[derive(Clone, Debug)]
+pub struct Ncer {
+    pub mapping: Mapping,
+    pub cells: Vec<Cell>,
+}
+
+impl Ncer {
+    pub const CELL_BANK_MAGIC: [u8; 4] = *b"KBEC";
+    pub const MAGIC: [u8; 4] = *b"RECN";
+
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let file = NitroFile::read(data, Self::MAGIC, Table::Ncer)?;
+
+        let section = file.require_section(Self::CELL_BANK_MAGIC, Table::Ncer)?;
+        let data = section.data;
+        let out_of_bounds = |offset: usize, expected: usize| Error::OutOfBounds {
+            table: Table::Ncer,
+            offset: section.offset + 8 + offset,
+            expected,
+            actual: data.len().saturating_sub(offset),
+        };
+
+        let (header, _) = LayoutVerified::<_, CellBankHeader>::new_from_prefix(data)
+            .ok_or_else(|| out_of_bounds(0, std::mem::size_of::<CellBankHeader>()))?;
+        let mapping = Mapping::from_raw(header.mapping.get()).ok_or(Error::UnsupportedFormat {
+            table: Table::Ncer,
+            value: header.mapping.get(),
+        })?;
+
+        let count = header.cell_count.get() as usize;
+        // Cells with bounding rectangles are followed by 4 more halfwords
+        let cell_size = if header.attributes.get() & 1 != 0 {
+            16
+        } else {
+            8
+        };
+        let cells_start = header.cells_offset.get() as usize;
+        let oams_start = cells_start + count * cell_size;
+
+        let mut cells = Vec::with_capacity(count);
+        for index in 0..count {
+            let offset = cells_start + index * cell_size;
+            let (cell, _) = data
+                .get(offset..)
+                .and_then(LayoutVerified::<_, CellHeader>::new_from_prefix)
+                .ok_or_else(|| out_of_bounds(offset, cell_size))?;
+
+            let offset = oams_start + cell.oam_offset.get() as usize;
+            let oam_count = cell.oam_count.get() as usize;
+            let (oams, _) = data
+                .get(offset..)
+                .and_then(|data| LayoutVerified::<_, [Oam]>::new_slice_from_prefix(data, oam_count))
+                .ok_or_else(|| out_of_bounds(offset, oam_count * std::mem::size_of::<Oam>()))?;
+
+            cells.push(Cell {
+                attributes: cell.attributes.get(),
+                oams: oams.to_vec(),
+            });
+        }
+
+        Ok(Self { mapping, cells })
+    }
+
+    /// Index of the tile at (`column`, `row`) of an object in `ncgr`
+    fn tile_index(&self, oam: &Oam, ncgr: &Ncgr, column: usize, row: usize) -> usize {
+        let (width, _) = oam.size();
+        match self.mapping {
+            Mapping::OneDimensional { shift } => {
+                oam.tile() * (32 << shift) / ncgr.depth.tile_size() + row * (width / 8) + column
+            },
+            Mapping::TwoDimensional => {
+                let row_length = 32 * 32 / ncgr.depth.tile_size();
+                oam.tile() * 32 / ncgr.depth.tile_size() + row * row_length + column
+            },
+        }
+    }
+
+    /// Draws `cell` into `image`, with the origin of the cell at (`x`, `y`)
+    /// of the image. Affine transformations are not applied.
+    pub fn draw_cell(
+        &self,
+        cell: &Cell,
+        ncgr: &Ncgr,
+        nclr: &Nclr,
+        image: &mut Image,
+        x: i32,
+        y: i32,
+    ) {
+        // Objects with a lower priority value, then earlier ones, end up on top
+        let mut order = (0..cell.oams.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&index| {
+            (
+                std::cmp::Reverse(cell.oams[index].priority()),
+                std::cmp::Reverse(index),
+            )
+        });
+
+        for oam in order.into_iter().map(|index| &cell.oams[index]) {
+            let colors = nclr.colors_for(oam.depth(), oam.palette() as u16);
+
+            let (width, height) = oam.size();
+            // Double size objects are centered in their doubled area
+            let (offset_x, offset_y) = if oam.is_double_size() {
+                (width as i32 / 2, height as i32 / 2)
+            } else {
+                (0, 0)
+            };
+
+            for row in 0..(height / 8) {
+                for column in 0..(width / 8) {
+                    let tile = match ncgr.tile(self.tile_index(oam, ncgr, column, row)) {
+                        Some(tile) => tile,
+                        None => continue,
+                    };
+
+                    for (pixel, &color) in tile.iter().enumerate() {
+                        if color == 0 {
+                            continue;
+                        }
+                        let color = match colors.get(color as usize) {
+                            Some(color) => color,
+                            None => continue,
+                        };
+
+                        let mut px = column * 8 + pixel % 8;
+                        let mut py = row * 8 + pixel / 8;
+                        if oam.h_flip() {
+                            px = width - 1 - px;
+                        }
+                        if oam.v_flip() {
+                            py = height - 1 - py;
+                        }
+
+                        let ix = x + oam.x() + offset_x + px as i32;
+                        let iy = y + oam.y() + offset_y + py as i32;
+                        if (0..image.width as i32).contains(&ix)
+                            && (0..image.height as i32).contains(&iy)
+                        {
+                            image.pixels[iy as usize * image.width + ix as usize] =
+                                color.to_rgba(0xFF);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Renders `cell` onto a transparent canvas covering `canvas`, in cell
+    /// coordinates
+    pub fn render_cell(&self, cell: &Cell, ncgr: &Ncgr, nclr: &Nclr, canvas: Rect) -> Image {
+        let mut image = Image::new(canvas.width(), canvas.height());
+        self.draw_cell(cell, ncgr, nclr, &mut image, -canvas.min_x, -canvas.min_y);
+        image
+    }
+
+    /// The area covered by all cells, so that renders line up
+    pub fn bounds(&self) -> Option<Rect> {
+        Rect::bounding(self.cells.iter().filter_map(Cell::bounds))
+    }
+
+    /// Draws the cell of an animation frame, moved by its translation
+    pub fn draw_frame(
+        &self,
+        frame: &Frame,
+        ncgr: &Ncgr,
+        nclr: &Nclr,
+        image: &mut Image,
+        x: i32,
+        y: i32,
+    ) {
+        if let Some(cell) = self.cells.get(frame.index as usize) {
+            let (dx, dy) = frame.translation.unwrap_or_default();
+            self.draw_cell(cell, ncgr, nclr, image, x + dx as i32, y + dy as i32);
+        }
+    }
+
+    /// Renders the cell of an animation frame onto a transparent canvas
+    /// covering `canvas`, in cell coordinates
+    pub fn render_frame(&self, frame: &Frame, ncgr: &Ncgr, nclr: &Nclr, canvas: Rect) -> Image {
+        let mut image = Image::new(canvas.width(), canvas.height());
+        self.draw_frame(frame, ncgr, nclr, &mut image, -canvas.min_x, -canvas.min_y);
+        image
+    }
+
+    pub fn frame_bounds(&self, frame: &Frame) -> Option<Rect> {
+        let (dx, dy) = frame.translation.unwrap_or_default();
+        let bounds = self.cells.get(frame.index as usize)?.bounds()?;
+        Some(bounds.translate(dx as i32, dy as i32))
+    }
+
+    /// The area covered by all frames of `sequence`
+    pub fn sequence_bounds(&self, sequence: &Sequence) -> Option<Rect> {
+        Rect::bounding(
+            sequence
+                .frames
+                .iter()
+                .filter_map(|frame| self.frame_bounds(frame)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oam(attribute_0: u16, attribute_1: u16, attribute_2: u16) -> Oam {
+        Oam {
+            attribute_0: attribute_0.into(),
+            attribute_1: attribute_1.into(),
+            attribute_2: attribute_2.into(),
+        }
+    }
+
+    fn ncgr(depth: BitDepth) -> Ncgr {
+        Ncgr {
+            depth,
+            mapping: 0,
+            linear: false,
+            size: None,
+            data: vec![],
+        }
+    }
+
+    #[test]
+    fn position() {
+        assert_eq!((oam(0x7F, 0xFF, 0).x(), oam(0x7F, 0xFF, 0).y()), (255, 127));
+        assert_eq!((oam(0xFF, 0x1FF, 0).x(), oam(0xFF, 0x1FF, 0).y()), (-1, -1));
+        assert_eq!(
+            (oam(0x80, 0x100, 0).x(), oam(0x80, 0x100, 0).y()),
+            (-256, -128)
+        );
+        // Only the low bits hold the position
+        assert_eq!(oam(0xFF00, 0xFE00, 0).x(), 0);
+        assert_eq!(oam(0xFF00, 0xFE00, 0).y(), 0);
+    }
+
+    #[test]
+    fn size() {
+        assert_eq!(oam(0x0000, 0x0000, 0).size(), (8, 8));
+        assert_eq!(oam(0x0000, 0xC000, 0).size(), (64, 64));
+        assert_eq!(oam(0x4000, 0x8000, 0).size(), (32, 16));
+        assert_eq!(oam(0x8000, 0x4000, 0).size(), (8, 32));
+        assert_eq!(oam(0xC000, 0xC000, 0).size(), (8, 8));
+
+        // Double size affine objects cover twice their size
+        let object = oam(0x43F0, 0x81F8, 0);
+        assert_eq!(
+            object.bounds(),
+            Rect {
+                min_x: -8,
+                min_y: -16,
+                max_x: 56,
+                max_y: 16,
+            }
+        );
+    }
+
+    #[test]
+    fn tile_index_1d() {
+        let ncer = |shift| Ncer {
+            mapping: Mapping::OneDimensional { shift },
+            cells: vec![],
+        };
+        // 16x16 starting at tile 3
+        let object = oam(0, 0x4000, 3);
+
+        let bpp4 = ncgr(BitDepth::Bpp4);
+        assert_eq!(ncer(0).tile_index(&object, &bpp4, 0, 0), 3);
+        assert_eq!(ncer(0).tile_index(&object, &bpp4, 1, 1), 6);
+        assert_eq!(ncer(1).tile_index(&object, &bpp4, 0, 0), 6);
+
+        let bpp8 = ncgr(BitDepth::Bpp8);
+        assert_eq!(ncer(1).tile_index(&object, &bpp8, 0, 0), 3);
+        assert_eq!(ncer(1).tile_index(&object, &bpp8, 1, 1), 6);
+    }
+
+    #[test]
+    fn tile_index_2d() {
+        let ncer = Ncer {
+            mapping: Mapping::TwoDimensional,
+            cells: vec![],
+        };
+        let object = oam(0, 0x4000, 4);
+
+        let bpp4 = ncgr(BitDepth::Bpp4);
+        assert_eq!(ncer.tile_index(&object, &bpp4, 1, 0), 5);
+        assert_eq!(ncer.tile_index(&object, &bpp4, 1, 1), 37);
+
+        // 8 bit tiles take two tile numbers and rows hold 16 of them
+        let bpp8 = ncgr(BitDepth::Bpp8);
+        assert_eq!(ncer.tile_index(&object, &bpp8, 0, 0), 2);
+        assert_eq!(ncer.tile_index(&object, &bpp8, 1, 1), 19);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    byte_types::int::{U16, U32},
+    error::{Error, Result, Table},
+    graphics::{
+        color::Bgr555,
+        image::{decode_tile, Image, IndexedImage},
+        nclr::Nclr,
+        BitDepth,
+    },
+    nitro_file::NitroFile,
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Start of the RAHC section
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct CharacterHeader {
+    /// 0xFFFF if the graphics have no fixed size, e.g. for sprites
+    pub height_tiles: U16<LittleEndian>,
+    pub width_tiles: U16<LittleEndian>,
+    /// 3 for 4 bits per pixel, 4 for 8 bits per pixel
+    pub bit_depth: U32<LittleEndian>,
+    /// 0 for 2D mapping, otherwise the 1D boundary of sprite tiles
+    pub mapping: U32<LittleEndian>,
+    /// Bit 0 set for linear (bitmap) data instead of 8x8 tiles
+    pub format: U32<LittleEndian>,
+    pub data_size: U32<LittleEndian>,
+    /// Relative to the start of this header
+    pub data_offset: U32<LittleEndian>,
+}
+
+/// Tile graphics
+#[derive(Clone, Debug)]
+pub struct Ncgr {
+    pub depth: BitDepth,
+    pub mapping: u32,
+    /// Pixels are stored row by row over the whole image instead of in tiles
+    pub linear: bool,
+    /// The size in tiles, if the file specifies one
+    pub size: Option<(usize, usize)>,
+    pub data: Vec<u8>,
+}
+
+impl Ncgr {
+    pub const CHARACTER_MAGIC: [u8; 4] = *b"RAHC";
+    /// Width in tiles used when the file doesn't specify a size
+    pub const DEFAULT_WIDTH: usize = 32;
+    pub const MAGIC: [u8; 4] = *b"RGCN";
+
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let file = NitroFile::read(data, Self::MAGIC, Table::Ncgr)?;
+
+        let section = file.require_section(Self::CHARACTER_MAGIC, Table::Ncgr)?;
+        let out_of_bounds = |expected| Error::OutOfBounds {
+            table: Table::Ncgr,
+            offset: section.offset,
+            expected,
+            actual: section.data.len(),
+        };
+
+        let (header, _) = LayoutVerified::<_, CharacterHeader>::new_from_prefix(section.data)
+            .ok_or_else(|| out_of_bounds(std::mem::size_of::<CharacterHeader>()))?;
+        let depth =
+            BitDepth::from_format(header.bit_depth.get()).ok_or(Error::UnsupportedFormat {
+                table: Table::Ncgr,
+                value: header.bit_depth.get(),
+            })?;
+
+        let start = header.data_offset.get() as usize;
+        let raw = section
+            .data
+            .get(start..)
+            .ok_or_else(|| out_of_bounds(start))?;
+        let raw = &raw[..raw.len().min(header.data_size.get() as usize)];
+
+        let (height, width) = (header.height_tiles.get(), header.width_tiles.get());
+        let size = (height != 0xFFFF && width != 0xFFFF && height != 0 && width != 0)
+            .then_some((width as usize, height as usize));
+
+        Ok(Self {
+            depth,
+            mapping: header.mapping.get(),
+            linear: header.format.get() & 1 != 0,
+            size,
+            data: raw.to_vec(),
+        })
+    }
+
+    pub fn tile_count(&self) -> usize {
+        self.data.len() / self.depth.tile_size()
+    }
+
+    /// The palette indices of tile `index`, row by row
+    pub fn tile(&self, index: usize) -> Option<[u8; 64]> {
+        let size = self.depth.tile_size();
+        let data = self.data.get((index * size)..((index + 1) * size))?;
+        let mut tile = [0; 64];
+        decode_tile(data, self.depth, &mut tile);
+        Some(tile)
+    }
+
+    /// Size in tiles, either as stored or `width` tiles wide (defaulting to
+    /// [Ncgr::DEFAULT_WIDTH]) and as high as needed. The stored size is only
+    /// used if the data has that many tiles.
+    pub fn dimensions(&self, width: Option<usize>) -> (usize, usize) {
+        match (self.size, width) {
+            (Some((width, height)), None) if width * height <= self.tile_count() => (width, height),
+            (_, width) => {
+                let width = width
+                    .unwrap_or(Self::DEFAULT_WIDTH)
+                    .clamp(1, self.tile_count().max(1));
+                (width, self.tile_count().div_ceil(width))
+            },
+        }
+    }
+
+    /// Decodes all graphics into one image, see [Ncgr::dimensions]
+    pub fn to_indexed(&self, width: Option<usize>) -> IndexedImage {
+        let (width, height) = self.dimensions(width);
+        if self.linear {
+            IndexedImage::from_linear(&self.data, self.depth, width * 8, height * 8)
+        } else {
+            IndexedImage::from_tiles(&self.data, self.depth, width, height)
+        }
+    }
+
+    /// The colors these graphics use with palette `palette_id`, see
+    /// [Nclr::colors_for]
+    pub fn palette_colors(&self, nclr: &Nclr, palette_id: u16) -> Vec<Bgr555> {
+        nclr.colors_for(self.depth, palette_id).into_owned()
+    }
+
+    /// Renders all graphics with palette `palette_id` of `nclr`. Color 0 is
+    /// transparent.
+    pub fn render(&self, nclr: &Nclr, palette_id: u16, width: Option<usize>) -> Image {
+        self.to_indexed(width)
+            .to_rgba(&self.palette_colors(nclr, palette_id), true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nitro_file::tests::build;
+
+    /// A 4 bpp NCGR of `tiles` tiles, each filled with its index
+    fn ncgr(width: u16, height: u16, tiles: u8) -> Vec<u8> {
+        let data = (0..tiles)
+            .flat_map(|tile| [tile | tile << 4; 32])
+            .collect::<Vec<_>>();
+        let header = CharacterHeader {
+            height_tiles: height.into(),
+            width_tiles: width.into(),
+            bit_depth: 3.into(),
+            mapping: 0.into(),
+            format: 0.into(),
+            data_size: (data.len() as u32).into(),
+            data_offset: (std::mem::size_of::<CharacterHeader>() as u32).into(),
+        };
+        let mut character = header.as_bytes().to_vec();
+        character.extend_from_slice(&data);
+        build(Ncgr::MAGIC, &[(Ncgr::CHARACTER_MAGIC, &character)])
+    }
+
+    #[test]
+    fn read() {
+        let ncgr = Ncgr::read(&ncgr(2, 3, 6)).unwrap();
+
+        assert_eq!(ncgr.depth, BitDepth::Bpp4);
+        assert_eq!(ncgr.size, Some((2, 3)));
+        assert_eq!(ncgr.tile_count(), 6);
+        assert_eq!(ncgr.tile(5), Some([5; 64]));
+        assert_eq!(ncgr.tile(6), None);
+        assert_eq!(ncgr.dimensions(None), (2, 3));
+        assert_eq!(ncgr.dimensions(Some(4)), (4, 2));
+    }
+
+    #[test]
+    fn no_size() {
+        let ncgr = Ncgr::read(&ncgr(0xFFFF, 0xFFFF, 40)).unwrap();
+
+        assert_eq!(ncgr.size, None);
+        assert_eq!(ncgr.dimensions(None), (Ncgr::DEFAULT_WIDTH, 2));
+    }
+
+    #[test]
+    fn size_larger_than_data() {
+        let ncgr = Ncgr::read(&ncgr(0xFFFE, 0xFFFE, 3)).unwrap();
+
+        assert_eq!(ncgr.size, Some((0xFFFE, 0xFFFE)));
+        assert_eq!(ncgr.dimensions(None), (3, 1));
+        let image = ncgr.to_indexed(None);
+        assert_eq!((image.width, image.height), (24, 8));
+    }
+}
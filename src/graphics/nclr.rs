@@ -0,0 +1,323 @@
+use crate::{
+    byte_types::int::{U16, U32},
+    error::{Error, Result, Table},
+    graphics::{color::Bgr555, image::Image, BitDepth},
+    nitro_file::NitroFile,
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Start of the TTLP section
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct PaletteHeader {
+    /// 3 for 4 bits per pixel, 4 for 8 bits per pixel
+    pub bit_depth: U32<LittleEndian>,
+    pub extended: U32<LittleEndian>,
+    pub data_size: U32<LittleEndian>,
+    /// Relative to the start of this header
+    pub data_offset: U32<LittleEndian>,
+}
+
+/// Start of the optional PMCP section, which names the stored palettes
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct PaletteCompressionHeader {
+    pub palette_count: U16<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip)]
+    pub _unknown: U16<LittleEndian>,
+    /// Relative to the start of this header
+    pub ids_offset: U32<LittleEndian>,
+}
+
+impl BitDepth {
+    /// Reads the bit depth field shared by NCLR and NCGR
+    pub fn from_format(format: u32) -> Option<Self> {
+        match format {
+            3 => Some(BitDepth::Bpp4),
+            4 => Some(BitDepth::Bpp8),
+            _ => None,
+        }
+    }
+}
+
+/// A single palette of an [Nclr]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Palette {
+    /// Palette number used by tiles and screens to select it
+    pub id: u16,
+    pub colors: Vec<Bgr555>,
+}
+
+/// A color palette file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Nclr {
+    pub depth: BitDepth,
+    /// Whether the palettes are meant for extended palette slots
+    pub extended: bool,
+    pub palettes: Vec<Palette>,
+}
+
+impl Nclr {
+    pub const COMPRESSION_MAGIC: [u8; 4] = *b"PMCP";
+    pub const MAGIC: [u8; 4] = *b"RLCN";
+    pub const PALETTE_MAGIC: [u8; 4] = *b"TTLP";
+    /// Width and height of a color in [Nclr::swatches]
+    pub const SWATCH_SIZE: usize = 8;
+
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let file = NitroFile::read(data, Self::MAGIC, Table::Nclr)?;
+
+        let section = file.require_section(Self::PALETTE_MAGIC, Table::Nclr)?;
+        let out_of_bounds = |expected| Error::OutOfBounds {
+            table: Table::Nclr,
+            offset: section.offset,
+            expected,
+            actual: section.data.len(),
+        };
+
+        let (header, _) = LayoutVerified::<_, PaletteHeader>::new_from_prefix(section.data)
+            .ok_or_else(|| out_of_bounds(std::mem::size_of::<PaletteHeader>()))?;
+        let depth =
+            BitDepth::from_format(header.bit_depth.get()).ok_or(Error::UnsupportedFormat {
+                table: Table::Nclr,
+                value: header.bit_depth.get(),
+            })?;
+
+        // The size is larger than the section in some files, so it is clamped
+        let start = header.data_offset.get() as usize;
+        let raw = section
+            .data
+            .get(start..)
+            .ok_or_else(|| out_of_bounds(start))?;
+        let raw = &raw[..raw.len().min(header.data_size.get() as usize) & !1];
+        let colors = LayoutVerified::<_, [Bgr555]>::new_slice(raw)
+            .expect("the length is even")
+            .to_vec();
+
+        let ids = match file.section(Self::COMPRESSION_MAGIC) {
+            Some(section) => Some(Self::read_ids(section.data, section.offset)?),
+            None => None,
+        };
+
+        let palettes = colors
+            .chunks(depth.colors())
+            .enumerate()
+            .map(|(index, colors)| Palette {
+                id: ids
+                    .as_ref()
+                    .and_then(|ids| ids.get(index).copied())
+                    .unwrap_or(index as u16),
+                colors: colors.to_vec(),
+            })
+            .collect();
+
+        Ok(Self {
+            depth,
+            extended: header.extended.get() != 0,
+            palettes,
+        })
+    }
+
+    fn read_ids(data: &[u8], offset: usize) -> Result<Vec<u16>> {
+        let out_of_bounds = |expected| Error::OutOfBounds {
+            table: Table::Nclr,
+            offset,
+            expected,
+            actual: data.len(),
+        };
+
+        let (header, _) = LayoutVerified::<_, PaletteCompressionHeader>::new_from_prefix(data)
+            .ok_or_else(|| out_of_bounds(std::mem::size_of::<PaletteCompressionHeader>()))?;
+        let count = header.palette_count.get() as usize;
+        let start = header.ids_offset.get() as usize;
+
+        let (ids, _) = data
+            .get(start..)
+            .and_then(|ids| {
+                LayoutVerified::<_, [U16<LittleEndian>]>::new_slice_from_prefix(ids, count)
+            })
+            .ok_or_else(|| out_of_bounds(start + count * 2))?;
+        Ok(ids.iter().map(|id| id.get()).collect())
+    }
+
+    /// The palette with the given number
+    pub fn palette(&self, id: u16) -> Option<&[Bgr555]> {
+        self.palettes
+            .iter()
+            .find(|palette| palette.id == id)
+            .map(|palette| palette.colors.as_slice())
+    }
+
+    /// The 16 colors 4 bit graphics use with palette `id`. Files with 8 bit
+    /// palettes hold them as one block of 256 colors, like palette memory.
+    pub fn bpp4_palette(&self, id: u16) -> Option<&[Bgr555]> {
+        match self.depth {
+            BitDepth::Bpp4 => self.palette(id),
+            BitDepth::Bpp8 => {
+                let colors = &self.palettes.first()?.colors;
+                let start = id as usize * 16;
+                colors
+                    .get(start..)
+                    .filter(|colors| !colors.is_empty())
+                    .map(|colors| &colors[..colors.len().min(16)])
+            },
+        }
+    }
+
+    /// The colors graphics of `depth` use with palette number `palette`, as
+    /// stored in tiles, screen entries or OAMs.
+    ///
+    /// 4 bit graphics use [Nclr::bpp4_palette], 8 bit graphics all colors, or
+    /// with extended palettes the 256 colors of palette `palette`.
+    pub fn colors_for(&self, depth: BitDepth, palette: u16) -> Cow<'_, [Bgr555]> {
+        match (depth, self.extended) {
+            (BitDepth::Bpp4, _) => Cow::Borrowed(self.bpp4_palette(palette).unwrap_or_default()),
+            (BitDepth::Bpp8, false) => match self.palettes.as_slice() {
+                [single] => Cow::Borrowed(&single.colors),
+                _ => Cow::Owned(self.colors()),
+            },
+            (BitDepth::Bpp8, true) => Cow::Borrowed(self.palette(palette).unwrap_or_default()),
+        }
+    }
+
+    /// All colors in order, as used by 8 bit graphics without extended
+    /// palettes
+    pub fn colors(&self) -> Vec<Bgr555> {
+        self.palettes
+            .iter()
+            .flat_map(|palette| palette.colors.iter().copied())
+            .collect()
+    }
+
+    /// Renders all colors as squares, 16 per row, each palette starting on a
+    /// new row
+    pub fn swatches(&self) -> Image {
+        let rows = self
+            .palettes
+            .iter()
+            .map(|palette| palette.colors.len().div_ceil(16))
+            .sum::<usize>();
+        let mut image = Image::new(16 * Self::SWATCH_SIZE, rows * Self::SWATCH_SIZE);
+
+        let mut row = 0;
+        for palette in &self.palettes {
+            for (index, color) in palette.colors.iter().enumerate() {
+                let x = (index % 16) * Self::SWATCH_SIZE;
+                let y = (row + index / 16) * Self::SWATCH_SIZE;
+                for py in y..(y + Self::SWATCH_SIZE) {
+                    let line = py * image.width;
+                    image.pixels[(line + x)..(line + x + Self::SWATCH_SIZE)]
+                        .fill(color.to_rgba(0xFF));
+                }
+            }
+            row += palette.colors.len().div_ceil(16);
+        }
+
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nitro_file::tests::build;
+
+    /// An NCLR with colors 0 to `count`, optionally naming the palettes
+    fn nclr(depth: u32, count: u16, ids: Option<&[u16]>) -> Vec<u8> {
+        let colors = (0..count).flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+        let header = PaletteHeader {
+            bit_depth: depth.into(),
+            extended: 0.into(),
+            data_size: (colors.len() as u32).into(),
+            data_offset: (std::mem::size_of::<PaletteHeader>() as u32).into(),
+        };
+        let mut palette = header.as_bytes().to_vec();
+        palette.extend_from_slice(&colors);
+
+        let mut compression = Vec::new();
+        if let Some(ids) = ids {
+            let header = PaletteCompressionHeader {
+                palette_count: (ids.len() as u16).into(),
+                _unknown: 0.into(),
+                ids_offset: (std::mem::size_of::<PaletteCompressionHeader>() as u32).into(),
+            };
+            compression.extend_from_slice(header.as_bytes());
+            compression.extend(ids.iter().flat_map(|id| id.to_le_bytes()));
+        }
+
+        let mut sections = vec![(Nclr::PALETTE_MAGIC, palette.as_slice())];
+        if ids.is_some() {
+            sections.push((Nclr::COMPRESSION_MAGIC, compression.as_slice()));
+        }
+        build(Nclr::MAGIC, &sections)
+    }
+
+    fn values(colors: &[Bgr555]) -> Vec<u16> {
+        colors.iter().map(|color| color.0.get()).collect()
+    }
+
+    #[test]
+    fn read() {
+        let nclr = Nclr::read(&nclr(3, 32, None)).unwrap();
+
+        assert_eq!(nclr.depth, BitDepth::Bpp4);
+        assert!(!nclr.extended);
+        assert_eq!(nclr.palettes.len(), 2);
+        assert_eq!(nclr.palettes[1].id, 1);
+        assert_eq!(
+            values(nclr.palette(1).unwrap()),
+            (16..32).collect::<Vec<_>>()
+        );
+        assert_eq!(nclr.colors().len(), 32);
+    }
+
+    #[test]
+    fn read_with_ids() {
+        let nclr = Nclr::read(&nclr(3, 32, Some(&[3, 7]))).unwrap();
+
+        let ids = nclr.palettes.iter().map(|palette| palette.id);
+        assert_eq!(ids.collect::<Vec<_>>(), [3, 7]);
+        assert_eq!(
+            values(nclr.palette(7).unwrap()),
+            (16..32).collect::<Vec<_>>()
+        );
+        assert!(nclr.palette(1).is_none());
+        assert_eq!(
+            values(&nclr.colors_for(BitDepth::Bpp4, 3)),
+            (0..16).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn bpp4_palette_of_8bpp() {
+        let nclr = Nclr::read(&nclr(4, 256, None)).unwrap();
+
+        assert_eq!(nclr.depth, BitDepth::Bpp8);
+        assert_eq!(nclr.palettes.len(), 1);
+        assert_eq!(
+            values(nclr.bpp4_palette(2).unwrap()),
+            (32..48).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            values(&nclr.colors_for(BitDepth::Bpp4, 15)),
+            (240..256).collect::<Vec<_>>()
+        );
+        assert!(nclr.bpp4_palette(16).is_none());
+        assert_eq!(nclr.colors_for(BitDepth::Bpp8, 5).len(), 256);
+    }
+
+    #[test]
+    fn unsupported_depth() {
+        assert!(matches!(
+            Nclr::read(&nclr(5, 16, None)),
+            Err(Error::UnsupportedFormat { value: 5, .. })
+        ));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{
+    byte_types::int::{I16, U16, U32},
+    error::{Error, Result, Table},
+    graphics::{
+        image::Image,
+        nanr::Nanr,
+        ncer::{Ncer, Rect},
+        ncgr::Ncgr,
+        nclr::Nclr,
+    },
+    nitro_file::NitroFile,
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Start of the KBCM section
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct MultiCellBankHeader {
+    pub multi_cell_count: U16<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip)]
+    pub _padding: U16<LittleEndian>,
+    /// Relative to the start of this header
+    pub multi_cells_offset: U32<LittleEndian>,
+    /// Relative to the start of this header
+    pub nodes_offset: U32<LittleEndian>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct MultiCellHeader {
+    pub node_count: U16<LittleEndian>,
+    pub cell_animation_count: U16<LittleEndian>,
+    /// Relative to the start of the node array
+    pub nodes_offset: U32<LittleEndian>,
+}
+
+/// A cell animation placed in a multi-cell
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct Node {
+    /// Sequence of the cell animation (NANR) that is shown
+    pub sequence: U16<LittleEndian>,
+    pub x: I16<LittleEndian>,
+    pub y: I16<LittleEndian>,
+    /// Bits 8-15 are the index of the cell animation instance, which nodes
+    /// may share
+    pub attributes: U16<LittleEndian>,
+}
+
+/// A group of cell animations shown together
+#[derive(Clone, Debug)]
+pub struct MultiCell {
+    pub nodes: Vec<Node>,
+}
+
+/// A bank of multi-cells
+#[derive(Clone, Debug)]
+pub struct Nmcr {
+    pub multi_cells: Vec<MultiCell>,
+}
+
+impl Nmcr {
+    pub const MAGIC: [u8; 4] = *b"RCMN";
+    pub const MULTI_CELL_BANK_MAGIC: [u8; 4] = *b"KBCM";
+
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let file = NitroFile::read(data, Self::MAGIC, Table::Nmcr)?;
+
+        let section = file.require_section(Self::MULTI_CELL_BANK_MAGIC, Table::Nmcr)?;
+        let data = section.data;
+        let out_of_bounds = |offset: usize, expected: usize| Error::OutOfBounds {
+            table: Table::Nmcr,
+            offset: section.offset + 8 + offset,
+            expected,
+            actual: data.len().saturating_sub(offset),
+        };
+
+        let (header, _) = LayoutVerified::<_, MultiCellBankHeader>::new_from_prefix(data)
+            .ok_or_else(|| out_of_bounds(0, std::mem::size_of::<MultiCellBankHeader>()))?;
+        let nodes_start = header.nodes_offset.get() as usize;
+
+        let count = header.multi_cell_count.get() as usize;
+        let offset = header.multi_cells_offset.get() as usize;
+        let (headers, _) = data
+            .get(offset..)
+            .and_then(|data| {
+                LayoutVerified::<_, [MultiCellHeader]>::new_slice_from_prefix(data, count)
+            })
+            .ok_or_else(|| out_of_bounds(offset, count * std::mem::size_of::<MultiCellHeader>()))?;
+
+        let multi_cells = headers
+            .iter()
+            .map(|multi_cell| {
+                let count = multi_cell.node_count.get() as usize;
+                let offset = nodes_start + multi_cell.nodes_offset.get() as usize;
+                let (nodes, _) = data
+                    .get(offset..)
+                    .and_then(|data| {
+                        LayoutVerified::<_, [Node]>::new_slice_from_prefix(data, count)
+                    })
+                    .ok_or_else(|| out_of_bounds(offset, count * std::mem::size_of::<Node>()))?;
+                Ok(MultiCell {
+                    nodes: nodes.to_vec(),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { multi_cells })
+    }
+}
+
+impl MultiCell {
+    /// The area covered by all frames of the node animations
+    pub fn bounds(&self, nanr: &Nanr, ncer: &Ncer) -> Option<Rect> {
+        Rect::bounding(self.nodes.iter().filter_map(|node| {
+            let sequence = nanr.sequences.get(node.sequence.get() as usize)?;
+            let bounds = ncer.sequence_bounds(sequence)?;
+            Some(bounds.translate(node.x.get() as i32, node.y.get() as i32))
+        }))
+    }
+
+    /// Renders every node with the frame of its cell animation that is shown
+    /// `time` video frames after the start, onto a transparent canvas
+    /// covering `canvas`. Earlier nodes end up on top.
+    pub fn render(
+        &self,
+        nanr: &Nanr,
+        ncer: &Ncer,
+        ncgr: &Ncgr,
+        nclr: &Nclr,
+        time: u32,
+        canvas: Rect,
+    ) -> Image {
+        let mut image = Image::new(canvas.width(), canvas.height());
+        for node in self.nodes.iter().rev() {
+            let frame = nanr
+                .sequences
+                .get(node.sequence.get() as usize)
+                .and_then(|sequence| sequence.frame_at(time));
+            if let Some(frame) = frame {
+                ncer.draw_frame(
+                    frame,
+                    ncgr,
+                    nclr,
+                    &mut image,
+                    node.x.get() as i32 - canvas.min_x,
+                    node.y.get() as i32 - canvas.min_y,
+                );
+            }
+        }
+        image
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{
+    byte_types::int::{U16, U32},
+    error::{Error, Result, Table},
+    graphics::{
+        image::{flip_tile, Image},
+        ncgr::Ncgr,
+        nclr::Nclr,
+    },
+    nitro_file::NitroFile,
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Start of the NRCS section
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct ScreenHeader {
+    /// In pixels
+    pub width: U16<LittleEndian>,
+    pub height: U16<LittleEndian>,
+    pub screen_size: U16<LittleEndian>,
+    /// 0 for text backgrounds, 1 for affine, 2 for extended affine
+    pub background_type: U16<LittleEndian>,
+    pub data_size: U32<LittleEndian>,
+}
+
+/// How a background is drawn, which decides the entry size
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum BackgroundType {
+    /// 16 bit entries with flips and palette
+    Text,
+    /// 8 bit entries with only the tile index
+    Affine,
+    /// 16 bit entries like [BackgroundType::Text]
+    ExtendedAffine,
+}
+
+/// One 8x8 tile on the screen
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ScreenEntry {
+    pub tile: u16,
+    pub h_flip: bool,
+    pub v_flip: bool,
+    pub palette: u8,
+}
+
+impl ScreenEntry {
+    pub fn from_raw(raw: u16) -> Self {
+        Self {
+            tile: raw & 0x3FF,
+            h_flip: raw & 0x400 != 0,
+            v_flip: raw & 0x800 != 0,
+            palette: (raw >> 12) as u8,
+        }
+    }
+
+    pub fn to_raw(self) -> u16 {
+        self.tile & 0x3FF
+            | (self.h_flip as u16) << 10
+            | (self.v_flip as u16) << 11
+            | (self.palette as u16 & 0xF) << 12
+    }
+}
+
+/// A background map of tiles from an [Ncgr]
+#[derive(Clone, Debug)]
+pub struct Nscr {
+    /// In tiles
+    pub width: usize,
+    pub height: usize,
+    pub background_type: BackgroundType,
+    /// Row-major
+    pub entries: Vec<ScreenEntry>,
+}
+
+impl Nscr {
+    pub const MAGIC: [u8; 4] = *b"RCSN";
+    pub const SCREEN_MAGIC: [u8; 4] = *b"NRCS";
+
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let file = NitroFile::read(data, Self::MAGIC, Table::Nscr)?;
+
+        let section = file.require_section(Self::SCREEN_MAGIC, Table::Nscr)?;
+        let (header, raw) = LayoutVerified::<_, ScreenHeader>::new_from_prefix(section.data)
+            .ok_or(Error::OutOfBounds {
+                table: Table::Nscr,
+                offset: section.offset,
+                expected: std::mem::size_of::<ScreenHeader>(),
+                actual: section.data.len(),
+            })?;
+        let raw = &raw[..raw.len().min(header.data_size.get() as usize)];
+
+        let background_type = match header.background_type.get() {
+            0 => BackgroundType::Text,
+            1 => BackgroundType::Affine,
+            2 => BackgroundType::ExtendedAffine,
+            value => {
+                return Err(Error::UnsupportedFormat {
+                    table: Table::Nscr,
+                    value: value as u32,
+                })
+            },
+        };
+
+        let entries = match background_type {
+            BackgroundType::Affine => raw
+                .iter()
+                .map(|&tile| ScreenEntry {
+                    tile: tile as u16,
+                    h_flip: false,
+                    v_flip: false,
+                    palette: 0,
+                })
+                .collect(),
+            BackgroundType::Text | BackgroundType::ExtendedAffine => raw
+                .chunks_exact(2)
+                .map(|raw| ScreenEntry::from_raw(u16::from_le_bytes([raw[0], raw[1]])))
+                .collect(),
+        };
+
+        Ok(Self {
+            width: header.width.get() as usize / 8,
+            height: header.height.get() as usize / 8,
+            background_type,
+            entries,
+        })
+    }
+
+    /// Composes the background from the tiles of `ncgr` and the colors of
+    /// `nclr`. Color 0 is transparent, missing tiles stay empty.
+    pub fn render(&self, ncgr: &Ncgr, nclr: &Nclr) -> Image {
+        let mut image = Image::new(self.width * 8, self.height * 8);
+
+        for (index, entry) in self
+            .entries
+            .iter()
+            .take(self.width * self.height)
+            .enumerate()
+        {
+            let mut tile = match ncgr.tile(entry.tile as usize) {
+                Some(tile) => tile,
+                None => continue,
+            };
+            flip_tile(&mut tile, entry.h_flip, entry.v_flip);
+
+            let colors = nclr.colors_for(ncgr.depth, entry.palette as u16);
+
+            let (x, y) = ((index % self.width) * 8, (index / self.width) * 8);
+            for (pixel, &color) in tile.iter().enumerate() {
+                if color == 0 {
+                    continue;
+                }
+                if let Some(color) = colors.get(color as usize) {
+                    let (px, py) = (x + pixel % 8, y + pixel / 8);
+                    image.pixels[py * image.width + px] = color.to_rgba(0xFF);
+                }
+            }
+        }
+
+        image
+    }
+}
@@ -0,0 +1,168 @@
+//! Typed views of the byte-sized [CartridgeHeader] fields.
+//!
+//! The header keeps the raw bytes, the enums convert from and to them without
+//! losing unknown values. The `serde` modules let the header serialize the
+//! fields as their enums, e.g. `#[serde(with = "unit_code")]`.
+
+use crate::cartridge_header::CartridgeHeader;
+use byte_unit::{Byte, KIBIBYTE};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+macro_rules! define_byte_enum {
+    (
+        $(#[$meta:meta])*
+        $name:ident, $module:ident {
+            $($(#[$variant_meta:meta])* $variant:ident = $value:expr),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// A value without a known meaning, kept as is
+            Unknown(u8),
+        }
+
+        impl From<u8> for $name {
+            fn from(raw: u8) -> Self {
+                match raw {
+                    $($value => $name::$variant,)*
+                    raw => $name::Unknown(raw),
+                }
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(value: $name) -> Self {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::Unknown(raw) => raw,
+                }
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    $name::Unknown(raw) => write!(f, "unknown ({:#04x})", raw),
+                    value => write!(f, "{:?}", value),
+                }
+            }
+        }
+
+        /// Serializes the raw byte as
+        #[doc = concat!("[", stringify!($name), "]")]
+        pub mod $module {
+            use super::$name;
+            use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+            pub fn serialize<S>(raw: &u8, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                $name::from(*raw).serialize(serializer)
+            }
+
+            pub fn deserialize<'de, D>(deserializer: D) -> Result<u8, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                $name::deserialize(deserializer).map(u8::from)
+            }
+        }
+    };
+}
+
+define_byte_enum! {
+    /// Which consoles the ROM runs on
+    UnitCode, unit_code {
+        Nds = 0x00,
+        /// DSi-enhanced, with a [TwlHeader](crate::twl_header::TwlHeader)
+        NdsAndDsi = 0x02,
+        /// DSi-exclusive, with a [TwlHeader](crate::twl_header::TwlHeader)
+        Dsi = 0x03,
+    }
+}
+
+define_byte_enum! {
+    /// Region lock, only enforced on DSi consoles
+    Region, region {
+        Normal = 0x00,
+        Korea = 0x40,
+        China = 0x80,
+    }
+}
+
+define_byte_enum! {
+    Autostart, autostart {
+        Normal = 0x00,
+        /// Skips the "press button" prompt after the health and safety screen
+        SkipPressButton = 0x04,
+    }
+}
+
+define_byte_enum! {
+    /// Selects the seed bytes of the KEY2 encryption
+    EncryptionSeed, encryption_seed {
+        Seed0 = 0x00,
+        Seed1 = 0x01,
+        Seed2 = 0x02,
+        Seed3 = 0x03,
+        Seed4 = 0x04,
+        Seed5 = 0x05,
+        Seed6 = 0x06,
+        Seed7 = 0x07,
+    }
+}
+
+define_byte_enum! {
+    /// Chip size of the cartridge, 128 KiB shifted left by the raw value
+    DeviceCapacity, device_capacity {
+        KiB128 = 0x00,
+        KiB256 = 0x01,
+        KiB512 = 0x02,
+        MiB1 = 0x03,
+        MiB2 = 0x04,
+        MiB4 = 0x05,
+        MiB8 = 0x06,
+        MiB16 = 0x07,
+        MiB32 = 0x08,
+        MiB64 = 0x09,
+        MiB128 = 0x0A,
+        MiB256 = 0x0B,
+        MiB512 = 0x0C,
+    }
+}
+
+impl DeviceCapacity {
+    /// The chip size, or `None` for unknown values
+    pub fn size(self) -> Option<Byte> {
+        match self {
+            DeviceCapacity::Unknown(_) => None,
+            capacity => Some(Byte::from_bytes((128 << u8::from(capacity)) * KIBIBYTE)),
+        }
+    }
+}
+
+impl CartridgeHeader {
+    pub fn unit_code(&self) -> UnitCode {
+        self.unit_code.into()
+    }
+
+    pub fn region(&self) -> Region {
+        self.region.into()
+    }
+
+    pub fn autostart(&self) -> Autostart {
+        self.autostart.into()
+    }
+
+    pub fn encryption_seed(&self) -> EncryptionSeed {
+        self.encryption_seed_select.into()
+    }
+
+    pub fn capacity(&self) -> DeviceCapacity {
+        self.device_capacity_raw.into()
+    }
+}
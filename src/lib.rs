@@ -1,3 +1,19 @@
+pub mod arm9;
+pub mod banner;
+pub mod builder;
 pub mod byte_types;
 pub mod cartridge_header;
+pub mod compression;
+pub mod crc;
+pub mod error;
 pub mod file;
+pub mod graphics;
+pub mod header_fields;
+pub mod nitro_file;
+pub mod overlay;
+pub mod patch;
+pub mod rom_source;
+pub mod secure_area;
+pub mod sound;
+pub mod trim;
+pub mod twl_header;
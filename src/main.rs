@@ -1,15 +1,344 @@
-use itertools::Itertools;
+use byte_unit::Byte;
+use clap::{Parser, Subcommand};
+use eyre::{eyre, WrapErr};
 use pony_reader::{
-    byte_types::embedded_string::EmbeddedStringCommon, cartridge_header::CartridgeHeader,
+    arm9,
+    banner::Banner,
+    builder::{RomBuilder, NITROCODE},
+    byte_types::embedded_string::EmbeddedStringCommon,
+    cartridge_header::CartridgeHeader,
+    compression,
+    error::Error,
+    file::{
+        file_name_table::FileNameTable,
+        narc::Narc,
+        traversal::{Entry, Order},
+        tree::{make_name, Directory, Node},
+        FileId, Files,
+    },
+    graphics::{
+        image::Image,
+        nanr::{Frame, Nanr},
+        ncer::{Ncer, Rect},
+        ncgr::Ncgr,
+        nclr::Nclr,
+        nmcr::Nmcr,
+        nscr::Nscr,
+    },
+    overlay::{Overlay, OverlayTableEntry},
+    patch::{self, Placement},
+    rom_source::RomSource,
+    secure_area::{self, KeyTable},
+    sound::{
+        sdat::Sdat,
+        sseq::Sseq,
+        strm::Strm,
+        swav::{Swar, Swav},
+        wave::Wave,
+    },
+    trim,
 };
 use ron::ser::{PrettyConfig, PrettyNumberFormat};
+use serde::Serialize;
 use std::{
     fs::File,
-    io::{BufWriter, Write},
-    ops::Deref,
-    path::PathBuf,
+    io::{BufReader, BufWriter, ErrorKind, Write},
+    path::{Component, Path, PathBuf},
 };
-use zerocopy::LayoutVerified;
+use zerocopy::{AsBytes, LayoutVerified};
+
+/// Inspect and extract Nintendo DS ROMs
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a short summary of the ROM
+    Info {
+        /// Path to the .nds file
+        rom: PathBuf,
+    },
+    /// List all files and directories in the ROM filesystem
+    Ls {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// List all entries of a directory before those of its subdirectories
+        #[arg(short, long)]
+        breadth_first: bool,
+    },
+    /// Dump the header, binaries, tables and filesystem into a directory
+    Extract {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// Output directory
+        #[arg(short, long, default_value = "out")]
+        output: PathBuf,
+        /// Decompress the ARM9 binary and overlays, and write decompressed
+        /// copies of compressed files to decompressed/
+        #[arg(short, long)]
+        decompress: bool,
+    },
+    /// List the ARM9 and ARM7 overlays
+    Overlays {
+        /// Path to the .nds file
+        rom: PathBuf,
+    },
+    /// Build a ROM from a directory created by `extract`
+    Pack {
+        /// Directory containing header.ron, arm9.bin, arm7.bin and files/
+        input: PathBuf,
+        /// Path of the .nds file to create
+        output: PathBuf,
+        /// Compress the ARM9 binary and all overlays
+        #[arg(short, long)]
+        compress: bool,
+    },
+    /// Write a single file from the ROM filesystem to stdout
+    Cat {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// Path of the file inside the ROM, e.g. /data/sound/sound_data.sdat
+        path: String,
+    },
+    /// Print the cartridge header as RON
+    Header {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the banner titles and export the icon and titles
+    Banner {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// Directory to write icon.png and titles.ron to
+        #[arg(short, long, default_value = "out")]
+        output: PathBuf,
+    },
+    /// List or extract the contents of a NARC archive
+    Narc {
+        #[command(subcommand)]
+        command: NarcCommand,
+    },
+    /// List and extract SDAT sound archives and decode their audio
+    Sound {
+        #[command(subcommand)]
+        command: SoundCommand,
+    },
+    /// Convert graphics files extracted from the ROM
+    Gfx {
+        #[command(subcommand)]
+        command: GfxCommand,
+    },
+    /// Replace a single file without rebuilding the ROM
+    Replace {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// Path of the file inside the ROM, or its file ID
+        file: String,
+        /// File with the new contents
+        replacement: PathBuf,
+        /// Write the patched ROM here instead of modifying it in place
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Remove the padding after the used part of the ROM
+    Trim {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// Write the trimmed ROM here instead of modifying it in place
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Pad the ROM with 0xFF up to the capacity of its cartridge
+    Pad {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// Write the padded ROM here instead of modifying it in place
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check, decrypt or encrypt the KEY1-encrypted secure area
+    SecureArea {
+        #[command(subcommand)]
+        command: SecureAreaCommand,
+    },
+    /// Recompute the header, logo and secure area checksums.
+    /// The secure area checksum is only recomputed if the secure area is
+    /// encrypted, which can only be checked with the key table.
+    FixChecksums {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// The KEY1 key table, or a dump of the ARM7 BIOS containing it
+        #[arg(short, long)]
+        key: Option<PathBuf>,
+        /// Write the fixed ROM here instead of modifying it in place
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum NarcCommand {
+    /// List all files in the archive
+    Ls {
+        /// Path to the .narc file
+        narc: PathBuf,
+    },
+    /// Write all files in the archive to a directory
+    Extract {
+        /// Path to the .narc file
+        narc: PathBuf,
+        /// Output directory
+        #[arg(short, long, default_value = "out")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum SoundCommand {
+    /// List the sequences, banks, wave archives and streams with their names
+    Ls {
+        /// Path to the .sdat file
+        sdat: PathBuf,
+    },
+    /// Write all files to directories by kind, named after their symbols,
+    /// with their info in info.ron
+    Extract {
+        /// Path to the .sdat file
+        sdat: PathBuf,
+        /// Output directory
+        #[arg(short, long, default_value = "out")]
+        output: PathBuf,
+    },
+    /// Decode a SWAV, SWAR or STRM file to WAV, one file per sample of a SWAR
+    Wav {
+        /// Path to the .swav, .swar or .strm file
+        file: PathBuf,
+        /// Output directory
+        #[arg(short, long, default_value = "out")]
+        output: PathBuf,
+    },
+    /// Convert an SSEQ sequence to a type 1 MIDI file with one track per
+    /// sequence track
+    Midi {
+        /// Path to the .sseq file
+        sseq: PathBuf,
+        /// How many times to play loops that never end
+        #[arg(short, long, default_value = "2")]
+        loops: u32,
+        /// Output directory
+        #[arg(short, long, default_value = "out")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum GfxCommand {
+    /// Export an NCLR palette as PNG swatches and RON
+    Palette {
+        /// Path to the .nclr file, which may be compressed
+        nclr: PathBuf,
+        /// Output directory
+        #[arg(short, long, default_value = "out")]
+        output: PathBuf,
+    },
+    /// Render NCGR tile graphics to PNG
+    Tiles {
+        /// Path to the .ncgr file, which may be compressed
+        ncgr: PathBuf,
+        /// Path to the .nclr file with the colors
+        #[arg(short, long)]
+        palette: PathBuf,
+        /// Palette number for 4 bit graphics, defaults to the first palette
+        #[arg(long)]
+        palette_id: Option<u16>,
+        /// Width in tiles, overriding the size stored in the file
+        #[arg(short, long)]
+        width: Option<usize>,
+        /// Output directory
+        #[arg(short, long, default_value = "out")]
+        output: PathBuf,
+    },
+    /// Compose an NSCR background from its tiles and palette
+    Screen {
+        /// Path to the .nscr file, which may be compressed
+        nscr: PathBuf,
+        /// Path to the .ncgr file with the tiles
+        #[arg(short, long)]
+        tiles: PathBuf,
+        /// Path to the .nclr file with the colors
+        #[arg(short, long)]
+        palette: PathBuf,
+        /// Output directory
+        #[arg(short, long, default_value = "out")]
+        output: PathBuf,
+    },
+    /// Assemble NCER sprite cells and render their animations frame by frame
+    Cells {
+        /// Path to the .ncer file, which may be compressed
+        ncer: PathBuf,
+        /// Path to the .ncgr file with the tiles
+        #[arg(short, long)]
+        tiles: PathBuf,
+        /// Path to the .nclr file with the colors
+        #[arg(short, long)]
+        palette: PathBuf,
+        /// Path to the .nanr file animating the cells
+        #[arg(short, long)]
+        animations: Option<PathBuf>,
+        /// Path to the .nmcr file combining the cell animations, needs
+        /// --animations and --multi-cell-animations
+        #[arg(long, requires_all = ["animations", "multi_cell_animations"])]
+        multi_cells: Option<PathBuf>,
+        /// Path to the .nmar file animating the multi-cells, needs
+        /// --animations and --multi-cells
+        #[arg(long, requires_all = ["animations", "multi_cells"])]
+        multi_cell_animations: Option<PathBuf>,
+        /// Output directory
+        #[arg(short, long, default_value = "out")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecureAreaCommand {
+    /// Print whether the secure area is encrypted
+    Status {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// The KEY1 key table, or a dump of the ARM7 BIOS containing it
+        #[arg(short, long)]
+        key: Option<PathBuf>,
+    },
+    /// Decrypt the secure area
+    Decrypt {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// The KEY1 key table, or a dump of the ARM7 BIOS containing it
+        #[arg(short, long)]
+        key: PathBuf,
+        /// Write the decrypted ROM here instead of modifying it in place
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Encrypt a decrypted secure area again
+    Encrypt {
+        /// Path to the .nds file
+        rom: PathBuf,
+        /// The KEY1 key table, or a dump of the ARM7 BIOS containing it
+        #[arg(short, long)]
+        key: PathBuf,
+        /// Write the encrypted ROM here instead of modifying it in place
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
 
 fn pretty() -> PrettyConfig {
     let mut pretty = PrettyConfig::default();
@@ -17,69 +346,1038 @@ fn pretty() -> PrettyConfig {
     pretty
 }
 
-fn read_rom() -> Vec<u8> {
-    // let mut rom = std::fs::read("pony/Pony Friends GER.nds").unwrap();
-    let mut rom = std::fs::read("pony/TinyFB.nds").unwrap();
+fn to_ron<T>(value: &T) -> eyre::Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(ron::ser::to_string_pretty(value, pretty())?)
+}
+
+fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> eyre::Result<()> {
+    std::fs::write(path, contents).wrap_err_with(|| format!("failed to write {}", path.display()))
+}
+
+fn read_rom(path: &Path) -> eyre::Result<Vec<u8>> {
+    let mut rom =
+        std::fs::read(path).wrap_err_with(|| format!("failed to read {}", path.display()))?;
 
     let min_len = std::mem::size_of::<CartridgeHeader>();
     if rom.len() < min_len {
         rom.resize(min_len, 0);
     }
 
-    rom
+    Ok(rom)
 }
 
-fn main() {
-    let _ = std::fs::create_dir_all("out");
-    let rom = read_rom();
+fn header(rom: &[u8]) -> &CartridgeHeader {
+    let (header, _) = LayoutVerified::<_, CartridgeHeader>::new_from_prefix(rom)
+        .expect("read_rom pads the ROM to the header size");
+    header.into_ref()
+}
+
+fn files<'lt>(header: &CartridgeHeader, rom: &'lt [u8]) -> eyre::Result<Files<'lt>> {
+    header
+        .read_files(rom)
+        .wrap_err("failed to read the ROM filesystem")
+}
 
-    let (header, _) =
-        LayoutVerified::<_, CartridgeHeader>::new_from_prefix(rom.as_slice()).unwrap();
-    std::fs::write(
-        "out/header.ron",
-        ron::ser::to_string_pretty(&*header, pretty()).unwrap(),
-    )
-    .unwrap();
+fn info(rom_path: &Path) -> eyre::Result<()> {
+    let rom = read_rom(rom_path)?;
+    let header = header(&rom);
+    let files = files(header, &rom)?;
 
-    let arm9_base = header.arm9.rom_offset.get() as usize;
-    let arm9_length = header.arm9.size.get() as usize;
-    let arm9 = rom.get(arm9_base..(arm9_base + arm9_length)).unwrap();
-    std::fs::write("out/arm9.bin", arm9).unwrap();
+    println!("Title:           {}", header.title);
+    println!("Game code:       {}", header.game_code);
+    println!("Maker code:      {}", header.maker_code);
+    println!("ROM version:     {}", header.rom_version);
+    println!("Unit code:       {}", header.unit_code());
+    println!("Region:          {}", header.region());
+    match header.device_capacity() {
+        Some(capacity) => println!("Capacity:        {}", capacity.get_appropriate_unit(true)),
+        None => println!("Capacity:        {}", header.capacity()),
+    }
+    println!("Used ROM size:   {:#x}", header.total_used_rom_size.get());
+    for (name, code) in [("ARM9", &header.arm9), ("ARM7", &header.arm7)] {
+        println!(
+            "{}:            rom {:#010x}, ram {:#010x}, entry {:#010x}, size {:#x}",
+            name,
+            code.rom_offset.get(),
+            code.ram_address.get(),
+            code.entry_address.get(),
+            code.size.get(),
+        );
+    }
+    let checksums = header.checksums(&rom);
+    for (name, checksum) in [
+        ("Header CRC:     ", Some(checksums.header)),
+        ("Logo CRC:       ", Some(checksums.nintendo_logo)),
+        ("Secure area CRC:", checksums.secure_area),
+    ] {
+        match checksum {
+            Some(c) if c.is_valid() => println!("{} {:#06x} (ok)", name, c.stored),
+            Some(c) => println!(
+                "{} {:#06x} (MISMATCH, computed {:#06x})",
+                name, c.stored, c.computed
+            ),
+            None => println!("{} -", name),
+        }
+    }
+    println!(
+        "Secure area:     {:?}",
+        secure_area::state(header, &rom, None)
+    );
+    println!("Directories:     {}", files.fnt.main_table.len());
+    println!("FAT entries:     {}", files.fat.len());
 
-    let files = header.read_files(&rom).unwrap();
+    if let Some(twl) = header.read_twl_header(&rom)? {
+        println!("DSi title ID:    {:016x}", twl.title_id());
+        println!("DSi used size:   {:#x}", twl.total_used_rom_size.get());
+        for (name, rom_offset, ram_address, size) in [
+            (
+                "ARM9i",
+                twl.arm9i_rom_offset,
+                twl.arm9i_ram_address,
+                twl.arm9i_size,
+            ),
+            (
+                "ARM7i",
+                twl.arm7i_rom_offset,
+                twl.arm7i_ram_address,
+                twl.arm7i_size,
+            ),
+        ] {
+            println!(
+                "{}:           rom {:#010x}, ram {:#010x}, size {:#x}",
+                name,
+                rom_offset.get(),
+                ram_address.get(),
+                size.get(),
+            );
+        }
+        println!(
+            "Modcrypt:        {}",
+            if twl.is_modcrypted() { "yes" } else { "no" }
+        );
+    }
 
-    std::fs::write(
-        "out/fnt.ron",
-        ron::ser::to_string_pretty(&files.fnt, pretty()).unwrap(),
-    )
-    .unwrap();
+    Ok(())
+}
 
-    std::fs::write(
-        "out/fat.ron",
-        ron::ser::to_string_pretty(&*files.fat, pretty()).unwrap(),
-    )
-    .unwrap();
+fn open_rom(path: &Path) -> eyre::Result<RomSource<BufReader<File>>> {
+    let file = File::open(path).wrap_err_with(|| format!("failed to open {}", path.display()))?;
+    RomSource::new(BufReader::new(file))
+        .wrap_err_with(|| format!("failed to read {}", path.display()))
+}
+
+fn ls(rom_path: &Path, breadth_first: bool) -> eyre::Result<()> {
+    let mut rom = open_rom(rom_path)?;
+    let fnt = rom.fnt()?;
+
+    let order = if breadth_first {
+        Order::BreadthFirst
+    } else {
+        Order::DepthFirst
+    };
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for entry in fnt.entries(order) {
+        let suffix = if entry.is_directory() { "/" } else { "" };
+        writeln!(stdout, "{}{}", entry.path(), suffix)?;
+    }
+
+    Ok(())
+}
+
+/// Where the file at `path` inside a ROM or archive is extracted to. Names
+/// come straight from the file, so anything that could lead outside of
+/// `output`, like `..`, is rejected.
+fn archive_path(output: &Path, path: &str) -> eyre::Result<PathBuf> {
+    let mut file_path = output.to_path_buf();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        let mut components = Path::new(name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(component)), None) if component == name => {
+                file_path.push(component)
+            },
+            _ => {
+                return Err(eyre!(
+                    "refusing to extract {:?}, {:?} is not a plain name",
+                    path,
+                    name
+                ))
+            },
+        }
+    }
+    Ok(file_path)
+}
+
+fn extract(rom_path: &Path, output: &Path, decompress: bool) -> eyre::Result<()> {
+    std::fs::create_dir_all(output)
+        .wrap_err_with(|| format!("failed to create {}", output.display()))?;
+    let rom = read_rom(rom_path)?;
+    let header = header(&rom);
+
+    write_file(&output.join("header.ron"), to_ron(header)?)?;
+
+    for (name, code) in [("arm9.bin", &header.arm9), ("arm7.bin", &header.arm7)] {
+        let base = code.rom_offset.get() as usize;
+        let mut length = code.size.get() as usize;
+        // Keep the NitroSDK footer, the builder splits it off again
+        if name == "arm9.bin" {
+            if let Some(footer) = rom.get((base + length)..(base + length + 12)) {
+                if footer[..4] == NITROCODE.to_le_bytes() {
+                    length += 12;
+                }
+            }
+        }
+        let binary = rom
+            .get(base..(base + length))
+            .ok_or_else(|| eyre!("{} lies outside of the ROM", name))?;
+
+        if decompress && name == "arm9.bin" {
+            match arm9::decompress(binary, code.ram_address.get()) {
+                Ok(binary) => write_file(&output.join(name), binary)?,
+                Err(Error::MissingModuleParams) => write_file(&output.join(name), binary)?,
+                Err(e) => return Err(e).wrap_err("failed to decompress the ARM9 binary"),
+            }
+        } else {
+            write_file(&output.join(name), binary)?;
+        }
+    }
+
+    if let Some(twl) = header.read_twl_header(&rom)? {
+        write_file(&output.join("twl_header.ron"), to_ron(&twl)?)?;
+        // Written as stored, modcrypt-encrypted binaries stay encrypted
+        write_file(&output.join("arm9i.bin"), twl.read_arm9i(&rom)?)?;
+        write_file(&output.join("arm7i.bin"), twl.read_arm7i(&rom)?)?;
+    }
+
+    let files = files(header, &rom)?;
+
+    write_file(&output.join("fnt.ron"), to_ron(&files.fnt)?)?;
+    write_file(&output.join("fat.ron"), to_ron(&*files.fat)?)?;
+
+    let overlay_dir = output.join("overlay");
+    for (prefix, table) in [
+        ("overlay9", header.read_arm9_overlays(&rom)?),
+        ("overlay7", header.read_arm7_overlays(&rom)?),
+    ] {
+        if table.is_empty() {
+            continue;
+        }
+        std::fs::create_dir_all(&overlay_dir)?;
+
+        let mut overlays = Overlay::read_all(&table, &files.fat, &rom)?;
+        if decompress {
+            for overlay in &mut overlays {
+                overlay.decompress().wrap_err_with(|| {
+                    format!(
+                        "failed to decompress overlay {}",
+                        overlay.entry.overlay_id.get()
+                    )
+                })?;
+            }
+        }
 
+        let entries = overlays
+            .iter()
+            .map(|overlay| overlay.entry)
+            .collect::<Vec<_>>();
+        write_file(
+            &overlay_dir.join(format!("{}.ron", prefix)),
+            to_ron(&entries)?,
+        )?;
+
+        for (index, overlay) in overlays.iter().enumerate() {
+            write_file(
+                &overlay_dir.join(format!("{}_{:04}.bin", prefix, index)),
+                &overlay.data,
+            )?;
+        }
+    }
+
+    if let Some(banner) = header.read_banner_raw(&rom)? {
+        write_file(&output.join("banner.bin"), banner)?;
+        export_banner(
+            &Banner::read(banner, header.icon_title_offset.get() as usize)?,
+            output,
+        )?;
+    }
+
+    let mut max_id = 0;
+    let mut decompressed_count = 0;
+    let mut tree = BufWriter::new(File::create(output.join("files.txt"))?);
+    for (id, path) in files
+        .entries(Order::DepthFirst)
+        .filter_map(|entry| match entry {
+            Entry::File { id, path } => Some((id, path)),
+            Entry::Directory { .. } => None,
+        })
     {
-        let mut max_id = 0;
+        max_id = max_id.max(id.0);
+        writeln!(tree, "{}", path)?;
+
+        let file = files
+            .file(id)
+            .wrap_err_with(|| format!("failed to read {}", path))?;
+
+        let write_to = |base: &str, contents: &[u8]| {
+            let file_path = archive_path(&output.join(base), &path)?;
+            if let Some(parent) = file_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_file(&file_path, contents)
+        };
+
+        write_to("files", file)?;
+        if decompress {
+            if let Some((_, decompressed)) = compression::detect(file) {
+                decompressed_count += 1;
+                write_to("decompressed", &decompressed)?;
+            }
+        }
+    }
+    tree.flush()?;
+
+    println!("Max file id: {}", max_id);
+    if decompress {
+        println!("Decompressed files: {}", decompressed_count);
+    }
+
+    Ok(())
+}
+
+fn export_banner(banner: &Banner, output: &Path) -> eyre::Result<()> {
+    let icon_path = output.join("icon.png");
+    banner
+        .icon_rgba()
+        .write_png(BufWriter::new(File::create(&icon_path)?))
+        .wrap_err_with(|| format!("failed to write {}", icon_path.display()))?;
+
+    write_file(&output.join("titles.ron"), to_ron(&banner.titles())?)
+}
+
+fn banner(rom_path: &Path, output: &Path) -> eyre::Result<()> {
+    let rom = read_rom(rom_path)?;
+    let header = header(&rom);
+
+    let raw = header
+        .read_banner_raw(&rom)?
+        .ok_or_else(|| eyre!("the ROM has no icon/title banner"))?;
+    let banner = Banner::read(raw, header.icon_title_offset.get() as usize)?;
+
+    println!("Version: {:#x}", banner.version.get());
+    for checksum in banner.checksums(raw) {
+        if !checksum.is_valid() {
+            println!(
+                "Checksum mismatch: stored {:#06x}, computed {:#06x}",
+                checksum.stored, checksum.computed
+            );
+        }
+    }
+    for (language, title) in banner.titles() {
+        println!("{:?}: {}", language, title.replace('\n', " / "));
+    }
+
+    std::fs::create_dir_all(output)
+        .wrap_err_with(|| format!("failed to create {}", output.display()))?;
+    export_banner(&banner, output)
+}
+
+fn load_directory(path: &Path) -> eyre::Result<Directory> {
+    let mut entries = Vec::new();
+
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name
+            .to_str()
+            .ok_or_else(|| eyre!("{:?} is not valid UTF-8", name))?;
+        let name = make_name(name)?;
+
+        let node = if entry.file_type()?.is_dir() {
+            Node::Directory(load_directory(&entry.path())?)
+        } else {
+            Node::File(std::fs::read(entry.path())?)
+        };
+
+        entries.push((name, node));
+    }
+
+    entries.sort_by(|(a, _), (b, _)| a.data().cmp(b.data()));
+
+    Ok(Directory { entries })
+}
+
+fn read_optional(path: &Path) -> eyre::Result<Option<Vec<u8>>> {
+    match std::fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).wrap_err_with(|| format!("failed to read {}", path.display())),
+    }
+}
+
+fn read_overlays(input: &Path, prefix: &str) -> eyre::Result<Vec<Overlay>> {
+    let overlay_dir = input.join("overlay");
+    let table = match read_optional(&overlay_dir.join(format!("{}.ron", prefix)))? {
+        Some(table) => ron::de::from_bytes::<Vec<OverlayTableEntry>>(&table)
+            .wrap_err_with(|| format!("failed to parse {}.ron", prefix))?,
+        None => return Ok(vec![]),
+    };
+
+    table
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let path = overlay_dir.join(format!("{}_{:04}.bin", prefix, index));
+            let data = std::fs::read(&path)
+                .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+            Ok(Overlay { entry, data })
+        })
+        .collect()
+}
+
+fn overlays(rom_path: &Path) -> eyre::Result<()> {
+    let rom = read_rom(rom_path)?;
+    let header = header(&rom);
+
+    for (name, table) in [
+        ("ARM9", header.read_arm9_overlays(&rom)?),
+        ("ARM7", header.read_arm7_overlays(&rom)?),
+    ] {
+        println!("{} overlays: {}", name, table.len());
+        for entry in table.iter() {
+            println!(
+                "  {:>4}: ram {:#010x}, size {:#8x}, bss {:#7x}, file {:>4}{}",
+                entry.overlay_id.get(),
+                entry.ram_address.get(),
+                entry.ram_size.get(),
+                entry.bss_size.get(),
+                entry.file_id.get(),
+                if entry.is_compressed() {
+                    format!(", compressed to {:#x}", entry.compressed_size())
+                } else {
+                    String::new()
+                },
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn pack(input: &Path, output: &Path, compress: bool) -> eyre::Result<()> {
+    let read = |name: &str| {
+        let path = input.join(name);
+        std::fs::read(&path).wrap_err_with(|| format!("failed to read {}", path.display()))
+    };
+
+    let header: CartridgeHeader = ron::de::from_bytes(&read("header.ron")?)
+        .wrap_err_with(|| format!("failed to parse {}", input.join("header.ron").display()))?;
+    if header.is_twl() {
+        eprintln!("DSi data is not rebuilt, the ROM will only work in DS mode");
+    }
+
+    let mut root = match input.join("files") {
+        files if files.is_dir() => load_directory(&files)?,
+        _ => Directory::default(),
+    };
+    if let Some(fnt) = read_optional(&input.join("fnt.ron"))? {
+        let fnt: FileNameTable = ron::de::from_bytes(&fnt)
+            .wrap_err_with(|| format!("failed to parse {}", input.join("fnt.ron").display()))?;
+        root.order_like(&fnt);
+    }
+
+    let mut builder = RomBuilder {
+        header,
+        arm9: read("arm9.bin")?,
+        arm7: read("arm7.bin")?,
+        arm9_overlays: read_overlays(input, "overlay9")?,
+        arm7_overlays: read_overlays(input, "overlay7")?,
+        banner: read_optional(&input.join("banner.bin"))?,
+        root,
+    };
+
+    if compress {
+        builder.arm9 = match arm9::compress(&builder.arm9, header.arm9.ram_address.get()) {
+            Ok(arm9) => arm9,
+            Err(Error::MissingModuleParams) => {
+                eprintln!("Not compressing arm9.bin, it has no NitroSDK module parameters");
+                builder.arm9
+            },
+            Err(e) => return Err(e).wrap_err("failed to compress the ARM9 binary"),
+        };
+        for overlay in builder
+            .arm9_overlays
+            .iter_mut()
+            .chain(builder.arm7_overlays.iter_mut())
+        {
+            overlay.compress();
+        }
+    }
+
+    let rom = builder.build()?;
+    write_file(output, &rom)?;
+    println!(
+        "Wrote {} ({})",
+        output.display(),
+        Byte::from_bytes(rom.len() as u128).get_appropriate_unit(true)
+    );
+
+    Ok(())
+}
 
-        let mut tree = BufWriter::new(File::create("out/files.txt").unwrap());
-        files.fnt.walk(|path, id| {
-            if id < 0xf000 {
-                max_id = max_id.max(id);
-                writeln!(tree, "/{}", path.iter().map(|s| s.as_str_lossy()).join("/")).unwrap();
+fn cat(rom_path: &Path, path: &str) -> eyre::Result<()> {
+    let mut rom = open_rom(rom_path)?;
+    let mut file = rom.open(path)?;
 
-                let file: &[u8] = files.fat.get(id as usize).unwrap().get_file(&rom).unwrap();
-                let mut file_path = PathBuf::from("out/files");
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    std::io::copy(&mut file, &mut stdout)?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+fn print_header(rom_path: &Path, output: Option<&Path>) -> eyre::Result<()> {
+    let rom = read_rom(rom_path)?;
+    let ron = to_ron(header(&rom))?;
+
+    match output {
+        Some(output) => write_file(output, ron),
+        None => {
+            println!("{}", ron);
+            Ok(())
+        },
+    }
+}
+
+/// Reads a NitroSDK file, decompressing it if it doesn't start with `magic`
+fn read_nitro_file(path: &Path, magic: [u8; 4]) -> eyre::Result<Vec<u8>> {
+    let data =
+        std::fs::read(path).wrap_err_with(|| format!("failed to read {}", path.display()))?;
+    if !data.starts_with(&magic) {
+        if let Some((_, decompressed)) = compression::detect(&data) {
+            if decompressed.starts_with(&magic) {
+                return Ok(decompressed);
+            }
+        }
+    }
+    Ok(data)
+}
+
+fn write_png(image: &Image, path: &Path) -> eyre::Result<()> {
+    let file =
+        File::create(path).wrap_err_with(|| format!("failed to create {}", path.display()))?;
+    image
+        .write_png(BufWriter::new(file))
+        .wrap_err_with(|| format!("failed to write {}", path.display()))
+}
+
+/// `output/name.extension`, where name is the whole file name of `input`, so
+/// that e.g. `a.nclr` and `a.ncgr` don't overwrite each other's `a.nclr.png`
+/// and `a.ncgr.png`
+fn output_path(output: &Path, input: &Path, extension: &str) -> PathBuf {
+    let mut name = input.file_name().unwrap_or(input.as_os_str()).to_owned();
+    name.push(".");
+    name.push(extension);
+    output.join(name)
+}
+
+fn read_nclr(path: &Path) -> eyre::Result<Nclr> {
+    Nclr::read(&read_nitro_file(path, Nclr::MAGIC)?)
+        .wrap_err_with(|| format!("failed to parse {}", path.display()))
+}
+
+fn read_ncgr(path: &Path) -> eyre::Result<Ncgr> {
+    Ncgr::read(&read_nitro_file(path, Ncgr::MAGIC)?)
+        .wrap_err_with(|| format!("failed to parse {}", path.display()))
+}
 
-                for e in path {
-                    file_path.push(e.as_str_lossy().deref());
+/// Writes `images` as `name_000.png` and so on into `directory`
+fn write_numbered_pngs(
+    directory: &Path,
+    name: &str,
+    images: impl Iterator<Item = Image>,
+) -> eyre::Result<()> {
+    std::fs::create_dir_all(directory)
+        .wrap_err_with(|| format!("failed to create {}", directory.display()))?;
+    for (index, image) in images.enumerate() {
+        write_png(&image, &directory.join(format!("{name}_{index:03}.png")))?;
+    }
+    Ok(())
+}
+
+/// `bounds`, or a single pixel at the origin if there is nothing to draw,
+/// because PNGs can't be empty
+fn canvas_for(bounds: Option<Rect>) -> Rect {
+    bounds
+        .filter(|bounds| bounds.width() > 0 && bounds.height() > 0)
+        .unwrap_or(Rect {
+            min_x: 0,
+            min_y: 0,
+            max_x: 1,
+            max_y: 1,
+        })
+}
+
+fn gfx(command: GfxCommand) -> eyre::Result<()> {
+    match &command {
+        GfxCommand::Palette { output, .. }
+        | GfxCommand::Tiles { output, .. }
+        | GfxCommand::Screen { output, .. }
+        | GfxCommand::Cells { output, .. } => std::fs::create_dir_all(output)
+            .wrap_err_with(|| format!("failed to create {}", output.display()))?,
+    }
+
+    match command {
+        GfxCommand::Palette { nclr, output } => {
+            let palette = read_nclr(&nclr)?;
+
+            write_png(&palette.swatches(), &output_path(&output, &nclr, "png"))?;
+            write_file(&output_path(&output, &nclr, "ron"), to_ron(&palette)?)?;
+            println!(
+                "{} palettes of {} colors",
+                palette.palettes.len(),
+                palette.depth.colors()
+            );
+        },
+        GfxCommand::Tiles {
+            ncgr,
+            palette,
+            palette_id,
+            width,
+            output,
+        } => {
+            let graphics = read_ncgr(&ncgr)?;
+            let palette = read_nclr(&palette)?;
+
+            let palette_id = palette_id
+                .or_else(|| palette.palettes.first().map(|palette| palette.id))
+                .unwrap_or_default();
+            let image = graphics.render(&palette, palette_id, width);
+            write_png(&image, &output_path(&output, &ncgr, "png"))?;
+            println!(
+                "{} tiles, {}x{} pixels",
+                graphics.tile_count(),
+                image.width,
+                image.height
+            );
+        },
+        GfxCommand::Screen {
+            nscr,
+            tiles,
+            palette,
+            output,
+        } => {
+            let screen = Nscr::read(&read_nitro_file(&nscr, Nscr::MAGIC)?)
+                .wrap_err_with(|| format!("failed to parse {}", nscr.display()))?;
+            let image = screen.render(&read_ncgr(&tiles)?, &read_nclr(&palette)?);
+            write_png(&image, &output_path(&output, &nscr, "png"))?;
+            println!("{}x{} pixels", image.width, image.height);
+        },
+        GfxCommand::Cells {
+            ncer,
+            tiles,
+            palette,
+            animations,
+            multi_cells,
+            multi_cell_animations,
+            output,
+        } => {
+            let cells = Ncer::read(&read_nitro_file(&ncer, Ncer::MAGIC)?)
+                .wrap_err_with(|| format!("failed to parse {}", ncer.display()))?;
+            let graphics = read_ncgr(&tiles)?;
+            let palette = read_nclr(&palette)?;
+            let directory = output_path(&output, &ncer, "cells");
+
+            // All cells share one canvas so that they line up
+            let canvas = canvas_for(cells.bounds());
+            write_numbered_pngs(
+                &directory,
+                "cell",
+                cells
+                    .cells
+                    .iter()
+                    .map(|cell| cells.render_cell(cell, &graphics, &palette, canvas)),
+            )?;
+            println!(
+                "{} cells, {}x{} pixels",
+                cells.cells.len(),
+                canvas.width(),
+                canvas.height()
+            );
+
+            if let Some(animations) = animations {
+                let nanr = Nanr::read(&read_nitro_file(&animations, Nanr::MAGIC)?)
+                    .wrap_err_with(|| format!("failed to parse {}", animations.display()))?;
+                for (index, sequence) in nanr.sequences.iter().enumerate() {
+                    let canvas = canvas_for(cells.sequence_bounds(sequence));
+                    write_numbered_pngs(
+                        &directory.join(format!("animation_{index:03}")),
+                        "frame",
+                        sequence
+                            .frames
+                            .iter()
+                            .map(|frame| cells.render_frame(frame, &graphics, &palette, canvas)),
+                    )?;
+                }
+                write_file(&directory.join("animations.ron"), to_ron(&nanr)?)?;
+                println!("{} animations", nanr.sequences.len());
+
+                if let (Some(multi_cells), Some(multi_cell_animations)) =
+                    (multi_cells, multi_cell_animations)
+                {
+                    let nmcr = Nmcr::read(&read_nitro_file(&multi_cells, Nmcr::MAGIC)?)
+                        .wrap_err_with(|| format!("failed to parse {}", multi_cells.display()))?;
+                    let nmar = Nanr::read_multi_cell(&read_nitro_file(
+                        &multi_cell_animations,
+                        Nanr::MULTI_CELL_MAGIC,
+                    )?)
+                    .wrap_err_with(|| {
+                        format!("failed to parse {}", multi_cell_animations.display())
+                    })?;
+                    for (index, sequence) in nmar.sequences.iter().enumerate() {
+                        let multi_cell = |frame: &Frame| {
+                            let (x, y) = frame.translation.unwrap_or_default();
+                            nmcr.multi_cells
+                                .get(frame.index as usize)
+                                .map(|multi_cell| (multi_cell, x as i32, y as i32))
+                        };
+                        let canvas = canvas_for(Rect::bounding(sequence.frames.iter().filter_map(
+                            |frame| {
+                                let (multi_cell, x, y) = multi_cell(frame)?;
+                                Some(multi_cell.bounds(&nanr, &cells)?.translate(x, y))
+                            },
+                        )));
+
+                        // The cell animations keep running while the multi-cells change
+                        let mut time = 0;
+                        let frames = sequence.frames.iter().map(|frame| {
+                            let image = match multi_cell(frame) {
+                                Some((multi_cell, x, y)) => multi_cell.render(
+                                    &nanr,
+                                    &cells,
+                                    &graphics,
+                                    &palette,
+                                    time,
+                                    canvas.translate(-x, -y),
+                                ),
+                                None => Image::new(canvas.width(), canvas.height()),
+                            };
+                            time += frame.duration as u32;
+                            image
+                        });
+                        write_numbered_pngs(
+                            &directory.join(format!("multi_cell_animation_{index:03}")),
+                            "frame",
+                            frames,
+                        )?;
+                    }
+                    write_file(&directory.join("multi_cell_animations.ron"), to_ron(&nmar)?)?;
+                    println!("{} multi-cell animations", nmar.sequences.len());
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
+fn narc(command: NarcCommand) -> eyre::Result<()> {
+    let narc_path = match &command {
+        NarcCommand::Ls { narc } | NarcCommand::Extract { narc, .. } => narc,
+    };
+    let data = std::fs::read(narc_path)
+        .wrap_err_with(|| format!("failed to read {}", narc_path.display()))?;
+    let narc =
+        Narc::read(&data).wrap_err_with(|| format!("failed to parse {}", narc_path.display()))?;
+
+    match command {
+        NarcCommand::Ls { .. } => {
+            for (path, id) in narc.paths() {
+                println!("{:>5} {}", id, path);
+            }
+        },
+        NarcCommand::Extract { output, .. } => {
+            for (path, id) in narc.paths() {
+                let file = narc
+                    .file(id)
+                    .wrap_err_with(|| format!("failed to read {}", path))?;
+
+                let file_path = archive_path(&output, &path)?;
+                if let Some(parent) = file_path.parent() {
+                    std::fs::create_dir_all(parent)?;
                 }
-                let _ = std::fs::create_dir_all(file_path.parent().unwrap());
-                std::fs::write(file_path, file).unwrap();
+                write_file(&file_path, file)?;
             }
-        });
+            println!("Extracted {} files", narc.len());
+        },
+    }
+
+    Ok(())
+}
+
+fn write_wav(wave: &Wave, path: &Path) -> eyre::Result<()> {
+    let file =
+        File::create(path).wrap_err_with(|| format!("failed to create {}", path.display()))?;
+    wave.write_wav(BufWriter::new(file))
+        .wrap_err_with(|| format!("failed to write {}", path.display()))
+}
+
+/// Decodes a SWAV, SWAR or STRM file, depending on its magic
+fn wav(path: &Path, output: &Path) -> eyre::Result<()> {
+    std::fs::create_dir_all(output)
+        .wrap_err_with(|| format!("failed to create {}", output.display()))?;
+    let data =
+        std::fs::read(path).wrap_err_with(|| format!("failed to read {}", path.display()))?;
+    let parse_error = || format!("failed to parse {}", path.display());
+
+    let waves = match data.get(..4) {
+        Some(magic) if magic == Swav::MAGIC => {
+            let swav = Swav::read(&data).wrap_err_with(parse_error)?;
+            write_wav(&swav.wave, &output_path(output, path, "wav"))?;
+            vec![swav.wave]
+        },
+        Some(magic) if magic == Swar::MAGIC => {
+            let swar = Swar::read(&data).wrap_err_with(parse_error)?;
+            let directory = output_path(output, path, "waves");
+            std::fs::create_dir_all(&directory)
+                .wrap_err_with(|| format!("failed to create {}", directory.display()))?;
+            for (index, swav) in swar.waves.iter().enumerate() {
+                write_wav(&swav.wave, &directory.join(format!("{index:03}.wav")))?;
+            }
+            swar.waves.into_iter().map(|swav| swav.wave).collect()
+        },
+        Some(magic) if magic == Strm::MAGIC => {
+            let strm = Strm::read(&data).wrap_err_with(parse_error)?;
+            write_wav(&strm.wave, &output_path(output, path, "wav"))?;
+            vec![strm.wave]
+        },
+        _ => return Err(eyre!("{} is not a SWAV, SWAR or STRM file", path.display())),
+    };
+
+    for wave in waves {
+        println!(
+            "{} channels, {} samples at {} Hz{}",
+            wave.channels.len(),
+            wave.len(),
+            wave.sample_rate,
+            match wave.loop_start {
+                Some(start) => format!(", looping from {}", start),
+                None => String::new(),
+            }
+        );
+    }
+    Ok(())
+}
+
+fn sound(command: SoundCommand) -> eyre::Result<()> {
+    match command {
+        SoundCommand::Ls { sdat } => {
+            let data = std::fs::read(&sdat)
+                .wrap_err_with(|| format!("failed to read {}", sdat.display()))?;
+            let sdat = Sdat::read(&data)
+                .wrap_err_with(|| format!("failed to parse {}", sdat.display()))?;
+            for (entry, path) in sdat.entries.iter().zip(sdat.paths()) {
+                println!("{:>5} {}", entry.info.file_id(), path);
+                for (index, name) in entry.sequence_names.iter().enumerate() {
+                    if let Some(name) = name {
+                        println!("{:>5}   {:>4} {}", "", index, name);
+                    }
+                }
+            }
+        },
+        SoundCommand::Extract { sdat, output } => {
+            let data = std::fs::read(&sdat)
+                .wrap_err_with(|| format!("failed to read {}", sdat.display()))?;
+            let sdat = Sdat::read(&data)
+                .wrap_err_with(|| format!("failed to parse {}", sdat.display()))?;
+            std::fs::create_dir_all(&output)
+                .wrap_err_with(|| format!("failed to create {}", output.display()))?;
+            for (entry, path) in sdat.entries.iter().zip(sdat.paths()) {
+                let file = sdat
+                    .file(entry.info.file_id())
+                    .wrap_err_with(|| format!("failed to read {}", path))?;
+
+                let file_path = archive_path(&output, &path)?;
+                if let Some(parent) = file_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                write_file(&file_path, file)?;
+            }
+            write_file(&output.join("info.ron"), to_ron(&sdat.entries)?)?;
+            println!("Extracted {} files", sdat.entries.len());
+        },
+        SoundCommand::Wav { file, output } => wav(&file, &output)?,
+        SoundCommand::Midi {
+            sseq,
+            loops,
+            output,
+        } => {
+            let data = std::fs::read(&sseq)
+                .wrap_err_with(|| format!("failed to read {}", sseq.display()))?;
+            let smf = Sseq::read(&data)
+                .and_then(|sequence| sequence.to_smf(loops))
+                .wrap_err_with(|| format!("failed to parse {}", sseq.display()))?;
+            std::fs::create_dir_all(&output)
+                .wrap_err_with(|| format!("failed to create {}", output.display()))?;
+            let path = output_path(&output, &sseq, "mid");
+            let file = File::create(&path)
+                .wrap_err_with(|| format!("failed to create {}", path.display()))?;
+            smf.write_smf(BufWriter::new(file))
+                .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+            println!("{} tracks", smf.tracks.len() - 1);
+        },
+    }
+
+    Ok(())
+}
+
+fn replace(
+    rom_path: &Path,
+    file: &str,
+    replacement: &Path,
+    output: Option<&Path>,
+) -> eyre::Result<()> {
+    let mut rom = read_rom(rom_path)?;
+    let data = std::fs::read(replacement)
+        .wrap_err_with(|| format!("failed to read {}", replacement.display()))?;
+
+    let file_id = match file.parse() {
+        Ok(id) => FileId(id),
+        Err(_) => files(header(&rom), &rom)?
+            .lookup(file)
+            .ok_or_else(|| Error::FileNotFound {
+                path: file.to_string(),
+            })?,
+    };
+
+    match patch::replace_file(&mut rom, file_id, &data)
+        .wrap_err_with(|| format!("failed to replace file {}", file_id))?
+    {
+        Placement::InPlace { offset } => println!("Replaced in place at {:#x}", offset),
+        Placement::Gap { from, to } | Placement::End { from, to } => {
+            println!("Moved from {:#x} to {:#x}", from, to)
+        },
+    }
+    write_file(output.unwrap_or(rom_path), &rom)
+}
+
+fn trim(rom_path: &Path, output: Option<&Path>) -> eyre::Result<()> {
+    let mut rom = read_rom(rom_path)?;
+    let header = *header(&rom);
+
+    let removed = trim::trim(&header, &mut rom)
+        .wrap_err_with(|| format!("failed to trim {}", rom_path.display()))?;
+    write_file(output.unwrap_or(rom_path), &rom)?;
+    println!(
+        "Removed {} of padding",
+        Byte::from_bytes(removed as u128).get_appropriate_unit(true)
+    );
+
+    Ok(())
+}
+
+fn pad(rom_path: &Path, output: Option<&Path>) -> eyre::Result<()> {
+    let mut rom = read_rom(rom_path)?;
+    let header = *header(&rom);
+
+    let added = trim::pad(&header, &mut rom)
+        .wrap_err_with(|| format!("failed to pad {}", rom_path.display()))?;
+    write_file(output.unwrap_or(rom_path), &rom)?;
+    println!(
+        "Added {} of padding",
+        Byte::from_bytes(added as u128).get_appropriate_unit(true)
+    );
+
+    Ok(())
+}
+
+fn read_key_table(path: &Path) -> eyre::Result<KeyTable> {
+    let data =
+        std::fs::read(path).wrap_err_with(|| format!("failed to read {}", path.display()))?;
+    KeyTable::read(&data).wrap_err_with(|| format!("failed to parse {}", path.display()))
+}
+
+fn secure_area(command: SecureAreaCommand) -> eyre::Result<()> {
+    let decrypt = matches!(command, SecureAreaCommand::Decrypt { .. });
+    match command {
+        SecureAreaCommand::Status { rom, key } => {
+            let key = key.as_deref().map(read_key_table).transpose()?;
+            let rom = read_rom(&rom)?;
+            println!("{:?}", secure_area::state(header(&rom), &rom, key.as_ref()));
+        },
+        SecureAreaCommand::Decrypt { rom, key, output }
+        | SecureAreaCommand::Encrypt { rom, key, output } => {
+            let key = read_key_table(&key)?;
+            let mut data = read_rom(&rom)?;
+            let header = *header(&data);
+
+            if decrypt {
+                secure_area::decrypt(&header, &mut data, &key)
+            } else {
+                secure_area::encrypt(&header, &mut data, &key)
+            }
+            .wrap_err_with(|| format!("failed to process {}", rom.display()))?;
+
+            write_file(output.as_deref().unwrap_or(&rom), &data)?;
+        },
+    }
+
+    Ok(())
+}
+
+fn fix_checksums(rom_path: &Path, key: Option<&Path>, output: Option<&Path>) -> eyre::Result<()> {
+    let key = key.map(read_key_table).transpose()?;
+    let mut rom = read_rom(rom_path)?;
+
+    let mut header = *header(&rom);
+    let state = header.update_all_checksums(&rom, key.as_ref());
+    if state != secure_area::State::Encrypted {
+        eprintln!(
+            "Keeping the secure area checksum, the secure area is not known to be encrypted ({:?})",
+            state
+        );
+    }
+    rom[..std::mem::size_of::<CartridgeHeader>()].copy_from_slice(header.as_bytes());
+
+    write_file(output.unwrap_or(rom_path), &rom)
+}
 
-        println!("Max file id: {}", max_id);
+fn main() -> eyre::Result<()> {
+    match Cli::parse().command {
+        Command::Info { rom } => info(&rom),
+        Command::Ls { rom, breadth_first } => ls(&rom, breadth_first),
+        Command::Extract {
+            rom,
+            output,
+            decompress,
+        } => extract(&rom, &output, decompress),
+        Command::Overlays { rom } => overlays(&rom),
+        Command::Pack {
+            input,
+            output,
+            compress,
+        } => pack(&input, &output, compress),
+        Command::Cat { rom, path } => cat(&rom, &path),
+        Command::Header { rom, output } => print_header(&rom, output.as_deref()),
+        Command::Banner { rom, output } => banner(&rom, &output),
+        Command::Narc { command } => narc(command),
+        Command::Sound { command } => sound(command),
+        Command::Gfx { command } => gfx(command),
+        Command::Replace {
+            rom,
+            file,
+            replacement,
+            output,
+        } => replace(&rom, &file, &replacement, output.as_deref()),
+        Command::Trim { rom, output } => trim(&rom, output.as_deref()),
+        Command::Pad { rom, output } => pad(&rom, output.as_deref()),
+        Command::SecureArea { command } => secure_area(command),
+        Command::FixChecksums { rom, key, output } => {
+            fix_checksums(&rom, key.as_deref(), output.as_deref())
+        },
     }
 }
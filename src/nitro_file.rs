@@ -0,0 +1,153 @@
+use crate::{
+    byte_types::int::{U16, U32},
+    error::{Error, Result, Table},
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Header of the NitroSDK file formats like NARC, NCLR or SDAT
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct NitroHeader {
+    pub magic: [u8; 4],
+    /// 0xFEFF for little endian
+    pub byte_order: U16<LittleEndian>,
+    pub version: U16<LittleEndian>,
+    pub file_size: U32<LittleEndian>,
+    pub header_size: U16<LittleEndian>,
+    pub section_count: U16<LittleEndian>,
+}
+
+/// Start of every section, the size includes this header
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct SectionHeader {
+    pub magic: [u8; 4],
+    pub size: U32<LittleEndian>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Section<'lt> {
+    pub magic: [u8; 4],
+    /// Offset of the section header in the file
+    pub offset: usize,
+    /// Contents after the section header
+    pub data: &'lt [u8],
+}
+
+/// A file split into its sections
+#[derive(Clone, Debug)]
+pub struct NitroFile<'lt> {
+    pub header: NitroHeader,
+    pub sections: Vec<Section<'lt>>,
+}
+
+impl<'lt> NitroFile<'lt> {
+    /// Reads the header and the sections following it, checking that the file
+    /// starts with `magic`
+    pub fn read(data: &'lt [u8], magic: [u8; 4], table: Table) -> Result<Self> {
+        let out_of_bounds = |offset: usize, expected: usize| Error::OutOfBounds {
+            table,
+            offset,
+            expected,
+            actual: data.len().saturating_sub(offset),
+        };
+
+        let (header, _) = LayoutVerified::<_, NitroHeader>::new_from_prefix(data)
+            .ok_or_else(|| out_of_bounds(0, std::mem::size_of::<NitroHeader>()))?;
+        let header = *header;
+        if header.magic != magic {
+            return Err(Error::InvalidMagic {
+                table,
+                offset: 0,
+                expected: magic,
+                actual: header.magic,
+            });
+        }
+
+        let mut offset = header.header_size.get() as usize;
+        let mut sections = Vec::with_capacity(header.section_count.get() as usize);
+        for _ in 0..header.section_count.get() {
+            let section_header = data
+                .get(offset..)
+                .and_then(LayoutVerified::<_, SectionHeader>::new_from_prefix)
+                .map(|(section_header, _)| *section_header)
+                .ok_or_else(|| out_of_bounds(offset, std::mem::size_of::<SectionHeader>()))?;
+
+            let size = section_header.size.get() as usize;
+            let start = offset + std::mem::size_of::<SectionHeader>();
+            let section_data = data
+                .get(start..(offset + size))
+                .ok_or_else(|| out_of_bounds(offset, size))?;
+
+            sections.push(Section {
+                magic: section_header.magic,
+                offset,
+                data: section_data,
+            });
+            offset += size;
+        }
+
+        Ok(Self { header, sections })
+    }
+
+    pub fn section(&self, magic: [u8; 4]) -> Option<&Section<'lt>> {
+        self.sections.iter().find(|section| section.magic == magic)
+    }
+
+    /// Like [NitroFile::section], but reports a missing section as an error
+    pub fn require_section(&self, magic: [u8; 4], table: Table) -> Result<&Section<'lt>> {
+        self.section(magic)
+            .ok_or(Error::MissingSection { table, magic })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A file with the given magic and sections, for building test fixtures
+    pub(crate) fn build(magic: [u8; 4], sections: &[([u8; 4], &[u8])]) -> Vec<u8> {
+        let header_size = std::mem::size_of::<NitroHeader>();
+        let mut data = vec![0; header_size];
+        for (magic, contents) in sections {
+            let size = std::mem::size_of::<SectionHeader>() + contents.len();
+            data.extend_from_slice(magic);
+            data.extend_from_slice(&(size as u32).to_le_bytes());
+            data.extend_from_slice(contents);
+        }
+
+        let header = NitroHeader {
+            magic,
+            byte_order: 0xFEFF.into(),
+            version: 0x0100.into(),
+            file_size: (data.len() as u32).into(),
+            header_size: (header_size as u16).into(),
+            section_count: (sections.len() as u16).into(),
+        };
+        data[..header_size].copy_from_slice(header.as_bytes());
+        data
+    }
+
+    #[test]
+    fn sections() {
+        let data = build(*b"TEST", &[(*b"ABCD", &[1, 2, 3]), (*b"EFGH", &[])]);
+        let file = NitroFile::read(&data, *b"TEST", Table::Nclr).unwrap();
+
+        assert_eq!(file.sections.len(), 2);
+        assert_eq!(file.section(*b"ABCD").unwrap().data, [1, 2, 3]);
+        assert_eq!(file.section(*b"EFGH").unwrap().offset, 0x10 + 0xB);
+        assert!(matches!(
+            file.require_section(*b"IJKL", Table::Nclr),
+            Err(Error::MissingSection { .. })
+        ));
+        assert!(matches!(
+            NitroFile::read(&data, *b"NOPE", Table::Nclr),
+            Err(Error::InvalidMagic { .. })
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{
+    byte_types::int::U32,
+    cartridge_header::OffsetAndSize,
+    compression::blz,
+    error::{Error, Result, Table},
+    file::file_allocation_table::FileAllocationTableEntry,
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct OverlayTableEntry {
+    pub overlay_id: U32<LittleEndian>,
+    pub ram_address: U32<LittleEndian>,
+    pub ram_size: U32<LittleEndian>,
+    pub bss_size: U32<LittleEndian>,
+    pub static_initializer_start: U32<LittleEndian>,
+    pub static_initializer_end: U32<LittleEndian>,
+    pub file_id: U32<LittleEndian>,
+
+    /// Bits 0-23: compressed size, bit 24: compressed, bit 25: has an
+    /// authentication code
+    pub compressed: U32<LittleEndian>,
+}
+
+impl OverlayTableEntry {
+    const AUTHENTICATED_BIT: u32 = 1 << 25;
+    const COMPRESSED_BIT: u32 = 1 << 24;
+    const SIZE_MASK: u32 = 0x00FF_FFFF;
+
+    pub fn read_table<'lt>(
+        rom: &'lt [u8],
+        region: &OffsetAndSize,
+        table: Table,
+    ) -> Result<LayoutVerified<&'lt [u8], [OverlayTableEntry]>> {
+        let base = region.offset.get() as usize;
+        let size = region.size.get() as usize;
+
+        static DUMMY: &[u8] = &[];
+        if size == 0 {
+            return Ok(LayoutVerified::new_slice(DUMMY).unwrap());
+        }
+
+        let raw = rom.get(base..(base + size)).ok_or(Error::OutOfBounds {
+            table,
+            offset: base,
+            expected: size,
+            actual: rom.len().saturating_sub(base),
+        })?;
+
+        LayoutVerified::new_slice(raw).ok_or(Error::Misaligned {
+            table,
+            offset: base,
+            size,
+            entry_size: std::mem::size_of::<OverlayTableEntry>(),
+        })
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed.get() & Self::COMPRESSED_BIT != 0
+    }
+
+    pub fn has_authentication_code(&self) -> bool {
+        self.compressed.get() & Self::AUTHENTICATED_BIT != 0
+    }
+
+    /// Size of the overlay in the ROM if it is compressed
+    pub fn compressed_size(&self) -> u32 {
+        self.compressed.get() & Self::SIZE_MASK
+    }
+
+    pub fn set_compressed_size(&mut self, size: u32) {
+        let flags = self.compressed.get() & !Self::SIZE_MASK;
+        self.compressed.set(flags | (size & Self::SIZE_MASK));
+    }
+
+    /// Sets the compressed flag and the compressed size, which is cleared if
+    /// the overlay is not compressed
+    pub fn set_compressed(&mut self, compressed_size: Option<u32>) {
+        let flags = self.compressed.get() & Self::AUTHENTICATED_BIT;
+        self.compressed.set(match compressed_size {
+            Some(size) => flags | Self::COMPRESSED_BIT | (size & Self::SIZE_MASK),
+            None => flags,
+        });
+    }
+
+    /// Looks up the code of this overlay through the FAT
+    pub fn read_file<'lt>(
+        &self,
+        fat: &[FileAllocationTableEntry],
+        rom: &'lt [u8],
+    ) -> Result<&'lt [u8]> {
+        FileAllocationTableEntry::read_file(fat, self.file_id.get() as usize, rom)
+    }
+}
+
+/// An overlay's table entry together with its code
+#[derive(Clone, Debug)]
+pub struct Overlay {
+    pub entry: OverlayTableEntry,
+    pub data: Vec<u8>,
+}
+
+impl Overlay {
+    pub fn read_all(
+        table: &[OverlayTableEntry],
+        fat: &[FileAllocationTableEntry],
+        rom: &[u8],
+    ) -> Result<Vec<Self>> {
+        table
+            .iter()
+            .map(|entry| {
+                Ok(Self {
+                    entry: *entry,
+                    data: entry.read_file(fat, rom)?.to_vec(),
+                })
+            })
+            .collect()
+    }
+
+    /// Decompresses the code if the entry marks it as compressed
+    pub fn decompress(&mut self) -> Result<()> {
+        if self.entry.is_compressed() {
+            self.data = blz::decompress(&self.data)?;
+            self.entry.set_compressed(None);
+        }
+        Ok(())
+    }
+
+    /// Compresses the code unless it already is or doesn't get any smaller
+    pub fn compress(&mut self) {
+        if self.entry.is_compressed() {
+            return;
+        }
+
+        let compressed = blz::compress(&self.data, 0);
+        if blz::is_compressed(&compressed) {
+            self.entry.set_compressed(Some(compressed.len() as u32));
+            self.data = compressed;
+        }
+    }
+}
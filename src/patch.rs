@@ -0,0 +1,365 @@
+//! Replacing single files in a finished ROM without rebuilding it
+
+use crate::{
+    builder::{device_capacity_for, ALIGNMENT},
+    cartridge_header::{CartridgeHeader, OffsetAndSize},
+    error::{Error, Result, Table},
+    file::{file_allocation_table::FileAllocationTableEntry, FileId},
+    trim::{rsa_signature, rsa_signature_offset, PADDING},
+};
+use std::ops::Range;
+use zerocopy::{AsBytes, LayoutVerified};
+
+/// Where [replace_file] put the new contents
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Placement {
+    /// Written over the old contents
+    InPlace { offset: usize },
+    /// Moved to a gap of padding between other data
+    Gap { from: usize, to: usize },
+    /// Moved past the end of the used area
+    End { from: usize, to: usize },
+}
+
+fn range(region: &OffsetAndSize) -> Range<usize> {
+    let offset = region.offset.get() as usize;
+    offset..(offset + region.size.get() as usize)
+}
+
+/// All areas of `rom` that are referenced by the header or the FAT, except
+/// the file `skip`
+fn used_regions(
+    header: &CartridgeHeader,
+    rom: &[u8],
+    fat: &[FileAllocationTableEntry],
+    skip: usize,
+) -> Result<Vec<Range<usize>>> {
+    let header_end = (header.rom_header_size.get() as usize).max(0x200);
+    let mut regions = Vec::with_capacity(fat.len() + 16);
+    regions.push(0..header_end);
+    for code in [&header.arm9, &header.arm7] {
+        let offset = code.rom_offset.get() as usize;
+        // Leaves room for the NitroSDK footer after the ARM9 binary
+        regions.push(offset..(offset + code.size.get() as usize + 12));
+    }
+    for region in [
+        &header.fnt,
+        &header.fat,
+        &header.arm9_overlay,
+        &header.arm7_overlay,
+        &header.debug,
+    ] {
+        regions.push(range(region));
+    }
+    if let Some(banner) = header.read_banner_raw(rom)? {
+        let offset = header.icon_title_offset.get() as usize;
+        regions.push(offset..(offset + banner.len()));
+    }
+    if let Some(twl) = header.read_twl_header(rom)? {
+        for (offset, size) in [
+            (twl.arm9i_rom_offset, twl.arm9i_size),
+            (twl.arm7i_rom_offset, twl.arm7i_size),
+        ] {
+            let offset = offset.get() as usize;
+            regions.push(offset..(offset + size.get() as usize));
+        }
+        for region in [
+            &twl.digest_ntr_region,
+            &twl.digest_twl_region,
+            &twl.digest_sector_hashtable,
+            &twl.digest_block_hashtable,
+            &twl.modcrypt_area_1,
+            &twl.modcrypt_area_2,
+        ] {
+            regions.push(range(region));
+        }
+    }
+    for (id, entry) in fat.iter().enumerate() {
+        if id != skip {
+            regions.push((entry.start.get() as usize)..(entry.end.get() as usize));
+        }
+    }
+
+    regions.retain(|region| !region.is_empty());
+    regions.sort_by_key(|region| region.start);
+    Ok(regions)
+}
+
+fn align(offset: usize) -> usize {
+    offset.div_ceil(ALIGNMENT) * ALIGNMENT
+}
+
+/// The first aligned gap of at least `size` bytes that only contains
+/// padding, below `limit`
+fn find_gap(regions: &[Range<usize>], rom: &[u8], size: usize, limit: usize) -> Option<usize> {
+    let limit = limit.min(rom.len());
+    let mut free_from = 0;
+    for region in regions.iter().cloned().chain(std::iter::once(limit..limit)) {
+        let start = align(free_from);
+        if start + size <= region.start.min(limit)
+            && rom[start..(start + size)]
+                .iter()
+                .all(|&byte| byte == PADDING)
+        {
+            return Some(start);
+        }
+        free_from = free_from.max(region.end);
+    }
+    None
+}
+
+/// Replaces the contents of `file_id` with `data`.
+///
+/// The data is written over the old contents if it fits, otherwise into the
+/// first gap of 0xFF padding that is large enough, or after the end of the
+/// used area. Space the file no longer uses is filled with 0xFF. Apart from
+/// that, only the FAT entry and the header are changed; `total_used_rom_size`,
+/// the device capacity and the header checksum are updated. The RSA signature
+/// of a download play game is moved to the new end of the used area.
+///
+/// Overlay table entries are not updated when replacing an overlay.
+pub fn replace_file(rom: &mut Vec<u8>, file_id: FileId, data: &[u8]) -> Result<Placement> {
+    let (header, _) = LayoutVerified::<_, CartridgeHeader>::new_from_prefix(&rom[..]).ok_or(
+        Error::OutOfBounds {
+            table: Table::Header,
+            offset: 0,
+            expected: std::mem::size_of::<CartridgeHeader>(),
+            actual: rom.len(),
+        },
+    )?;
+    let mut header = *header;
+
+    let fat = header.read_fat(rom)?.to_vec();
+    let index = file_id.0 as usize;
+    let entry = *fat.get(index).ok_or(Error::InvalidFileId {
+        table: Table::FileAllocationTable,
+        file_id: index,
+        count: fat.len(),
+    })?;
+    let old = (entry.start.get() as usize)..(entry.end.get() as usize);
+    if old.end > rom.len() || old.start > old.end {
+        return Err(Error::OutOfBounds {
+            table: Table::File,
+            offset: old.start,
+            expected: old.len(),
+            actual: rom.len().saturating_sub(old.start),
+        });
+    }
+
+    let regions = used_regions(&header, rom, &fat, index)?;
+    let regions_end = regions.iter().map(|region| region.end).max().unwrap_or(0);
+    let mut used_end = header.total_used_rom_size.get() as usize;
+    if used_end == old.end {
+        // The file was the last thing in the ROM and may shrink or move
+        used_end = regions_end;
+    }
+    used_end = used_end.max(regions_end);
+
+    // Taken out and written back after the new end of the used area
+    let old_signature_offset = rsa_signature_offset(&header, rom)?;
+    let signature = rsa_signature(&header, rom)?.map(<[u8]>::to_vec);
+
+    let placement = if data.len() <= old.len() {
+        Placement::InPlace { offset: old.start }
+    } else if let Some(offset) = find_gap(&regions, rom, data.len(), used_end) {
+        Placement::Gap {
+            from: old.start,
+            to: offset,
+        }
+    } else {
+        Placement::End {
+            from: old.start,
+            to: align(used_end),
+        }
+    };
+
+    let start = match placement {
+        Placement::InPlace { offset }
+        | Placement::Gap { to: offset, .. }
+        | Placement::End { to: offset, .. } => offset,
+    };
+    let end = start + data.len();
+
+    rom[old.clone()].fill(PADDING);
+    if let Some(signature) = &signature {
+        rom[old_signature_offset..(old_signature_offset + signature.len())].fill(PADDING);
+    }
+    if rom.len() < end {
+        rom.resize(end, PADDING);
+    }
+    rom[start..end].copy_from_slice(data);
+
+    let new_entry = FileAllocationTableEntry {
+        start: (start as u32).into(),
+        end: (end as u32).into(),
+    };
+    let fat_offset =
+        header.fat.offset.get() as usize + index * std::mem::size_of::<FileAllocationTableEntry>();
+    rom[fat_offset..(fat_offset + std::mem::size_of::<FileAllocationTableEntry>())]
+        .copy_from_slice(new_entry.as_bytes());
+
+    header.total_used_rom_size = (used_end.max(end) as u32).into();
+    let mut rom_end = end;
+    if let Some(signature) = signature {
+        let offset = rsa_signature_offset(&header, rom)?;
+        rom_end = rom_end.max(offset + signature.len());
+        if rom.len() < rom_end {
+            rom.resize(rom_end, PADDING);
+        }
+        rom[offset..(offset + signature.len())].copy_from_slice(&signature);
+    }
+    let needed = device_capacity_for(rom_end).ok_or(Error::RomTooLarge { size: rom_end })?;
+    header.device_capacity_raw = header.device_capacity_raw.max(needed);
+    header.update_checksums();
+    rom[..std::mem::size_of::<CartridgeHeader>()].copy_from_slice(header.as_bytes());
+
+    Ok(placement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::RomBuilder,
+        file::tree::Directory,
+        trim::{used_size, RSA_SIGNATURE_MAGIC, RSA_SIGNATURE_SIZE},
+    };
+    use zerocopy::FromBytes;
+
+    /// A ROM with the files `a`, `b` and `c` of 0x10 bytes each
+    fn rom() -> Vec<u8> {
+        let mut root = Directory::default();
+        for (name, fill) in [("a", 0xA), ("b", 0xB), ("c", 0xC)] {
+            root.add_file(name, vec![fill; 0x10]).unwrap();
+        }
+        RomBuilder {
+            header: CartridgeHeader::new_zeroed(),
+            arm9: vec![0; 0x10],
+            arm7: vec![0; 0x10],
+            arm9_overlays: vec![],
+            arm7_overlays: vec![],
+            banner: None,
+            root,
+        }
+        .build()
+        .unwrap()
+    }
+
+    fn header(rom: &[u8]) -> CartridgeHeader {
+        CartridgeHeader::read_from_prefix(rom).unwrap()
+    }
+
+    fn file(rom: &[u8], id: u16) -> Vec<u8> {
+        header(rom)
+            .read_files(rom)
+            .unwrap()
+            .file(FileId(id))
+            .unwrap()
+            .to_vec()
+    }
+
+    fn start(rom: &[u8], id: u16) -> usize {
+        header(rom).read_fat(rom).unwrap()[id as usize].start.get() as usize
+    }
+
+    fn used(rom: &[u8]) -> usize {
+        header(rom).total_used_rom_size.get() as usize
+    }
+
+    fn add_signature(rom: &mut Vec<u8>) -> Vec<u8> {
+        let mut signature = RSA_SIGNATURE_MAGIC.to_vec();
+        signature.resize(RSA_SIGNATURE_SIZE, 0x5A);
+        rom.extend_from_slice(&signature);
+        signature
+    }
+
+    fn signature_at(rom: &[u8], offset: usize) -> &[u8] {
+        &rom[offset..(offset + RSA_SIGNATURE_SIZE)]
+    }
+
+    #[test]
+    fn in_place() {
+        let mut rom = rom();
+        let a = start(&rom, 0);
+        let used_before = used(&rom);
+
+        let placement = replace_file(&mut rom, FileId(0), &[1; 8]).unwrap();
+
+        assert_eq!(placement, Placement::InPlace { offset: a });
+        assert_eq!(file(&rom, 0), vec![1; 8]);
+        assert!(rom[(a + 8)..(a + 0x10)].iter().all(|&byte| byte == PADDING));
+        assert_eq!(file(&rom, 1), vec![0xB; 0x10]);
+        assert_eq!(used(&rom), used_before);
+        assert!(header(&rom).checksums(&rom).header.is_valid());
+    }
+
+    #[test]
+    fn gap() {
+        let mut rom = rom();
+        let a = start(&rom, 0);
+        let b = start(&rom, 1);
+
+        // Moving `a` to the end leaves its space free
+        replace_file(&mut rom, FileId(0), &[1; 0x300]).unwrap();
+        let placement = replace_file(&mut rom, FileId(1), &[2; 0x180]).unwrap();
+
+        assert_eq!(placement, Placement::Gap { from: b, to: a });
+        assert_eq!(file(&rom, 0), vec![1; 0x300]);
+        assert_eq!(file(&rom, 1), vec![2; 0x180]);
+        assert_eq!(file(&rom, 2), vec![0xC; 0x10]);
+    }
+
+    #[test]
+    fn end() {
+        let mut rom = rom();
+        let a = start(&rom, 0);
+        let used_before = used(&rom);
+
+        let placement = replace_file(&mut rom, FileId(0), &[1; 0x300]).unwrap();
+
+        let to = used_before.div_ceil(ALIGNMENT) * ALIGNMENT;
+        assert_eq!(placement, Placement::End { from: a, to });
+        assert_eq!(file(&rom, 0), vec![1; 0x300]);
+        assert!(rom[a..(a + 0x10)].iter().all(|&byte| byte == PADDING));
+        assert_eq!(used(&rom), to + 0x300);
+        assert_eq!(file(&rom, 2), vec![0xC; 0x10]);
+    }
+
+    #[test]
+    fn end_with_signature() {
+        let mut rom = rom();
+        let old_used = used(&rom);
+        let signature = add_signature(&mut rom);
+
+        replace_file(&mut rom, FileId(0), &[1; 0x300]).unwrap();
+
+        let new_used = used(&rom);
+        assert!(new_used > old_used);
+        assert_eq!(file(&rom, 0), vec![1; 0x300]);
+        assert_eq!(signature_at(&rom, new_used), signature);
+        assert!(signature_at(&rom, old_used)
+            .iter()
+            .all(|&byte| byte == PADDING));
+        assert_eq!(
+            used_size(&header(&rom), &rom).unwrap(),
+            new_used + RSA_SIGNATURE_SIZE
+        );
+    }
+
+    #[test]
+    fn shrink_last_with_signature() {
+        let mut rom = rom();
+        let c = start(&rom, 2);
+        let signature = add_signature(&mut rom);
+
+        let placement = replace_file(&mut rom, FileId(2), &[3; 4]).unwrap();
+
+        assert_eq!(placement, Placement::InPlace { offset: c });
+        assert_eq!(used(&rom), c + 4);
+        assert_eq!(signature_at(&rom, c + 4), signature);
+        assert_eq!(
+            used_size(&header(&rom), &rom).unwrap(),
+            c + 4 + RSA_SIGNATURE_SIZE
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! Access to a ROM through [Read] and [Seek], for when loading the whole ROM
+//! into memory is too expensive.
+//!
+//! Only the header is read up front, the FNT and FAT are read on first use and
+//! files are returned as readers limited to their data.
+
+use crate::{
+    cartridge_header::CartridgeHeader,
+    error::{Error, Result, Table},
+    file::{
+        file_allocation_table::FileAllocationTableEntry, file_name_table::FileNameTable, FileId,
+    },
+};
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use zerocopy::{AsBytes, FromBytes};
+
+pub struct RomSource<R> {
+    reader: R,
+    header: CartridgeHeader,
+    /// Size of the whole ROM
+    len: u64,
+    fnt: Option<FileNameTable>,
+    fat: Option<Vec<FileAllocationTableEntry>>,
+}
+
+impl<R: Read + Seek> RomSource<R> {
+    /// Reads the header. Like a cartridge, a ROM shorter than the header reads
+    /// as zeros after its end.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut header = CartridgeHeader::new_zeroed();
+        read_up_to(&mut reader, header.as_bytes_mut())?;
+
+        Ok(Self {
+            reader,
+            header,
+            len,
+            fnt: None,
+            fat: None,
+        })
+    }
+
+    pub fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    /// Size of the whole ROM in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads `size` bytes at `offset`, which have to lie inside the ROM
+    pub fn read_at(&mut self, offset: u64, size: usize, table: Table) -> Result<Vec<u8>> {
+        self.check_bounds(offset, size as u64, table)?;
+
+        let mut data = vec![0; size];
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    fn check_bounds(&self, offset: u64, size: u64, table: Table) -> Result<()> {
+        if offset.saturating_add(size) > self.len {
+            return Err(Error::OutOfBounds {
+                table,
+                offset: offset as usize,
+                expected: size as usize,
+                actual: self.len.saturating_sub(offset) as usize,
+            });
+        }
+        Ok(())
+    }
+
+    /// The file name table, read on first use.
+    ///
+    /// Offsets in errors are relative to the start of the FNT.
+    pub fn fnt(&mut self) -> Result<&FileNameTable> {
+        if self.fnt.is_none() {
+            let size = self.header.fnt.size.get() as usize;
+            let fnt = if size == 0 {
+                FileNameTable {
+                    main_table: vec![],
+                    sub_tables: vec![],
+                }
+            } else {
+                let offset = self.header.fnt.offset.get() as u64;
+                FileNameTable::read(&self.read_at(offset, size, Table::FileNameTable)?, 0)?
+            };
+            self.fnt = Some(fnt);
+        }
+
+        Ok(self.fnt.as_ref().unwrap())
+    }
+
+    /// The file allocation table, read on first use
+    pub fn fat(&mut self) -> Result<&[FileAllocationTableEntry]> {
+        if self.fat.is_none() {
+            let offset = self.header.fat.offset.get() as usize;
+            let size = self.header.fat.size.get() as usize;
+            let raw = self.read_at(offset as u64, size, Table::FileAllocationTable)?;
+            let fat = FileAllocationTableEntry::parse_fat(&raw, offset)?.to_vec();
+            self.fat = Some(fat);
+        }
+
+        Ok(self.fat.as_deref().unwrap())
+    }
+
+    /// See [FileNameTable::lookup]
+    pub fn lookup(&mut self, path: &str) -> Result<Option<FileId>> {
+        Ok(self.fnt()?.lookup(path))
+    }
+
+    /// Returns a reader over the data of `file_id`
+    pub fn file(&mut self, file_id: FileId) -> Result<FileReader<'_, R>> {
+        let fat = self.fat()?;
+        let entry = *fat.get(file_id.0 as usize).ok_or(Error::InvalidFileId {
+            table: Table::FileAllocationTable,
+            file_id: file_id.0 as usize,
+            count: fat.len(),
+        })?;
+
+        let start = entry.start.get() as u64;
+        let len = (entry.end.get() as u64).saturating_sub(start);
+        self.check_bounds(start, len, Table::File)?;
+
+        self.reader.seek(SeekFrom::Start(start))?;
+        Ok(FileReader {
+            reader: &mut self.reader,
+            start,
+            len,
+            position: 0,
+        })
+    }
+
+    /// Returns a reader over the file at `path`
+    pub fn open(&mut self, path: &str) -> Result<FileReader<'_, R>> {
+        let file_id = self.lookup(path)?.ok_or_else(|| Error::FileNotFound {
+            path: path.to_string(),
+        })?;
+        self.file(file_id)
+    }
+}
+
+/// Fills `buffer` as far as the reader allows, leaving the rest untouched
+fn read_up_to(reader: &mut impl Read, buffer: &mut [u8]) -> Result<()> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {},
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// A file inside a [RomSource], positions are relative to the start of the
+/// file
+pub struct FileReader<'lt, R> {
+    reader: &'lt mut R,
+    start: u64,
+    len: u64,
+    position: u64,
+}
+
+impl<'lt, R> FileReader<'lt, R> {
+    /// Size of the file in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<'lt, R: Read + Seek> Read for FileReader<'lt, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.position);
+        let size = (buf.len() as u64).min(remaining) as usize;
+        if size == 0 {
+            return Ok(0);
+        }
+
+        let read = self.reader.read(&mut buf[..size])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<'lt, R: Read + Seek> Seek for FileReader<'lt, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        }
+        .ok_or_else(|| {
+            std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        self.reader
+            .seek(SeekFrom::Start(self.start.saturating_add(position)))?;
+        self.position = position;
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::RomBuilder, file::tree::Directory};
+    use std::io::Cursor;
+
+    /// A ROM with `/a` and `/dir/b`, files are laid out in ID order
+    fn rom() -> Vec<u8> {
+        let mut root = Directory::default();
+        root.add_file("a", vec![0xA; 0x10]).unwrap();
+        root.add_file("dir/b", vec![0xB; 0x8]).unwrap();
+        RomBuilder {
+            header: CartridgeHeader::new_zeroed(),
+            arm9: vec![0; 0x10],
+            arm7: vec![0; 0x10],
+            arm9_overlays: vec![],
+            arm7_overlays: vec![],
+            banner: None,
+            root,
+        }
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    fn open() {
+        let mut source = RomSource::new(Cursor::new(rom())).unwrap();
+
+        assert_eq!(source.lookup("/dir/b").unwrap(), Some(FileId(1)));
+        assert_eq!(source.lookup("/dir/missing").unwrap(), None);
+        assert!(matches!(
+            source.open("/missing"),
+            Err(Error::FileNotFound { .. })
+        ));
+        assert!(matches!(
+            source.file(FileId(2)),
+            Err(Error::InvalidFileId { count: 2, .. })
+        ));
+
+        // Reads stop at the end of the file, not at the end of the buffer
+        let mut file = source.open("/a").unwrap();
+        assert_eq!(file.len(), 0x10);
+        let mut data = vec![];
+        file.read_to_end(&mut data).unwrap();
+        assert_eq!(data, [0xA; 0x10]);
+        let mut buffer = [0; 4];
+        assert_eq!(file.read(&mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn seek() {
+        let mut source = RomSource::new(Cursor::new(rom())).unwrap();
+        let mut file = source.open("/dir/b").unwrap();
+
+        assert_eq!(file.seek(SeekFrom::End(-2)).unwrap(), 6);
+        let mut buffer = [0; 4];
+        assert_eq!(file.read(&mut buffer).unwrap(), 2);
+        assert_eq!(buffer, [0xB, 0xB, 0, 0]);
+
+        assert_eq!(file.seek(SeekFrom::Current(-8)).unwrap(), 0);
+        assert!(file.seek(SeekFrom::Current(-1)).is_err());
+
+        // Past the end reads nothing, like a file
+        assert_eq!(file.seek(SeekFrom::Start(0x20)).unwrap(), 0x20);
+        assert_eq!(file.read(&mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn truncated() {
+        let mut rom = rom();
+        let end = RomSource::new(Cursor::new(&rom)).unwrap().fat().unwrap()[1]
+            .end
+            .get() as usize;
+        rom.truncate(end - 1);
+        let mut source = RomSource::new(Cursor::new(rom)).unwrap();
+
+        assert!(source.open("/a").is_ok());
+        assert!(matches!(
+            source.open("/dir/b"),
+            Err(Error::OutOfBounds {
+                table: Table::File,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn short_header() {
+        let mut source = RomSource::new(Cursor::new(b"TITLE".to_vec())).unwrap();
+
+        assert_eq!(source.len(), 5);
+        assert_eq!(source.header().title.0[..6], *b"TITLE\0");
+        assert_eq!(source.lookup("/a").unwrap(), None);
+        assert!(source.fat().is_ok());
+    }
+}
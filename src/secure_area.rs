@@ -0,0 +1,363 @@
+//! KEY1 encryption of the secure area, the first 2 KiB of the ARM9 binary.
+//!
+//! KEY1 is Blowfish with a key table taken from the ARM7 BIOS, which is not
+//! included here and has to be supplied by the user. The table is modified by
+//! the game code before use.
+//!
+//! Decrypted, the secure area starts with "encryObj". Cartridges and most
+//! tools destroy that marker after checking it, replacing it with
+//! [DECRYPTED_MARKER].
+
+use crate::{
+    cartridge_header::CartridgeHeader,
+    error::{Error, Result},
+};
+use std::ops::Range;
+
+/// ROM region that is KEY1-encrypted on retail cartridges
+pub const SECURE_AREA_RANGE: Range<usize> = 0x4000..0x4800;
+
+/// Start of a decrypted secure area, two `0xE7FFDEFF` words
+pub const DECRYPTED_MARKER: [u8; 8] = [0xFF, 0xDE, 0xFF, 0xE7, 0xFF, 0xDE, 0xFF, 0xE7];
+
+/// Start of a decrypted secure area before the marker is destroyed
+pub const ENCRYPTION_MARKER: [u8; 8] = *b"encryObj";
+
+/// Size of the key table in bytes
+pub const KEY_TABLE_SIZE: usize = 0x1048;
+
+/// Where the key table lies in the ARM7 BIOS
+pub const BIOS_KEY_TABLE_RANGE: Range<usize> = 0x30..(0x30 + KEY_TABLE_SIZE);
+
+const BIOS_SIZE: usize = 0x4000;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum State {
+    /// The ARM9 binary does not start at the secure area
+    Absent,
+    /// Starts with [DECRYPTED_MARKER]
+    Decrypted,
+    /// Decrypted, but still starts with [ENCRYPTION_MARKER]
+    Marked,
+    /// Decrypts to [ENCRYPTION_MARKER] with the given key table
+    Encrypted,
+    /// None of the above, either encrypted and checked without a key table,
+    /// or code without a secure area
+    Unknown,
+}
+
+/// The Blowfish state, 18 P-array entries followed by four S-boxes
+#[derive(Clone)]
+pub struct KeyTable {
+    words: Vec<u32>,
+}
+
+impl KeyTable {
+    /// Reads the table from either the table itself or a dump of the ARM7 BIOS
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let table = match data.len() {
+            KEY_TABLE_SIZE => data,
+            BIOS_SIZE => &data[BIOS_KEY_TABLE_RANGE],
+            size => return Err(Error::InvalidKeyTable { size }),
+        };
+
+        Ok(Self {
+            words: table
+                .chunks_exact(4)
+                .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+                .collect(),
+        })
+    }
+
+    /// The table modified for `game_code`, as done by the BIOS before it
+    /// decrypts the secure area
+    fn for_game(&self, game_code: u32, level: u32) -> Self {
+        let mut key = self.clone();
+        let mut key_code = [game_code, game_code / 2, game_code.wrapping_mul(2)];
+
+        if level >= 1 {
+            key.apply_key_code(&mut key_code);
+        }
+        if level >= 2 {
+            key.apply_key_code(&mut key_code);
+        }
+        key_code[1] = key_code[1].wrapping_mul(2);
+        key_code[2] /= 2;
+        if level >= 3 {
+            key.apply_key_code(&mut key_code);
+        }
+
+        key
+    }
+
+    fn apply_key_code(&mut self, key_code: &mut [u32; 3]) {
+        let mut pair = [key_code[1], key_code[2]];
+        self.encrypt(&mut pair);
+        [key_code[1], key_code[2]] = pair;
+        let mut pair = [key_code[0], key_code[1]];
+        self.encrypt(&mut pair);
+        [key_code[0], key_code[1]] = pair;
+
+        // The key code is used with a modulo of 8 bytes, so only the first two
+        // words take part
+        for index in 0..18 {
+            self.words[index] ^= key_code[index % 2].swap_bytes();
+        }
+
+        let mut scratch = [0; 2];
+        for index in (0..0x412).step_by(2) {
+            self.encrypt(&mut scratch);
+            self.words[index] = scratch[1];
+            self.words[index + 1] = scratch[0];
+        }
+    }
+
+    fn feistel(&self, z: u32) -> u32 {
+        let s = &self.words[18..];
+        let x = s[(z >> 24) as usize];
+        let x = s[0x100 + ((z >> 16) & 0xFF) as usize].wrapping_add(x);
+        let x = s[0x200 + ((z >> 8) & 0xFF) as usize] ^ x;
+        s[0x300 + (z & 0xFF) as usize].wrapping_add(x)
+    }
+
+    fn encrypt(&self, block: &mut [u32; 2]) {
+        let [mut y, mut x] = *block;
+        for index in 0..16 {
+            let z = self.words[index] ^ x;
+            x = y ^ self.feistel(z);
+            y = z;
+        }
+        *block = [x ^ self.words[16], y ^ self.words[17]];
+    }
+
+    fn decrypt(&self, block: &mut [u32; 2]) {
+        let [mut y, mut x] = *block;
+        for index in (2..18).rev() {
+            let z = self.words[index] ^ x;
+            x = y ^ self.feistel(z);
+            y = z;
+        }
+        *block = [x ^ self.words[1], y ^ self.words[0]];
+    }
+}
+
+fn apply_to_blocks(data: &mut [u8], mut function: impl FnMut(&mut [u32; 2])) {
+    for chunk in data.chunks_exact_mut(8) {
+        let mut block = [
+            u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+            u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+        ];
+        function(&mut block);
+        chunk[..4].copy_from_slice(&block[0].to_le_bytes());
+        chunk[4..].copy_from_slice(&block[1].to_le_bytes());
+    }
+}
+
+fn game_code(header: &CartridgeHeader) -> u32 {
+    let code = header.game_code.0;
+    u32::from_le_bytes([code[0], code[1], code[2], code[3]])
+}
+
+fn secure_area<'lt>(header: &CartridgeHeader, rom: &'lt mut [u8]) -> Result<&'lt mut [u8]> {
+    if header.arm9.rom_offset.get() as usize != SECURE_AREA_RANGE.start {
+        return Err(Error::InvalidSecureArea {
+            reason: "the ARM9 binary does not start at the secure area",
+        });
+    }
+    rom.get_mut(SECURE_AREA_RANGE)
+        .ok_or(Error::InvalidSecureArea {
+            reason: "the ROM ends before the end of the secure area",
+        })
+}
+
+/// Decrypts only the first block, enough to check for [ENCRYPTION_MARKER]
+fn decrypt_first_block(first_block: &[u8], game_code: u32, key: &KeyTable) -> [u8; 8] {
+    let mut block = [0; 8];
+    block.copy_from_slice(&first_block[..8]);
+    apply_to_blocks(&mut block, |b| key.for_game(game_code, 2).decrypt(b));
+    apply_to_blocks(&mut block, |b| key.for_game(game_code, 3).decrypt(b));
+    block
+}
+
+/// Classifies the secure area of `rom`. Without a key table, encrypted secure
+/// areas are reported as [State::Unknown].
+pub fn state(header: &CartridgeHeader, rom: &[u8], key: Option<&KeyTable>) -> State {
+    if header.arm9.rom_offset.get() as usize != SECURE_AREA_RANGE.start {
+        return State::Absent;
+    }
+    let Some(area) = rom.get(SECURE_AREA_RANGE) else {
+        return State::Absent;
+    };
+
+    if area[..8] == DECRYPTED_MARKER {
+        State::Decrypted
+    } else if area[..8] == ENCRYPTION_MARKER {
+        State::Marked
+    } else if key
+        .is_some_and(|key| decrypt_first_block(area, game_code(header), key) == ENCRYPTION_MARKER)
+    {
+        State::Encrypted
+    } else {
+        State::Unknown
+    }
+}
+
+/// Decrypts the secure area in place and replaces [ENCRYPTION_MARKER] with
+/// [DECRYPTED_MARKER].
+///
+/// `secure_area_checksum` is left alone, it covers the encrypted data.
+pub fn decrypt(header: &CartridgeHeader, rom: &mut [u8], key: &KeyTable) -> Result<()> {
+    let game_code = game_code(header);
+    let area = secure_area(header, rom)?;
+    if decrypt_first_block(area, game_code, key) != ENCRYPTION_MARKER {
+        return Err(Error::InvalidSecureArea {
+            reason: "it does not decrypt to \"encryObj\", it is either not encrypted or the key table is wrong",
+        });
+    }
+
+    apply_to_blocks(&mut area[..8], |b| key.for_game(game_code, 2).decrypt(b));
+    let level_3 = key.for_game(game_code, 3);
+    apply_to_blocks(area, |b| level_3.decrypt(b));
+    area[..8].copy_from_slice(&DECRYPTED_MARKER);
+
+    Ok(())
+}
+
+/// Reverses [decrypt], restoring the secure area as found on cartridges
+pub fn encrypt(header: &CartridgeHeader, rom: &mut [u8], key: &KeyTable) -> Result<()> {
+    let game_code = game_code(header);
+    let area = secure_area(header, rom)?;
+    if area[..8] != DECRYPTED_MARKER && area[..8] != ENCRYPTION_MARKER {
+        return Err(Error::InvalidSecureArea {
+            reason: "it does not start with a decrypted marker",
+        });
+    }
+
+    area[..8].copy_from_slice(&ENCRYPTION_MARKER);
+    let level_3 = key.for_game(game_code, 3);
+    apply_to_blocks(area, |b| level_3.encrypt(b));
+    apply_to_blocks(&mut area[..8], |b| key.for_game(game_code, 2).encrypt(b));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zerocopy::FromBytes;
+
+    /// A key table of deterministic pseudo-random bytes
+    fn key() -> KeyTable {
+        let mut value = 0x1234_5678u32;
+        let data = (0..KEY_TABLE_SIZE)
+            .map(|_| {
+                value ^= value << 13;
+                value ^= value >> 17;
+                value ^= value << 5;
+                value as u8
+            })
+            .collect::<Vec<_>>();
+        KeyTable::read(&data).unwrap()
+    }
+
+    /// A ROM whose decrypted secure area is followed by counting bytes
+    fn rom() -> (CartridgeHeader, Vec<u8>) {
+        let mut header = CartridgeHeader::new_zeroed();
+        header.game_code.0 = *b"ABCD";
+        header.arm9.rom_offset = (SECURE_AREA_RANGE.start as u32).into();
+
+        let mut rom = vec![0; SECURE_AREA_RANGE.end + 0x10];
+        for (index, byte) in rom[SECURE_AREA_RANGE].iter_mut().enumerate() {
+            *byte = index as u8;
+        }
+        rom[SECURE_AREA_RANGE.start..][..8].copy_from_slice(&DECRYPTED_MARKER);
+        (header, rom)
+    }
+
+    #[test]
+    fn read_key_table() {
+        let mut bios = vec![0; BIOS_SIZE];
+        bios[BIOS_KEY_TABLE_RANGE.start] = 0x78;
+        bios[BIOS_KEY_TABLE_RANGE.end - 1] = 0x12;
+        let key = KeyTable::read(&bios).unwrap();
+        assert_eq!(key.words.len(), KEY_TABLE_SIZE / 4);
+        assert_eq!(key.words[0], 0x78);
+        assert_eq!(key.words[KEY_TABLE_SIZE / 4 - 1], 0x1200_0000);
+
+        assert!(matches!(
+            KeyTable::read(&[0; 0x100]),
+            Err(Error::InvalidKeyTable { size: 0x100 })
+        ));
+    }
+
+    #[test]
+    fn blocks() {
+        let key = key();
+        for level in 1..=3 {
+            let key = key.for_game(0x4443_4241, level);
+            let mut block = [0x0123_4567, 0x89AB_CDEF];
+            key.encrypt(&mut block);
+            assert_ne!(block, [0x0123_4567, 0x89AB_CDEF]);
+            key.decrypt(&mut block);
+            assert_eq!(block, [0x0123_4567, 0x89AB_CDEF]);
+        }
+
+        // Every level changes the table further
+        let words = |level| key.for_game(0x4443_4241, level).words;
+        assert_ne!(words(1), key.words);
+        assert_ne!(words(2), words(1));
+        assert_ne!(words(3), words(2));
+        assert_eq!(words(3), words(3));
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = key();
+        let (header, original) = rom();
+        let mut rom = original.clone();
+        assert_eq!(state(&header, &rom, Some(&key)), State::Decrypted);
+
+        encrypt(&header, &mut rom, &key).unwrap();
+        let area = &rom[SECURE_AREA_RANGE];
+        assert_ne!(area, &original[SECURE_AREA_RANGE]);
+        assert_eq!(
+            decrypt_first_block(area, game_code(&header), &key),
+            ENCRYPTION_MARKER
+        );
+        assert_eq!(
+            rom[SECURE_AREA_RANGE.end..],
+            original[SECURE_AREA_RANGE.end..]
+        );
+        assert_eq!(state(&header, &rom, Some(&key)), State::Encrypted);
+        assert_eq!(state(&header, &rom, None), State::Unknown);
+
+        decrypt(&header, &mut rom, &key).unwrap();
+        assert_eq!(rom, original);
+    }
+
+    #[test]
+    fn marked() {
+        let key = key();
+        let (header, original) = rom();
+        let mut rom = original.clone();
+        rom[SECURE_AREA_RANGE.start..][..8].copy_from_slice(&ENCRYPTION_MARKER);
+        assert_eq!(state(&header, &rom, None), State::Marked);
+
+        encrypt(&header, &mut rom, &key).unwrap();
+        decrypt(&header, &mut rom, &key).unwrap();
+        assert_eq!(rom, original);
+    }
+
+    #[test]
+    fn plain_area() {
+        let key = key();
+        let (mut header, mut rom) = rom();
+        rom[SECURE_AREA_RANGE.start..][..8].fill(0);
+        assert_eq!(state(&header, &rom, Some(&key)), State::Unknown);
+        assert!(decrypt(&header, &mut rom, &key).is_err());
+        assert!(encrypt(&header, &mut rom, &key).is_err());
+
+        header.arm9.rom_offset = 0x8000.into();
+        assert_eq!(state(&header, &rom, Some(&key)), State::Absent);
+    }
+}
@@ -0,0 +1,184 @@
+use byteorder::{BigEndian, WriteBytesExt};
+use std::io::Write;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MidiEvent {
+    NoteOff {
+        key: u8,
+    },
+    NoteOn {
+        key: u8,
+        velocity: u8,
+    },
+    Controller {
+        controller: u8,
+        value: u8,
+    },
+    Program(u8),
+    /// -0x2000 to 0x1FFF
+    PitchBend(i16),
+    /// Microseconds per quarter note
+    Tempo(u32),
+    TrackName(String),
+}
+
+impl MidiEvent {
+    /// Note offs sort before other events at the same time, so that a note
+    /// can end and start again on the same tick
+    fn order(&self) -> u8 {
+        match self {
+            MidiEvent::NoteOff { .. } => 0,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MidiTrack {
+    /// Used for all channel events of the track
+    pub channel: u8,
+    /// Events with their time in ticks, in any order
+    pub events: Vec<(u32, MidiEvent)>,
+}
+
+impl MidiTrack {
+    pub fn push(&mut self, time: u32, event: MidiEvent) {
+        self.events.push((time, event));
+    }
+}
+
+/// A Standard MIDI File of type 1, with a track for tempo changes followed by
+/// one track per channel
+#[derive(Clone, Debug)]
+pub struct Smf {
+    /// Ticks per quarter note
+    pub division: u16,
+    pub tracks: Vec<MidiTrack>,
+}
+
+fn write_variable_length<W: Write>(writer: &mut W, value: u32) -> std::io::Result<()> {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value != 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.reverse();
+    writer.write_all(&bytes)
+}
+
+impl Smf {
+    pub fn write_smf<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(b"MThd")?;
+        writer.write_u32::<BigEndian>(6)?;
+        writer.write_u16::<BigEndian>(1)?;
+        writer.write_u16::<BigEndian>(self.tracks.len() as u16)?;
+        writer.write_u16::<BigEndian>(self.division)?;
+
+        for track in &self.tracks {
+            let mut events = track.events.iter().collect::<Vec<_>>();
+            events.sort_by_key(|(time, event)| (*time, event.order()));
+
+            let mut data = vec![];
+            let mut last_time = 0;
+            let channel = track.channel & 0xF;
+            for (time, event) in events {
+                write_variable_length(&mut data, time - last_time)?;
+                last_time = *time;
+
+                match event {
+                    MidiEvent::NoteOff { key } => data.write_all(&[0x80 | channel, *key, 0])?,
+                    MidiEvent::NoteOn { key, velocity } => {
+                        data.write_all(&[0x90 | channel, *key, (*velocity).clamp(1, 0x7F)])?
+                    },
+                    MidiEvent::Controller { controller, value } => {
+                        data.write_all(&[0xB0 | channel, *controller, *value])?
+                    },
+                    MidiEvent::Program(program) => data.write_all(&[0xC0 | channel, *program])?,
+                    MidiEvent::PitchBend(value) => {
+                        let value = (*value as i32 + 0x2000).clamp(0, 0x3FFF) as u16;
+                        data.write_all(&[0xE0 | channel, (value & 0x7F) as u8, (value >> 7) as u8])?
+                    },
+                    MidiEvent::Tempo(tempo) => {
+                        data.write_all(&[0xFF, 0x51, 3])?;
+                        data.write_all(&tempo.to_be_bytes()[1..])?
+                    },
+                    MidiEvent::TrackName(name) => {
+                        data.write_all(&[0xFF, 0x03])?;
+                        write_variable_length(&mut data, name.len() as u32)?;
+                        data.write_all(name.as_bytes())?
+                    },
+                }
+            }
+            data.write_all(&[0, 0xFF, 0x2F, 0])?;
+
+            writer.write_all(b"MTrk")?;
+            writer.write_u32::<BigEndian>(data.len() as u32)?;
+            writer.write_all(&data)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable_length() {
+        for (value, expected) in [
+            (0, vec![0x00]),
+            (0x7F, vec![0x7F]),
+            (0x80, vec![0x81, 0x00]),
+            (0x0FFF_FFFF, vec![0xFF, 0xFF, 0xFF, 0x7F]),
+        ] {
+            let mut bytes = vec![];
+            write_variable_length(&mut bytes, value).unwrap();
+            assert_eq!(bytes, expected);
+        }
+    }
+
+    #[test]
+    fn data_bytes() {
+        let smf = Smf {
+            division: 48,
+            tracks: vec![MidiTrack {
+                channel: 3,
+                events: vec![
+                    (
+                        0,
+                        MidiEvent::NoteOn {
+                            key: 60,
+                            velocity: 0x90,
+                        },
+                    ),
+                    (
+                        0,
+                        MidiEvent::NoteOn {
+                            key: 62,
+                            velocity: 0,
+                        },
+                    ),
+                    (0, MidiEvent::NoteOff { key: 64 }),
+                    (1, MidiEvent::PitchBend(-0x2000)),
+                ],
+            }],
+        };
+        let mut bytes = vec![];
+        smf.write_smf(&mut bytes).unwrap();
+        assert_eq!(
+            bytes[22..],
+            [
+                0, 0x83, 64, 0, // note offs come first
+                0, 0x93, 60, 0x7F, // velocities stay data bytes
+                0, 0x93, 62, 1, // and don't turn into note offs
+                1, 0xE3, 0, 0, // lowest pitch bend
+                0, 0xFF, 0x2F, 0,
+            ]
+        );
+    }
+}
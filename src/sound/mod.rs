@@ -0,0 +1,6 @@
+pub mod midi;
+pub mod sdat;
+pub mod sseq;
+pub mod strm;
+pub mod swav;
+pub mod wave;
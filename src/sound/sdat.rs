@@ -0,0 +1,699 @@
+use crate::{
+    byte_types::int::{U16, U32},
+    cartridge_header::default_array,
+    error::{Error, Result, Table},
+    nitro_file::{NitroFile, NitroHeader, Section},
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Start of the SYMB and INFO blocks, one offset per record type
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct RecordOffsets {
+    /// Relative to the start of the block
+    pub offsets: [U32<LittleEndian>; 8],
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _reserved: [u8; 24],
+}
+
+/// SYMB entry of a sequence archive
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct SequenceArchiveSymbol {
+    /// Relative to the start of the SYMB block
+    pub name: U32<LittleEndian>,
+    /// Offset of the record naming the sequences inside
+    pub sequences: U32<LittleEndian>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct SoundFatEntry {
+    /// Relative to the start of the SDAT file
+    pub offset: U32<LittleEndian>,
+    pub size: U32<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _reserved: [u8; 8],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct SequenceInfo {
+    pub file_id: U16<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip)]
+    pub _unknown: U16<LittleEndian>,
+    pub bank: U16<LittleEndian>,
+    pub volume: u8,
+    pub channel_priority: u8,
+    pub player_priority: u8,
+    pub player: u8,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _reserved: [u8; 2],
+}
+
+/// Info of sequence archives and wave archives
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct ArchiveInfo {
+    pub file_id: U16<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip)]
+    pub _unknown: U16<LittleEndian>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct BankInfo {
+    pub file_id: U16<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip)]
+    pub _unknown: U16<LittleEndian>,
+    /// Indices of the wave archives the bank plays, 0xFFFF if unused
+    pub wave_archives: [U16<LittleEndian>; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct StreamInfo {
+    pub file_id: U16<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip)]
+    pub _unknown: U16<LittleEndian>,
+    pub volume: u8,
+    pub priority: u8,
+    pub player: u8,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _reserved: [u8; 5],
+}
+
+/// The record types of an SDAT that refer to files
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SoundKind {
+    /// SSEQ
+    Sequence,
+    /// SSAR
+    SequenceArchive,
+    /// SBNK
+    Bank,
+    /// SWAR
+    WaveArchive,
+    /// STRM
+    Stream,
+}
+
+impl SoundKind {
+    pub const ALL: [SoundKind; 5] = [
+        SoundKind::Sequence,
+        SoundKind::SequenceArchive,
+        SoundKind::Bank,
+        SoundKind::WaveArchive,
+        SoundKind::Stream,
+    ];
+
+    /// Position in [RecordOffsets], players and groups lie in between
+    fn record(self) -> usize {
+        match self {
+            SoundKind::Sequence => 0,
+            SoundKind::SequenceArchive => 1,
+            SoundKind::Bank => 2,
+            SoundKind::WaveArchive => 3,
+            SoundKind::Stream => 7,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            SoundKind::Sequence => "sseq",
+            SoundKind::SequenceArchive => "ssar",
+            SoundKind::Bank => "sbnk",
+            SoundKind::WaveArchive => "swar",
+            SoundKind::Stream => "strm",
+        }
+    }
+
+    /// Directory the files of this kind are extracted to
+    pub fn directory(self) -> &'static str {
+        match self {
+            SoundKind::Sequence => "sequences",
+            SoundKind::SequenceArchive => "sequence_archives",
+            SoundKind::Bank => "banks",
+            SoundKind::WaveArchive => "wave_archives",
+            SoundKind::Stream => "streams",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum SoundInfo {
+    Sequence(SequenceInfo),
+    SequenceArchive(ArchiveInfo),
+    Bank(BankInfo),
+    WaveArchive(ArchiveInfo),
+    Stream(StreamInfo),
+}
+
+impl SoundInfo {
+    pub fn kind(&self) -> SoundKind {
+        match self {
+            SoundInfo::Sequence(_) => SoundKind::Sequence,
+            SoundInfo::SequenceArchive(_) => SoundKind::SequenceArchive,
+            SoundInfo::Bank(_) => SoundKind::Bank,
+            SoundInfo::WaveArchive(_) => SoundKind::WaveArchive,
+            SoundInfo::Stream(_) => SoundKind::Stream,
+        }
+    }
+
+    /// Index into the FAT
+    pub fn file_id(&self) -> usize {
+        match self {
+            SoundInfo::Sequence(info) => info.file_id.get() as usize,
+            SoundInfo::SequenceArchive(info) | SoundInfo::WaveArchive(info) => {
+                info.file_id.get() as usize
+            },
+            SoundInfo::Bank(info) => info.file_id.get() as usize,
+            SoundInfo::Stream(info) => info.file_id.get() as usize,
+        }
+    }
+}
+
+/// A file referenced by the INFO block
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SoundEntry {
+    /// Position in the record of its kind, which is how sequences refer to
+    /// banks and banks to wave archives
+    pub index: usize,
+    /// From the SYMB block, which is optional
+    pub name: Option<String>,
+    pub info: SoundInfo,
+    /// Names of the sequences in a sequence archive
+    pub sequence_names: Vec<Option<String>>,
+}
+
+/// Names read from one SYMB record
+#[derive(Default)]
+struct Names {
+    names: Vec<Option<String>>,
+    sequence_names: Vec<Vec<Option<String>>>,
+}
+
+/// A sound archive with sequences, instrument banks, samples and streams
+pub struct Sdat<'lt> {
+    pub header: NitroHeader,
+    pub entries: Vec<SoundEntry>,
+    pub fat: Vec<SoundFatEntry>,
+    data: &'lt [u8],
+}
+
+/// The whole block of `section`, including the section header that offsets
+/// inside it count from
+fn block<'lt>(data: &'lt [u8], section: &Section) -> &'lt [u8] {
+    &data[section.offset..(section.offset + 8 + section.data.len())]
+}
+
+/// The count-prefixed list of words at `offset` of `block`
+fn read_list(block: &[u8], offset: usize) -> Result<Vec<u32>> {
+    let out_of_bounds = |expected| Error::OutOfBounds {
+        table: Table::Sdat,
+        offset,
+        expected,
+        actual: block.len().saturating_sub(offset),
+    };
+
+    let (count, rest) = block
+        .get(offset..)
+        .and_then(LayoutVerified::<_, U32<LittleEndian>>::new_from_prefix)
+        .ok_or_else(|| out_of_bounds(4))?;
+    let count = count.get() as usize;
+    let (list, _) = LayoutVerified::<_, [U32<LittleEndian>]>::new_slice_from_prefix(rest, count)
+        .ok_or_else(|| out_of_bounds(4 + count * 4))?;
+    Ok(list.iter().map(|value| value.get()).collect())
+}
+
+/// The null-terminated string at `offset`, or `None` for offset 0
+fn read_name(block: &[u8], offset: u32) -> Option<String> {
+    let name = block.get((offset as usize)..).filter(|_| offset != 0)?;
+    let end = name
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(name.len());
+    Some(String::from_utf8_lossy(&name[..end]).into_owned())
+}
+
+impl<'lt> Sdat<'lt> {
+    pub const FAT_MAGIC: [u8; 4] = *b"FAT ";
+    pub const FILE_MAGIC: [u8; 4] = *b"FILE";
+    pub const INFO_MAGIC: [u8; 4] = *b"INFO";
+    pub const MAGIC: [u8; 4] = *b"SDAT";
+    pub const SYMBOL_MAGIC: [u8; 4] = *b"SYMB";
+
+    pub fn read(data: &'lt [u8]) -> Result<Self> {
+        let file = NitroFile::read(data, Self::MAGIC, Table::Sdat)?;
+
+        let info = block(data, file.require_section(Self::INFO_MAGIC, Table::Sdat)?);
+        let info_offsets = Self::record_offsets(info)?;
+        let symbols = match file.section(Self::SYMBOL_MAGIC) {
+            Some(section) => {
+                let symbols = block(data, section);
+                Some((symbols, Self::record_offsets(symbols)?))
+            },
+            None => None,
+        };
+
+        let mut entries = vec![];
+        for kind in SoundKind::ALL {
+            let infos = match info_offsets.offsets[kind.record()].get() {
+                0 => vec![],
+                offset => read_list(info, offset as usize)?,
+            };
+
+            let names = match &symbols {
+                Some((symbols, offsets)) => {
+                    Self::read_names(symbols, offsets.offsets[kind.record()].get(), kind)?
+                },
+                None => Names::default(),
+            };
+
+            for (index, &offset) in infos.iter().enumerate() {
+                // Removed entries keep their index but have no info
+                if offset == 0 {
+                    continue;
+                }
+                entries.push(SoundEntry {
+                    index,
+                    name: names.names.get(index).cloned().flatten(),
+                    info: Self::read_info(info, offset as usize, kind)?,
+                    sequence_names: names.sequence_names.get(index).cloned().unwrap_or_default(),
+                });
+            }
+        }
+
+        let fat_block = block(data, file.require_section(Self::FAT_MAGIC, Table::Sdat)?);
+        let out_of_bounds = |expected| Error::OutOfBounds {
+            table: Table::Sdat,
+            offset: 8,
+            expected,
+            actual: fat_block.len().saturating_sub(8),
+        };
+        let (count, rest) = fat_block
+            .get(8..)
+            .and_then(LayoutVerified::<_, U32<LittleEndian>>::new_from_prefix)
+            .ok_or_else(|| out_of_bounds(4))?;
+        let count = count.get() as usize;
+        let (fat, _) = LayoutVerified::<_, [SoundFatEntry]>::new_slice_from_prefix(rest, count)
+            .ok_or_else(|| out_of_bounds(4 + count * std::mem::size_of::<SoundFatEntry>()))?;
+
+        Ok(Self {
+            header: file.header,
+            entries,
+            fat: fat.to_vec(),
+            data,
+        })
+    }
+
+    fn record_offsets(block: &[u8]) -> Result<RecordOffsets> {
+        block
+            .get(8..)
+            .and_then(LayoutVerified::<_, RecordOffsets>::new_from_prefix)
+            .map(|(offsets, _)| *offsets)
+            .ok_or(Error::OutOfBounds {
+                table: Table::Sdat,
+                offset: 8,
+                expected: std::mem::size_of::<RecordOffsets>(),
+                actual: block.len().saturating_sub(8),
+            })
+    }
+
+    /// Names of the entries of a SYMB record, and for sequence archives also
+    /// the names of the sequences inside
+    fn read_names(symbols: &[u8], offset: u32, kind: SoundKind) -> Result<Names> {
+        if offset == 0 {
+            return Ok(Names::default());
+        }
+
+        let offset = offset as usize;
+        if kind != SoundKind::SequenceArchive {
+            return Ok(Names {
+                names: read_list(symbols, offset)?
+                    .into_iter()
+                    .map(|offset| read_name(symbols, offset))
+                    .collect(),
+                sequence_names: vec![],
+            });
+        }
+
+        let out_of_bounds = |expected| Error::OutOfBounds {
+            table: Table::Sdat,
+            offset,
+            expected,
+            actual: symbols.len().saturating_sub(offset),
+        };
+        let (count, rest) = symbols
+            .get(offset..)
+            .and_then(LayoutVerified::<_, U32<LittleEndian>>::new_from_prefix)
+            .ok_or_else(|| out_of_bounds(4))?;
+        let count = count.get() as usize;
+        let (archives, _) =
+            LayoutVerified::<_, [SequenceArchiveSymbol]>::new_slice_from_prefix(rest, count)
+                .ok_or_else(|| {
+                    out_of_bounds(4 + count * std::mem::size_of::<SequenceArchiveSymbol>())
+                })?;
+
+        let mut names = Names::default();
+        for archive in archives.iter() {
+            names.names.push(read_name(symbols, archive.name.get()));
+            names.sequence_names.push(match archive.sequences.get() {
+                0 => vec![],
+                offset => read_list(symbols, offset as usize)?
+                    .into_iter()
+                    .map(|offset| read_name(symbols, offset))
+                    .collect(),
+            });
+        }
+        Ok(names)
+    }
+
+    fn read_info(info: &[u8], offset: usize, kind: SoundKind) -> Result<SoundInfo> {
+        fn read<T: FromBytes + Copy>(info: &[u8], offset: usize) -> Result<T> {
+            info.get(offset..)
+                .and_then(LayoutVerified::<_, T>::new_from_prefix)
+                .map(|(value, _)| *value)
+                .ok_or(Error::OutOfBounds {
+                    table: Table::Sdat,
+                    offset,
+                    expected: std::mem::size_of::<T>(),
+                    actual: info.len().saturating_sub(offset),
+                })
+        }
+
+        Ok(match kind {
+            SoundKind::Sequence => SoundInfo::Sequence(read(info, offset)?),
+            SoundKind::SequenceArchive => SoundInfo::SequenceArchive(read(info, offset)?),
+            SoundKind::Bank => SoundInfo::Bank(read(info, offset)?),
+            SoundKind::WaveArchive => SoundInfo::WaveArchive(read(info, offset)?),
+            SoundKind::Stream => SoundInfo::Stream(read(info, offset)?),
+        })
+    }
+
+    /// The contents of FAT entry `file_id`
+    pub fn file(&self, file_id: usize) -> Result<&'lt [u8]> {
+        let entry = self.fat.get(file_id).ok_or(Error::InvalidFileId {
+            table: Table::Sdat,
+            file_id,
+            count: self.fat.len(),
+        })?;
+        let (offset, size) = (entry.offset.get() as usize, entry.size.get() as usize);
+        self.data
+            .get(offset..(offset + size))
+            .ok_or(Error::OutOfBounds {
+                table: Table::Sdat,
+                offset,
+                expected: size,
+                actual: self.data.len().saturating_sub(offset),
+            })
+    }
+
+    /// Where an entry is extracted to, e.g. `/sequences/SEQ_TITLE.sseq`.
+    ///
+    /// Entries without a name are named after their index, e.g.
+    /// `/streams/0003.strm`. The path is not unique, see [Sdat::paths].
+    pub fn path(entry: &SoundEntry) -> String {
+        Self::path_with_suffix(entry, "")
+    }
+
+    fn path_with_suffix(entry: &SoundEntry, suffix: &str) -> String {
+        let kind = entry.info.kind();
+        let name = match &entry.name {
+            Some(name) if !name.is_empty() => name.replace('/', "_"),
+            _ => format!("{:04}", entry.index),
+        };
+        format!(
+            "/{}/{}{}.{}",
+            kind.directory(),
+            name,
+            suffix,
+            kind.extension()
+        )
+    }
+
+    /// [Sdat::path] of every entry, in the order of [Sdat::entries]. Paths
+    /// that are already taken, also when ignoring case, get the index of the
+    /// entry appended, e.g. `/sequences/SEQ_TITLE_0003.sseq`.
+    pub fn paths(&self) -> Vec<String> {
+        let mut taken = HashSet::new();
+        self.entries
+            .iter()
+            .map(|entry| {
+                let mut path = Self::path(entry);
+                let mut copy = 0;
+                while !taken.insert(path.to_lowercase()) {
+                    let suffix = match copy {
+                        0 => format!("_{:04}", entry.index),
+                        copy => format!("_{:04}_{}", entry.index, copy),
+                    };
+                    path = Self::path_with_suffix(entry, &suffix);
+                    copy += 1;
+                }
+                path
+            })
+            .collect()
+    }
+
+    /// The entry of `kind` at `index`
+    pub fn entry(&self, kind: SoundKind, index: usize) -> Option<&SoundEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.info.kind() == kind && entry.index == index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nitro_file::tests::build;
+
+    /// Contents of a SYMB or INFO section, offsets count from the section
+    /// header
+    struct Block(Vec<u8>);
+
+    impl Block {
+        fn new() -> Self {
+            Self(RecordOffsets::new_zeroed().as_bytes().to_vec())
+        }
+
+        /// Appends `bytes` and returns their offset
+        fn push(&mut self, bytes: &[u8]) -> u32 {
+            let offset = self.0.len() + 8;
+            self.0.extend_from_slice(bytes);
+            offset as u32
+        }
+
+        fn list(&mut self, values: &[u32]) -> u32 {
+            let mut bytes = (values.len() as u32).to_le_bytes().to_vec();
+            bytes.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+            self.push(&bytes)
+        }
+
+        fn name(&mut self, name: &str) -> u32 {
+            self.push(&[name.as_bytes(), &[0]].concat())
+        }
+
+        fn record(&mut self, kind: SoundKind, offset: u32) {
+            let start = kind.record() * 4;
+            self.0[start..(start + 4)].copy_from_slice(&offset.to_le_bytes());
+        }
+    }
+
+    /// Two sequences with a removed one in between, a sequence archive, a
+    /// stream and a FAT entry past the end of the file
+    fn sdat(symbols: bool) -> Vec<u8> {
+        let mut info = Block::new();
+        let sequences = [0, 1].map(|file_id| {
+            let mut sequence = SequenceInfo::new_zeroed();
+            sequence.file_id = file_id.into();
+            info.push(sequence.as_bytes())
+        });
+        let list = info.list(&[sequences[0], 0, sequences[1]]);
+        info.record(SoundKind::Sequence, list);
+        for (kind, file_id) in [(SoundKind::SequenceArchive, 2), (SoundKind::Stream, 3)] {
+            let entry = info.push(&[file_id, 0, 0, 0, 0, 0, 0, 0]);
+            let list = info.list(&[entry]);
+            info.record(kind, list);
+        }
+
+        let mut symb = Block::new();
+        let names = [symb.name("SEQ_A"), symb.name("UNUSED"), symb.name("SEQ/B")];
+        let list = symb.list(&names);
+        symb.record(SoundKind::Sequence, list);
+        let sequence_names = [symb.name("SSAR_SEQ"), 0];
+        let sequence_names = symb.list(&sequence_names);
+        let archive = SequenceArchiveSymbol {
+            name: symb.name("SSAR").into(),
+            sequences: sequence_names.into(),
+        };
+        let list = symb.push(&[&1u32.to_le_bytes(), archive.as_bytes()].concat());
+        symb.record(SoundKind::SequenceArchive, list);
+
+        let files: [&[u8]; 4] = [b"seq0", b"seq1", b"ssar", b"strm"];
+        // After the Nitro header, INFO, FAT and the header of FILE
+        let mut file_offset = 0x10 + 8 + info.0.len() + 8 + 4 + 5 * 16 + 8;
+        if symbols {
+            file_offset += 8 + symb.0.len();
+        }
+        let mut fat = 5u32.to_le_bytes().to_vec();
+        for (index, file) in files.iter().enumerate() {
+            let mut entry = SoundFatEntry::new_zeroed();
+            entry.offset = ((file_offset + index * 4) as u32).into();
+            entry.size = (file.len() as u32).into();
+            fat.extend_from_slice(entry.as_bytes());
+        }
+        let mut entry = SoundFatEntry::new_zeroed();
+        entry.offset = ((file_offset + 12) as u32).into();
+        entry.size = 8.into();
+        fat.extend_from_slice(entry.as_bytes());
+        let file = files.concat();
+
+        let mut sections = vec![
+            (Sdat::INFO_MAGIC, info.0.as_slice()),
+            (Sdat::FAT_MAGIC, fat.as_slice()),
+            (Sdat::FILE_MAGIC, file.as_slice()),
+        ];
+        if symbols {
+            sections.insert(0, (Sdat::SYMBOL_MAGIC, symb.0.as_slice()));
+        }
+        build(Sdat::MAGIC, &sections)
+    }
+
+    #[test]
+    fn read() {
+        let data = sdat(true);
+        let sdat = Sdat::read(&data).unwrap();
+
+        let entries = sdat
+            .entries
+            .iter()
+            .map(|entry| (entry.info.kind(), entry.index, entry.name.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                (SoundKind::Sequence, 0, Some("SEQ_A")),
+                (SoundKind::Sequence, 2, Some("SEQ/B")),
+                (SoundKind::SequenceArchive, 0, Some("SSAR")),
+                (SoundKind::Stream, 0, None),
+            ]
+        );
+        assert_eq!(
+            sdat.entry(SoundKind::SequenceArchive, 0)
+                .unwrap()
+                .sequence_names,
+            [Some("SSAR_SEQ".to_string()), None]
+        );
+        assert!(sdat.entry(SoundKind::Sequence, 1).is_none());
+        assert_eq!(
+            sdat.paths(),
+            [
+                "/sequences/SEQ_A.sseq",
+                "/sequences/SEQ_B.sseq",
+                "/sequence_archives/SSAR.ssar",
+                "/streams/0000.strm",
+            ]
+        );
+
+        let files = sdat
+            .entries
+            .iter()
+            .map(|entry| sdat.file(entry.info.file_id()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            [b"seq0", b"seq1", b"ssar", b"strm"].map(|file| &file[..])
+        );
+    }
+
+    #[test]
+    fn without_symbols() {
+        let data = sdat(false);
+        let sdat = Sdat::read(&data).unwrap();
+
+        assert!(sdat.entries.iter().all(|entry| entry.name.is_none()));
+        assert!(sdat.entries[2].sequence_names.is_empty());
+        assert_eq!(
+            sdat.paths(),
+            [
+                "/sequences/0000.sseq",
+                "/sequences/0002.sseq",
+                "/sequence_archives/0000.ssar",
+                "/streams/0000.strm",
+            ]
+        );
+        assert_eq!(sdat.file(3).unwrap(), b"strm");
+    }
+
+    #[test]
+    fn file_bounds() {
+        let data = sdat(true);
+        let sdat = Sdat::read(&data).unwrap();
+
+        assert!(matches!(
+            sdat.file(4),
+            Err(Error::OutOfBounds {
+                expected: 8,
+                actual: 4,
+                ..
+            })
+        ));
+        assert!(matches!(
+            sdat.file(5),
+            Err(Error::InvalidFileId {
+                file_id: 5,
+                count: 5,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn colliding_paths() {
+        let data = sdat(true);
+        let mut sdat = Sdat::read(&data).unwrap();
+        sdat.entries[1].name = Some("seq_a".to_string());
+        sdat.entries.push(sdat.entries[0].clone());
+        sdat.entries[0].name = Some("0002".to_string());
+        sdat.entries[4].name = None;
+        sdat.entries[4].index = 2;
+
+        assert_eq!(
+            sdat.paths(),
+            [
+                "/sequences/0002.sseq",
+                "/sequences/seq_a.sseq",
+                "/sequence_archives/SSAR.ssar",
+                "/streams/0000.strm",
+                "/sequences/0002_0002.sseq",
+            ]
+        );
+
+        sdat.entries.push(sdat.entries[1].clone());
+        sdat.entries.push(sdat.entries[1].clone());
+        assert_eq!(
+            sdat.paths()[5..],
+            ["/sequences/seq_a_0002.sseq", "/sequences/seq_a_0002_1.sseq"]
+        );
+    }
+}
@@ -0,0 +1,902 @@
+use crate::{
+    byte_types::int::U32,
+    error::{Error, Result, Table},
+    nitro_file::NitroFile,
+    sound::midi::{MidiEvent, MidiTrack, Smf},
+};
+use byteorder::LittleEndian;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use zerocopy::LayoutVerified;
+
+/// Commands 0xB0 to 0xBD, which change a variable or compare it to a value
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum VariableOperation {
+    Set,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    /// Shifts left, or right for negative values
+    Shift,
+    /// Sets the variable to a random number between 0 and the value
+    Random,
+    Unknown,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+    LessOrEqual,
+    Less,
+    NotEqual,
+}
+
+/// Commands 0xC0 to 0xD6, which take a single byte
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Parameter {
+    Pan,
+    Volume,
+    MasterVolume,
+    /// Signed
+    Transpose,
+    /// Signed
+    PitchBend,
+    /// In semitones
+    PitchBendRange,
+    Priority,
+    /// 0 to play notes over each other, 1 to wait for each note to end
+    NoteWait,
+    /// 1 to hold notes until the next one starts
+    Tie,
+    PortamentoKey,
+    ModulationDepth,
+    ModulationSpeed,
+    ModulationType,
+    ModulationRange,
+    Portamento,
+    PortamentoTime,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    /// Repeats up to the loop end as often as the value says, 0 for endless
+    LoopStart,
+    Expression,
+    PrintVariable,
+}
+
+impl Parameter {
+    const ALL: [Parameter; 23] = [
+        Parameter::Pan,
+        Parameter::Volume,
+        Parameter::MasterVolume,
+        Parameter::Transpose,
+        Parameter::PitchBend,
+        Parameter::PitchBendRange,
+        Parameter::Priority,
+        Parameter::NoteWait,
+        Parameter::Tie,
+        Parameter::PortamentoKey,
+        Parameter::ModulationDepth,
+        Parameter::ModulationSpeed,
+        Parameter::ModulationType,
+        Parameter::ModulationRange,
+        Parameter::Portamento,
+        Parameter::PortamentoTime,
+        Parameter::Attack,
+        Parameter::Decay,
+        Parameter::Sustain,
+        Parameter::Release,
+        Parameter::LoopStart,
+        Parameter::Expression,
+        Parameter::PrintVariable,
+    ];
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    /// Plays `key` for `duration` ticks
+    Note {
+        key: u8,
+        velocity: u8,
+        duration: u32,
+    },
+    Wait(u32),
+    /// Bits 7 and up select the bank
+    Program(u32),
+    /// Starts another track at `offset`
+    OpenTrack {
+        track: u8,
+        offset: u32,
+    },
+    Jump(u32),
+    Call(u32),
+    Variable {
+        operation: VariableOperation,
+        variable: u8,
+        value: i16,
+    },
+    Parameter {
+        parameter: Parameter,
+        value: u8,
+    },
+    ModulationDelay(i16),
+    /// In quarter notes per minute
+    Tempo(u16),
+    SweepPitch(i16),
+    LoopEnd,
+    Return,
+    /// Bit mask of the tracks the sequence uses
+    AllocateTracks(u16),
+    End,
+}
+
+impl Command {
+    /// Replaces the last argument, as the random and variable prefixes do
+    pub fn with_last_argument(self, value: i32) -> Self {
+        match self {
+            Command::Note { key, velocity, .. } => Command::Note {
+                key,
+                velocity,
+                duration: value.max(0) as u32,
+            },
+            Command::Wait(_) => Command::Wait(value.max(0) as u32),
+            Command::Program(_) => Command::Program(value.max(0) as u32),
+            Command::OpenTrack { track, .. } => Command::OpenTrack {
+                track,
+                offset: value as u32,
+            },
+            Command::Jump(_) => Command::Jump(value as u32),
+            Command::Call(_) => Command::Call(value as u32),
+            Command::Variable {
+                operation,
+                variable,
+                ..
+            } => Command::Variable {
+                operation,
+                variable,
+                value: value as i16,
+            },
+            Command::Parameter { parameter, .. } => Command::Parameter {
+                parameter,
+                value: value as u8,
+            },
+            Command::ModulationDelay(_) => Command::ModulationDelay(value as i16),
+            Command::Tempo(_) => Command::Tempo(value as u16),
+            Command::SweepPitch(_) => Command::SweepPitch(value as i16),
+            Command::AllocateTracks(_) => Command::AllocateTracks(value as u16),
+            command @ (Command::LoopEnd | Command::Return | Command::End) => command,
+        }
+    }
+}
+
+/// Where the last argument of a command comes from instead
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Argument {
+    /// 0xA0 prefix, a random number between `min` and `max`
+    Random { min: i16, max: i16 },
+    /// 0xA1 prefix, the value of a variable
+    Variable(u8),
+}
+
+/// A command with its prefixes
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Instruction {
+    /// 0xA2 prefix, only executed if the last comparison was true
+    pub conditional: bool,
+    pub command: Command,
+    pub argument: Option<Argument>,
+}
+
+#[derive(Copy, Clone)]
+enum ArgumentType {
+    U8,
+    I16,
+    U16,
+    U24,
+    VariableLength,
+}
+
+struct Reader<'lt> {
+    data: &'lt [u8],
+    offset: usize,
+    /// The prefix that replaces the last argument
+    prefix: Option<u8>,
+    argument: Option<Argument>,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, count: usize) -> Result<&[u8]> {
+        let bytes =
+            self.data
+                .get(self.offset..(self.offset + count))
+                .ok_or(Error::OutOfBounds {
+                    table: Table::Sseq,
+                    offset: self.offset,
+                    expected: count,
+                    actual: self.data.len().saturating_sub(self.offset),
+                })?;
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn read(&mut self, argument: ArgumentType) -> Result<i32> {
+        Ok(match argument {
+            ArgumentType::U8 => self.u8()? as i32,
+            ArgumentType::I16 => {
+                let bytes = self.bytes(2)?;
+                i16::from_le_bytes([bytes[0], bytes[1]]) as i32
+            },
+            ArgumentType::U16 => {
+                let bytes = self.bytes(2)?;
+                u16::from_le_bytes([bytes[0], bytes[1]]) as i32
+            },
+            ArgumentType::U24 => {
+                let bytes = self.bytes(3)?;
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) as i32
+            },
+            ArgumentType::VariableLength => {
+                let mut value = 0u32;
+                for _ in 0..4 {
+                    let byte = self.u8()?;
+                    value = (value << 7) | (byte & 0x7F) as u32;
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                }
+                value as i32
+            },
+        })
+    }
+
+    /// Reads the last argument of a command, or what a prefix replaced it
+    /// with. Replaced arguments read as 0.
+    fn last(&mut self, argument: ArgumentType) -> Result<i32> {
+        match self.prefix {
+            Some(0xA0) => {
+                let min = self.read(ArgumentType::I16)? as i16;
+                let max = self.read(ArgumentType::I16)? as i16;
+                self.argument = Some(Argument::Random { min, max });
+                Ok(0)
+            },
+            Some(0xA1) => {
+                self.argument = Some(Argument::Variable(self.u8()?));
+                Ok(0)
+            },
+            _ => self.read(argument),
+        }
+    }
+}
+
+/// Ticks per quarter note
+pub const TICKS_PER_QUARTER: u16 = 48;
+
+/// A music sequence, the command stream of an SSEQ file
+#[derive(Clone, Debug)]
+pub struct Sseq {
+    /// Offsets in commands are relative to the start of this
+    pub data: Vec<u8>,
+}
+
+impl Sseq {
+    pub const DATA_MAGIC: [u8; 4] = *b"DATA";
+    pub const MAGIC: [u8; 4] = *b"SSEQ";
+    /// Stops tracks that run for longer without ending, e.g. because of
+    /// loops that never end
+    pub const MAX_COMMANDS: usize = 1 << 20;
+    /// The longest time a MIDI file can hold in a single delta, tracks stop
+    /// once they reach it
+    pub const MAX_TICKS: u32 = 0x0FFF_FFFF;
+
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let file = NitroFile::read(data, Self::MAGIC, Table::Sseq)?;
+        let section = file.require_section(Self::DATA_MAGIC, Table::Sseq)?;
+
+        // Relative to the start of the file
+        let (offset, _) = LayoutVerified::<_, U32<LittleEndian>>::new_from_prefix(section.data)
+            .ok_or(Error::OutOfBounds {
+                table: Table::Sseq,
+                offset: section.offset + 8,
+                expected: 4,
+                actual: section.data.len(),
+            })?;
+        let start = offset.get() as usize;
+        let end = section.offset + 8 + section.data.len();
+        let sequence = data.get(start..end).ok_or(Error::OutOfBounds {
+            table: Table::Sseq,
+            offset: start,
+            expected: end.saturating_sub(start),
+            actual: data.len().saturating_sub(start),
+        })?;
+
+        Ok(Self {
+            data: sequence.to_vec(),
+        })
+    }
+
+    /// Reads the instruction at `offset`, returning it and the offset of the
+    /// next one
+    pub fn instruction(&self, offset: usize) -> Result<(Instruction, usize)> {
+        let mut reader = Reader {
+            data: &self.data,
+            offset,
+            prefix: None,
+            argument: None,
+        };
+
+        let mut conditional = false;
+        let mut code = reader.u8()?;
+        loop {
+            match code {
+                0xA0 | 0xA1 => reader.prefix = Some(code),
+                0xA2 => conditional = true,
+                _ => break,
+            }
+            code = reader.u8()?;
+        }
+
+        use ArgumentType::*;
+        let command = match code {
+            0x00..=0x7F => Command::Note {
+                key: code,
+                velocity: reader.read(U8)? as u8,
+                duration: reader.last(VariableLength)? as u32,
+            },
+            0x80 => Command::Wait(reader.last(VariableLength)? as u32),
+            0x81 => Command::Program(reader.last(VariableLength)? as u32),
+            0x93 => Command::OpenTrack {
+                track: reader.read(U8)? as u8,
+                offset: reader.last(U24)? as u32,
+            },
+            0x94 => Command::Jump(reader.last(U24)? as u32),
+            0x95 => Command::Call(reader.last(U24)? as u32),
+            0xB0..=0xBD => {
+                let operation = [
+                    VariableOperation::Set,
+                    VariableOperation::Add,
+                    VariableOperation::Subtract,
+                    VariableOperation::Multiply,
+                    VariableOperation::Divide,
+                    VariableOperation::Shift,
+                    VariableOperation::Random,
+                    VariableOperation::Unknown,
+                    VariableOperation::Equal,
+                    VariableOperation::GreaterOrEqual,
+                    VariableOperation::Greater,
+                    VariableOperation::LessOrEqual,
+                    VariableOperation::Less,
+                    VariableOperation::NotEqual,
+                ][(code - 0xB0) as usize];
+                Command::Variable {
+                    operation,
+                    variable: reader.read(U8)? as u8,
+                    value: reader.last(I16)? as i16,
+                }
+            },
+            0xC0..=0xD6 => Command::Parameter {
+                parameter: Parameter::ALL[(code - 0xC0) as usize],
+                value: reader.last(U8)? as u8,
+            },
+            0xE0 => Command::ModulationDelay(reader.last(I16)? as i16),
+            0xE1 => Command::Tempo(reader.last(U16)? as u16),
+            0xE3 => Command::SweepPitch(reader.last(I16)? as i16),
+            0xFC => Command::LoopEnd,
+            0xFD => Command::Return,
+            0xFE => Command::AllocateTracks(reader.last(U16)? as u16),
+            0xFF => Command::End,
+            code => {
+                return Err(Error::UnsupportedFormat {
+                    table: Table::Sseq,
+                    value: code as u32,
+                })
+            },
+        };
+
+        Ok((
+            Instruction {
+                conditional,
+                command,
+                argument: reader.argument,
+            },
+            reader.offset,
+        ))
+    }
+
+    /// Converts the sequence to a MIDI file with one track per sequence track,
+    /// on the MIDI channel of the same number.
+    ///
+    /// Endless loops, both loop commands with a count of 0 and jumps
+    /// backwards, are played `loop_count` times. Random numbers come from the
+    /// same generator as on the console, with a fixed seed. Tracks run one
+    /// after another, so variables shared between tracks only see the
+    /// changes of earlier tracks.
+    pub fn to_smf(&self, loop_count: u32) -> Result<Smf> {
+        let mut conductor = MidiTrack::default();
+        conductor.push(0, MidiEvent::Tempo(60_000_000 / 120));
+
+        let mut player = Player {
+            sseq: self,
+            loop_count: loop_count.max(1),
+            variables: [-1; 32],
+            seed: 0x12345678,
+            conductor,
+        };
+
+        let mut tracks = vec![(0u8, 0usize)];
+        let mut midi_tracks = vec![];
+        let mut index = 0;
+        while let Some(&(track, offset)) = tracks.get(index) {
+            let (midi_track, opened) = player.play(track, offset)?;
+            midi_tracks.push(midi_track);
+            for opened in opened {
+                if !tracks.iter().any(|&(track, _)| track == opened.0) {
+                    tracks.push(opened);
+                }
+            }
+            index += 1;
+        }
+
+        let mut all_tracks = vec![player.conductor];
+        all_tracks.extend(midi_tracks);
+        Ok(Smf {
+            division: TICKS_PER_QUARTER,
+            tracks: all_tracks,
+        })
+    }
+}
+
+/// State shared by all tracks of a sequence
+struct Player<'lt> {
+    sseq: &'lt Sseq,
+    loop_count: u32,
+    variables: [i16; 32],
+    seed: u32,
+    /// Collects the tempo changes
+    conductor: MidiTrack,
+}
+
+impl Player<'_> {
+    /// The random number generator of the sound driver
+    fn random(&mut self) -> u16 {
+        self.seed = self.seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        (self.seed >> 16) as u16
+    }
+
+    fn variable(&mut self, index: u8) -> &mut i16 {
+        &mut self.variables[index as usize % 32]
+    }
+
+    /// Runs the track starting at `offset`, returning its events and the
+    /// tracks it opened
+    fn play(&mut self, track: u8, offset: usize) -> Result<(MidiTrack, Vec<(u8, usize)>)> {
+        let mut midi = MidiTrack {
+            channel: track & 0xF,
+            events: vec![],
+        };
+        midi.push(0, MidiEvent::TrackName(format!("Track {}", track)));
+
+        let mut opened = vec![];
+        let mut offset = offset;
+        let mut time = 0u32;
+        let mut call_stack = vec![];
+        // Start of the loop and how many more times it plays
+        let mut loop_stack: Vec<(usize, u32)> = vec![];
+        let mut jumps = HashMap::<usize, u32>::new();
+        let mut condition = true;
+        let mut note_wait = true;
+        let mut tie = false;
+        let mut tied_note = None;
+        let mut transpose = 0i32;
+
+        for _ in 0..Sseq::MAX_COMMANDS {
+            if time >= Sseq::MAX_TICKS {
+                break;
+            }
+            let (instruction, next) = self.sseq.instruction(offset)?;
+            let command_offset = offset;
+            offset = next;
+
+            if instruction.conditional && !condition {
+                continue;
+            }
+            let command = match instruction.argument {
+                Some(Argument::Random { min, max }) => {
+                    // Up to 0x10000, which overflows i32 once multiplied
+                    let range = (max as i64 - min as i64 + 1).max(1);
+                    let value = min as i64 + ((self.random() as i64 * range) >> 16);
+                    instruction.command.with_last_argument(value as i32)
+                },
+                Some(Argument::Variable(index)) => {
+                    let value = *self.variable(index) as i32;
+                    instruction.command.with_last_argument(value)
+                },
+                None => instruction.command,
+            };
+
+            match command {
+                Command::Note {
+                    key,
+                    velocity,
+                    duration,
+                } => {
+                    let key = (key as i32 + transpose).clamp(0, 127) as u8;
+                    if let Some(tied_key) = tied_note.take() {
+                        midi.push(time, MidiEvent::NoteOff { key: tied_key });
+                    }
+                    midi.push(time, MidiEvent::NoteOn { key, velocity });
+                    if tie {
+                        tied_note = Some(key);
+                    } else {
+                        // A note off on the same tick would sort before the
+                        // note on, so notes last at least one tick
+                        let end = time.saturating_add(duration.max(1)).min(Sseq::MAX_TICKS);
+                        midi.push(end, MidiEvent::NoteOff { key });
+                    }
+                    if note_wait {
+                        time = time.saturating_add(duration);
+                    }
+                },
+                Command::Wait(duration) => time = time.saturating_add(duration),
+                Command::Program(program) => {
+                    midi.push(
+                        time,
+                        MidiEvent::Controller {
+                            controller: 0,
+                            value: (program >> 7).min(0x7F) as u8,
+                        },
+                    );
+                    midi.push(time, MidiEvent::Program((program & 0x7F) as u8));
+                },
+                Command::OpenTrack { track, offset } => opened.push((track, offset as usize)),
+                Command::Jump(target) => {
+                    let target = target as usize;
+                    if target <= command_offset {
+                        let count = jumps.entry(command_offset).or_default();
+                        *count += 1;
+                        if *count >= self.loop_count {
+                            break;
+                        }
+                    }
+                    offset = target;
+                },
+                Command::Call(target) => {
+                    call_stack.push(offset);
+                    offset = target as usize;
+                },
+                Command::Return => match call_stack.pop() {
+                    Some(back) => offset = back,
+                    None => break,
+                },
+                Command::Variable {
+                    operation,
+                    variable,
+                    value,
+                } => {
+                    let random = match operation {
+                        VariableOperation::Random => self.random() as i64,
+                        _ => 0,
+                    };
+                    let current = *self.variable(variable);
+                    let result = match operation {
+                        VariableOperation::Set => Some(value),
+                        VariableOperation::Add => Some(current.wrapping_add(value)),
+                        VariableOperation::Subtract => Some(current.wrapping_sub(value)),
+                        VariableOperation::Multiply => Some(current.wrapping_mul(value)),
+                        VariableOperation::Divide if value != 0 => {
+                            Some(current.wrapping_div(value))
+                        },
+                        VariableOperation::Shift if value >= 0 => {
+                            Some(current.wrapping_shl(value as u32))
+                        },
+                        VariableOperation::Shift => {
+                            Some(current.wrapping_shr(value.unsigned_abs() as u32))
+                        },
+                        VariableOperation::Random => {
+                            let range = value as i64 + value.signum() as i64;
+                            Some(((random * range) >> 16) as i16)
+                        },
+                        _ => None,
+                    };
+                    match result {
+                        Some(result) => *self.variable(variable) = result,
+                        None => {
+                            let compared = match operation {
+                                VariableOperation::Equal => Some(current == value),
+                                VariableOperation::GreaterOrEqual => Some(current >= value),
+                                VariableOperation::Greater => Some(current > value),
+                                VariableOperation::LessOrEqual => Some(current <= value),
+                                VariableOperation::Less => Some(current < value),
+                                VariableOperation::NotEqual => Some(current != value),
+                                _ => None,
+                            };
+                            if let Some(compared) = compared {
+                                condition = compared;
+                            }
+                        },
+                    }
+                },
+                Command::Parameter { parameter, value } => {
+                    let controller = |controller| MidiEvent::Controller {
+                        controller,
+                        value: value.min(0x7F),
+                    };
+                    match parameter {
+                        Parameter::Pan => midi.push(time, controller(10)),
+                        Parameter::Volume => midi.push(time, controller(7)),
+                        Parameter::Expression => midi.push(time, controller(11)),
+                        Parameter::ModulationDepth => midi.push(time, controller(1)),
+                        Parameter::PortamentoTime => midi.push(time, controller(5)),
+                        Parameter::PortamentoKey => midi.push(time, controller(84)),
+                        Parameter::Portamento => midi.push(
+                            time,
+                            MidiEvent::Controller {
+                                controller: 65,
+                                value: if value != 0 { 0x7F } else { 0 },
+                            },
+                        ),
+                        Parameter::Attack => midi.push(time, controller(73)),
+                        Parameter::Release => midi.push(time, controller(72)),
+                        Parameter::Transpose => transpose = value as i8 as i32,
+                        Parameter::PitchBend => {
+                            midi.push(time, MidiEvent::PitchBend((value as i8 as i16) * 64))
+                        },
+                        Parameter::PitchBendRange => {
+                            // Registered parameter 0
+                            for (controller, value) in [(101, 0), (100, 0), (6, value), (38, 0)] {
+                                midi.push(
+                                    time,
+                                    MidiEvent::Controller {
+                                        controller,
+                                        value: value.min(0x7F),
+                                    },
+                                );
+                            }
+                        },
+                        Parameter::NoteWait => note_wait = value != 0,
+                        Parameter::Tie => {
+                            tie = value != 0;
+                            if let Some(key) = tied_note.take() {
+                                midi.push(time, MidiEvent::NoteOff { key });
+                            }
+                        },
+                        Parameter::LoopStart => {
+                            let count = if value == 0 {
+                                self.loop_count
+                            } else {
+                                value as u32
+                            };
+                            loop_stack.push((offset, count));
+                        },
+                        // Nothing in MIDI matches the rest
+                        _ => {},
+                    }
+                },
+                Command::LoopEnd => {
+                    if let Some((start, count)) = loop_stack.last_mut() {
+                        *count -= 1;
+                        if *count > 0 {
+                            offset = *start;
+                        } else {
+                            loop_stack.pop();
+                        }
+                    }
+                },
+                Command::Tempo(tempo) => {
+                    self.conductor
+                        .push(time, MidiEvent::Tempo(60_000_000 / tempo.max(1) as u32));
+                },
+                Command::ModulationDelay(_)
+                | Command::SweepPitch(_)
+                | Command::AllocateTracks(_) => {},
+                Command::End => break,
+            }
+        }
+
+        if let Some(key) = tied_note {
+            midi.push(time.min(Sseq::MAX_TICKS), MidiEvent::NoteOff { key });
+        }
+        Ok((midi, opened))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(data: &[u8], loop_count: u32) -> Smf {
+        Sseq {
+            data: data.to_vec(),
+        }
+        .to_smf(loop_count)
+        .unwrap()
+    }
+
+    /// (time, key) of every note on in a track of the SMF
+    fn notes(smf: &Smf, track: usize) -> Vec<(u32, u8)> {
+        let mut notes = smf.tracks[track]
+            .events
+            .iter()
+            .filter_map(|(time, event)| match event {
+                MidiEvent::NoteOn { key, .. } => Some((*time, *key)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        notes.sort();
+        notes
+    }
+
+    #[test]
+    fn instructions() {
+        let sseq = Sseq {
+            data: vec![
+                0xA2, 0xA0, 0x3C, 0x40, 0xF6, 0xFF, 0x0A, 0x00, 0x94, 0x01, 0x02, 0x03,
+            ],
+        };
+        assert_eq!(
+            sseq.instruction(0).unwrap(),
+            (
+                Instruction {
+                    conditional: true,
+                    command: Command::Note {
+                        key: 0x3C,
+                        velocity: 0x40,
+                        duration: 0,
+                    },
+                    argument: Some(Argument::Random { min: -10, max: 10 }),
+                },
+                8
+            )
+        );
+        assert_eq!(
+            sseq.instruction(8).unwrap().0.command,
+            Command::Jump(0x030201)
+        );
+        // A note whose duration is cut off
+        assert!(sseq.instruction(10).is_err());
+    }
+
+    #[test]
+    fn loops() {
+        // Loop twice around a note, then loop endlessly around another
+        let smf = convert(
+            &[
+                0xD4, 2, 0x3C, 100, 10, 0xFC, 0xD4, 0, 0x3E, 100, 20, 0xFC, 0xFF,
+            ],
+            3,
+        );
+        assert_eq!(
+            notes(&smf, 1),
+            [(0, 0x3C), (10, 0x3C), (20, 0x3E), (40, 0x3E), (60, 0x3E)]
+        );
+    }
+
+    #[test]
+    fn backward_jump() {
+        let data = [0x3C, 100, 10, 0x94, 0, 0, 0];
+        assert_eq!(notes(&convert(&data, 1), 1), [(0, 0x3C)]);
+        assert_eq!(
+            notes(&convert(&data, 3), 1),
+            [(0, 0x3C), (10, 0x3C), (20, 0x3C)]
+        );
+    }
+
+    #[test]
+    fn call_and_open_track() {
+        let smf = convert(
+            &[
+                0x93, 1, 17, 0, 0, // open track 1 at 17
+                0x95, 13, 0, 0, // call 13
+                0x3C, 100, 10,   // note
+                0xFF, // end
+                0x40, 100, 5, 0xFD, // subroutine at 13
+                0x30, 100, 7, 0xFF, // track 1 at 17
+            ],
+            1,
+        );
+        assert_eq!(smf.tracks.len(), 3);
+        assert_eq!(notes(&smf, 1), [(0, 0x40), (5, 0x3C)]);
+        assert_eq!(notes(&smf, 2), [(0, 0x30)]);
+        assert_eq!(smf.tracks[2].channel, 1);
+    }
+
+    #[test]
+    fn variables_and_conditions() {
+        let smf = convert(
+            &[
+                0xB0, 3, 7, 0, // var3 = 7
+                0xB1, 3, 2, 0, // var3 += 2
+                0xA1, 0x80, 3, // wait var3
+                0xB8, 3, 9, 0, // var3 == 9
+                0xA2, 0x3C, 100, 1, // played
+                0xBA, 3, 9, 0, // var3 > 9
+                0xA2, 0x3E, 100, 1, // skipped
+                0xFF,
+            ],
+            1,
+        );
+        assert_eq!(notes(&smf, 1), [(9, 0x3C)]);
+    }
+
+    #[test]
+    fn random_argument() {
+        let data = [
+            0xA0, 0x3C, 100, 10, 0, 20, 0, // note with a duration of 10 to 20
+            0xA0, 0x80, 0x00, 0x80, 0xFF, 0x7F, // wait anywhere in the i16 range
+            0x3E, 100, 1, 0xFF,
+        ];
+        let first = notes(&convert(&data, 1), 1);
+        assert_eq!(first, notes(&convert(&data, 1), 1));
+
+        let end = convert(&data, 1).tracks[1]
+            .events
+            .iter()
+            .find_map(|(time, event)| match event {
+                MidiEvent::NoteOff { key: 0x3C } => Some(*time),
+                _ => None,
+            })
+            .unwrap();
+        assert!((10..=20).contains(&end));
+    }
+
+    #[test]
+    fn arithmetic_limits() {
+        // Shift right by i16::MIN
+        convert(&[0xB5, 0, 0x00, 0x80, 0xFF], 1);
+        // Random number up to i16::MIN
+        convert(&[0xB6, 0, 0x00, 0x80, 0xFF], 1);
+
+        // Waits that add up to more than fits in a u32
+        let mut data = vec![];
+        for _ in 0..17 {
+            data.extend_from_slice(&[0x80, 0xFF, 0xFF, 0xFF, 0x7F]);
+        }
+        data.extend_from_slice(&[0x3C, 100, 1, 0xFF]);
+        let smf = convert(&data, 1);
+        assert!(smf.tracks[1]
+            .events
+            .iter()
+            .all(|(time, _)| *time <= Sseq::MAX_TICKS));
+        let mut bytes = vec![];
+        smf.write_smf(&mut bytes).unwrap();
+    }
+
+    #[test]
+    fn zero_duration() {
+        let smf = convert(&[0x3C, 100, 0, 0x3E, 100, 2, 0xFF], 1);
+        assert_eq!(notes(&smf, 1), [(0, 0x3C), (0, 0x3E)]);
+
+        let mut bytes = vec![];
+        smf.write_smf(&mut bytes).unwrap();
+        assert!(bytes.ends_with(&[
+            0, 0x90, 0x3C, 100, // both notes start
+            0, 0x90, 0x3E, 100, //
+            1, 0x80, 0x3C, 0, // the first one a tick later
+            1, 0x80, 0x3E, 0, //
+            0, 0xFF, 0x2F, 0,
+        ]));
+    }
+
+    #[test]
+    fn smf_bytes() {
+        let smf = convert(&[0xE1, 150, 0, 0x3C, 100, 0x30, 0xFF], 1);
+        let mut bytes = vec![];
+        smf.write_smf(&mut bytes).unwrap();
+
+        let mut expected = vec![];
+        expected.extend_from_slice(b"MThd\0\0\0\x06\0\x01\0\x02\0\x30");
+        // Conductor track with the default tempo and 150 BPM
+        expected.extend_from_slice(b"MTrk\0\0\0\x12");
+        expected.extend_from_slice(&[0, 0xFF, 0x51, 3, 0x07, 0xA1, 0x20]);
+        expected.extend_from_slice(&[0, 0xFF, 0x51, 3, 0x06, 0x1A, 0x80]);
+        expected.extend_from_slice(&[0, 0xFF, 0x2F, 0]);
+        expected.extend_from_slice(b"MTrk\0\0\0\x17");
+        expected.extend_from_slice(b"\0\xFF\x03\x07Track 0");
+        expected.extend_from_slice(&[0, 0x90, 0x3C, 100]);
+        expected.extend_from_slice(&[0x30, 0x80, 0x3C, 0]);
+        expected.extend_from_slice(&[0, 0xFF, 0x2F, 0]);
+        assert_eq!(bytes, expected);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{
+    byte_types::int::{U16, U32},
+    cartridge_header::default_array,
+    error::{Error, Result, Table},
+    nitro_file::NitroFile,
+    sound::wave::{Wave, WaveType},
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Contents of the HEAD section
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct StreamHeader {
+    /// 0 for PCM8, 1 for PCM16, 2 for IMA-ADPCM
+    pub wave_type: u8,
+    pub looped: u8,
+    pub channel_count: u8,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip)]
+    pub _unknown: u8,
+    pub sample_rate: U16<LittleEndian>,
+    /// Sound hardware timer value for the sample rate
+    pub timer: U16<LittleEndian>,
+    /// In samples
+    pub loop_offset: U32<LittleEndian>,
+    pub sample_count: U32<LittleEndian>,
+    /// Relative to the start of the file
+    pub data_offset: U32<LittleEndian>,
+    pub block_count: U32<LittleEndian>,
+    /// Size of a block of one channel
+    pub block_size: U32<LittleEndian>,
+    pub block_samples: U32<LittleEndian>,
+    /// The last block is shorter, but padded to a multiple of 4 bytes
+    pub last_block_size: U32<LittleEndian>,
+    pub last_block_samples: U32<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _reserved: [u8; 32],
+}
+
+/// Streamed audio, stored in blocks that alternate between the channels
+#[derive(Clone, Debug)]
+pub struct Strm {
+    pub header: StreamHeader,
+    pub wave_type: WaveType,
+    pub wave: Wave,
+}
+
+impl Strm {
+    pub const DATA_MAGIC: [u8; 4] = *b"DATA";
+    pub const HEAD_MAGIC: [u8; 4] = *b"HEAD";
+    pub const MAGIC: [u8; 4] = *b"STRM";
+
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let file = NitroFile::read(data, Self::MAGIC, Table::Strm)?;
+
+        let section = file.require_section(Self::HEAD_MAGIC, Table::Strm)?;
+        let (header, _) = LayoutVerified::<_, StreamHeader>::new_from_prefix(section.data).ok_or(
+            Error::OutOfBounds {
+                table: Table::Strm,
+                offset: section.offset + 8,
+                expected: std::mem::size_of::<StreamHeader>(),
+                actual: section.data.len(),
+            },
+        )?;
+        let wave_type = WaveType::from_raw(header.wave_type).ok_or(Error::UnsupportedFormat {
+            table: Table::Strm,
+            value: header.wave_type as u32,
+        })?;
+
+        let channel_count = header.channel_count.max(1) as usize;
+        let block_count = header.block_count.get() as usize;
+        let mut channels = vec![vec![]; channel_count];
+        let mut offset = header.data_offset.get() as usize;
+        for block in 0..block_count {
+            let (size, stride) = if block + 1 == block_count {
+                let size = header.last_block_size.get() as usize;
+                (size, size.next_multiple_of(4))
+            } else {
+                let size = header.block_size.get() as usize;
+                (size, size)
+            };
+
+            for channel in channels.iter_mut() {
+                let samples = data
+                    .get(offset..(offset + size))
+                    .ok_or(Error::OutOfBounds {
+                        table: Table::Strm,
+                        offset,
+                        expected: size,
+                        actual: data.len().saturating_sub(offset),
+                    })?;
+                // Every ADPCM block starts with its own header
+                channel.extend(wave_type.decode(samples));
+                offset += stride;
+            }
+        }
+        for channel in channels.iter_mut() {
+            channel.truncate(header.sample_count.get() as usize);
+        }
+
+        Ok(Self {
+            header: *header,
+            wave_type,
+            wave: Wave {
+                sample_rate: header.sample_rate.get() as u32,
+                channels,
+                loop_start: (header.looped != 0).then(|| header.loop_offset.get() as usize),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nitro_file::tests::build;
+
+    /// A stereo PCM8 stream of two blocks of 4 bytes, the last one holding
+    /// only 2
+    fn strm(data: &[u8]) -> Vec<u8> {
+        // Nitro header, HEAD section, DATA section header
+        let data_offset = 0x10 + 8 + std::mem::size_of::<StreamHeader>() + 8;
+        let header = StreamHeader {
+            wave_type: 0,
+            looped: 1,
+            channel_count: 2,
+            _unknown: 0,
+            sample_rate: 8000.into(),
+            timer: 0.into(),
+            loop_offset: 1.into(),
+            sample_count: 6.into(),
+            data_offset: (data_offset as u32).into(),
+            block_count: 2.into(),
+            block_size: 4.into(),
+            block_samples: 4.into(),
+            last_block_size: 2.into(),
+            last_block_samples: 2.into(),
+            _reserved: [0; 32],
+        };
+        build(
+            Strm::MAGIC,
+            &[
+                (Strm::HEAD_MAGIC, header.as_bytes()),
+                (Strm::DATA_MAGIC, data),
+            ],
+        )
+    }
+
+    #[test]
+    fn interleaved_blocks() {
+        let data = [
+            1, 2, 3, 4, // left
+            11, 12, 13, 14, // right
+            5, 6, 0, 0, // last left block, padded
+            15, 16, 0, 0, // last right block
+        ];
+        let strm = Strm::read(&strm(&data)).unwrap();
+
+        let expected =
+            |samples: &[i16]| samples.iter().map(|sample| sample << 8).collect::<Vec<_>>();
+        assert_eq!(
+            strm.wave.channels,
+            [
+                expected(&[1, 2, 3, 4, 5, 6]),
+                expected(&[11, 12, 13, 14, 15, 16])
+            ]
+        );
+        assert_eq!(strm.wave.loop_start, Some(1));
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(matches!(
+            Strm::read(&strm(&[0; 13])),
+            Err(Error::OutOfBounds { expected: 2, .. })
+        ));
+    }
+}
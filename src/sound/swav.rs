@@ -0,0 +1,195 @@
+use crate::{
+    byte_types::int::{U16, U32},
+    cartridge_header::default_array,
+    error::{Error, Result, Table},
+    nitro_file::NitroFile,
+    sound::wave::{Wave, WaveType},
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Precedes the samples of every SWAV, also inside SWAR archives
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct WaveHeader {
+    /// 0 for PCM8, 1 for PCM16, 2 for IMA-ADPCM
+    pub wave_type: u8,
+    pub looped: u8,
+    pub sample_rate: U16<LittleEndian>,
+    /// Sound hardware timer value for the sample rate
+    pub timer: U16<LittleEndian>,
+    /// In words from the start of the data
+    pub loop_offset: U16<LittleEndian>,
+    /// In words after the loop offset
+    pub loop_length: U32<LittleEndian>,
+}
+
+/// Start of the DATA section of a SWAR
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct WaveArchiveHeader {
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _reserved: [u8; 32],
+    pub wave_count: U32<LittleEndian>,
+}
+
+/// A single sample
+#[derive(Clone, Debug)]
+pub struct Swav {
+    pub header: WaveHeader,
+    pub wave_type: WaveType,
+    pub wave: Wave,
+}
+
+impl Swav {
+    pub const DATA_MAGIC: [u8; 4] = *b"DATA";
+    pub const MAGIC: [u8; 4] = *b"SWAV";
+
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let file = NitroFile::read(data, Self::MAGIC, Table::Swav)?;
+        let section = file.require_section(Self::DATA_MAGIC, Table::Swav)?;
+        Self::read_wave(section.data, section.offset + 8, Table::Swav)
+    }
+
+    /// Reads a [WaveHeader] and the samples following it. `offset` is only
+    /// used for errors.
+    fn read_wave(data: &[u8], offset: usize, table: Table) -> Result<Self> {
+        let (header, samples) =
+            LayoutVerified::<_, WaveHeader>::new_from_prefix(data).ok_or(Error::OutOfBounds {
+                table,
+                offset,
+                expected: std::mem::size_of::<WaveHeader>(),
+                actual: data.len(),
+            })?;
+        let wave_type = WaveType::from_raw(header.wave_type).ok_or(Error::UnsupportedFormat {
+            table,
+            value: header.wave_type as u32,
+        })?;
+
+        let loop_offset = header.loop_offset.get() as usize * 4;
+        let size = loop_offset + header.loop_length.get() as usize * 4;
+        let samples = &samples[..samples.len().min(size)];
+
+        Ok(Self {
+            header: *header,
+            wave_type,
+            wave: Wave {
+                sample_rate: header.sample_rate.get() as u32,
+                channels: vec![wave_type.decode(samples)],
+                loop_start: (header.looped != 0).then(|| wave_type.samples(loop_offset)),
+            },
+        })
+    }
+}
+
+/// An archive of samples that instrument banks play
+#[derive(Clone, Debug)]
+pub struct Swar {
+    pub waves: Vec<Swav>,
+}
+
+impl Swar {
+    pub const DATA_MAGIC: [u8; 4] = *b"DATA";
+    pub const MAGIC: [u8; 4] = *b"SWAR";
+
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let file = NitroFile::read(data, Self::MAGIC, Table::Swar)?;
+        let section = file.require_section(Self::DATA_MAGIC, Table::Swar)?;
+        let out_of_bounds = |offset: usize, expected: usize| Error::OutOfBounds {
+            table: Table::Swar,
+            offset,
+            expected,
+            actual: data.len().saturating_sub(offset),
+        };
+
+        let (header, rest) = LayoutVerified::<_, WaveArchiveHeader>::new_from_prefix(section.data)
+            .ok_or_else(|| {
+                out_of_bounds(section.offset + 8, std::mem::size_of::<WaveArchiveHeader>())
+            })?;
+        let count = header.wave_count.get() as usize;
+        let (offsets, _) =
+            LayoutVerified::<_, [U32<LittleEndian>]>::new_slice_from_prefix(rest, count)
+                .ok_or_else(|| out_of_bounds(section.offset + 8 + 36, count * 4))?;
+
+        // Offsets are relative to the start of the file
+        let waves = offsets
+            .iter()
+            .map(|offset| {
+                let offset = offset.get() as usize;
+                let wave = data
+                    .get(offset..)
+                    .ok_or_else(|| out_of_bounds(offset, std::mem::size_of::<WaveHeader>()))?;
+                Swav::read_wave(wave, offset, Table::Swar)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { waves })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nitro_file::tests::build;
+
+    fn wave(wave_type: u8, looped: bool, loop_offset: u16, loop_length: u32) -> Vec<u8> {
+        let header = WaveHeader {
+            wave_type,
+            looped: looped as u8,
+            sample_rate: 8000.into(),
+            timer: 0.into(),
+            loop_offset: loop_offset.into(),
+            loop_length: loop_length.into(),
+        };
+        header.as_bytes().to_vec()
+    }
+
+    #[test]
+    fn adpcm_loop() {
+        // The ADPCM header and 8 samples before the loop, then 16 in the loop
+        let mut data = wave(2, true, 2, 2);
+        data.extend_from_slice(&[0; 16]);
+        // Past the end of the loop
+        data.extend_from_slice(&[0x77; 4]);
+        let swav = Swav::read(&build(Swav::MAGIC, &[(Swav::DATA_MAGIC, &data)])).unwrap();
+
+        assert_eq!(swav.wave_type, WaveType::ImaAdpcm);
+        assert_eq!(swav.wave.sample_rate, 8000);
+        assert_eq!(swav.wave.len(), 24);
+        assert_eq!(swav.wave.loop_start, Some(8));
+    }
+
+    #[test]
+    fn pcm16_loop() {
+        let mut data = wave(1, true, 1, 1);
+        data.extend_from_slice(&[1, 0, 2, 0, 3, 0, 4, 0]);
+        let swav = Swav::read(&build(Swav::MAGIC, &[(Swav::DATA_MAGIC, &data)])).unwrap();
+
+        assert_eq!(swav.wave.channels, [[1, 2, 3, 4]]);
+        assert_eq!(swav.wave.loop_start, Some(2));
+    }
+
+    #[test]
+    fn not_looped() {
+        let mut data = wave(0, false, 0, 1);
+        data.extend_from_slice(&[1, 2, 3, 4]);
+        let swav = Swav::read(&build(Swav::MAGIC, &[(Swav::DATA_MAGIC, &data)])).unwrap();
+
+        assert_eq!(swav.wave.len(), 4);
+        assert_eq!(swav.wave.loop_start, None);
+    }
+
+    #[test]
+    fn unsupported_type() {
+        let data = wave(3, false, 0, 0);
+        assert!(matches!(
+            Swav::read(&build(Swav::MAGIC, &[(Swav::DATA_MAGIC, &data)])),
+            Err(Error::UnsupportedFormat { value: 3, .. })
+        ));
+    }
+}
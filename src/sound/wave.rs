@@ -0,0 +1,254 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+/// Sample encodings of SWAV and STRM files
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum WaveType {
+    /// Signed 8 bit samples
+    Pcm8,
+    /// Signed 16 bit samples
+    Pcm16,
+    /// 4 bit samples after a header with the initial state
+    ImaAdpcm,
+}
+
+impl WaveType {
+    pub fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            0 => Some(WaveType::Pcm8),
+            1 => Some(WaveType::Pcm16),
+            2 => Some(WaveType::ImaAdpcm),
+            _ => None,
+        }
+    }
+
+    /// Number of samples in the first `size` bytes of a block
+    pub fn samples(self, size: usize) -> usize {
+        match self {
+            WaveType::Pcm8 => size,
+            WaveType::Pcm16 => size / 2,
+            WaveType::ImaAdpcm => size.saturating_sub(ADPCM_HEADER_SIZE) * 2,
+        }
+    }
+
+    /// Decodes a block of samples, ADPCM blocks start with their own header
+    pub fn decode(self, data: &[u8]) -> Vec<i16> {
+        match self {
+            WaveType::Pcm8 => data
+                .iter()
+                .map(|&sample| (sample as i8 as i16) << 8)
+                .collect(),
+            WaveType::Pcm16 => data
+                .chunks_exact(2)
+                .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+                .collect(),
+            WaveType::ImaAdpcm => decode_adpcm(data),
+        }
+    }
+}
+
+/// Initial sample (16 bit) and step index (8 bit), followed by padding
+const ADPCM_HEADER_SIZE: usize = 4;
+
+const ADPCM_INDEX_TABLE: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+const ADPCM_STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+/// Decodes IMA-ADPCM the way the sound hardware does, low nibble first and
+/// clamped to ±0x7FFF
+fn decode_adpcm(data: &[u8]) -> Vec<i16> {
+    if data.len() < ADPCM_HEADER_SIZE {
+        return vec![];
+    }
+    let mut sample = i16::from_le_bytes([data[0], data[1]]) as i32;
+    let mut index = (data[2] as i32).clamp(0, 88);
+
+    let mut samples = Vec::with_capacity(WaveType::ImaAdpcm.samples(data.len()));
+    for &byte in &data[ADPCM_HEADER_SIZE..] {
+        for nibble in [byte & 0xF, byte >> 4] {
+            let step = ADPCM_STEP_TABLE[index as usize];
+            let mut diff = step >> 3;
+            if nibble & 1 != 0 {
+                diff += step >> 2;
+            }
+            if nibble & 2 != 0 {
+                diff += step >> 1;
+            }
+            if nibble & 4 != 0 {
+                diff += step;
+            }
+            sample = if nibble & 8 != 0 {
+                (sample - diff).max(-0x7FFF)
+            } else {
+                (sample + diff).min(0x7FFF)
+            };
+            index = (index + ADPCM_INDEX_TABLE[(nibble & 7) as usize]).clamp(0, 88);
+            samples.push(sample as i16);
+        }
+    }
+    samples
+}
+
+/// Decoded audio
+#[derive(Clone, Debug)]
+pub struct Wave {
+    pub sample_rate: u32,
+    /// Samples of each channel, all of the same length
+    pub channels: Vec<Vec<i16>>,
+    /// Playback continues here after the last sample
+    pub loop_start: Option<usize>,
+}
+
+impl Wave {
+    pub fn len(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes a 16 bit RIFF WAV file, with the loop in a `smpl` chunk
+    pub fn write_wav<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        let channels = self.channels.len().max(1) as u16;
+        let data_size = self.len() as u32 * channels as u32 * 2;
+        let smpl_size = if self.loop_start.is_some() { 60 } else { 0 };
+
+        writer.write_all(b"RIFF")?;
+        let chunk_headers = if smpl_size != 0 { 3 } else { 2 };
+        writer.write_u32::<LittleEndian>(4 + chunk_headers * 8 + 16 + data_size + smpl_size)?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_u32::<LittleEndian>(16)?;
+        writer.write_u16::<LittleEndian>(1)?;
+        writer.write_u16::<LittleEndian>(channels)?;
+        writer.write_u32::<LittleEndian>(self.sample_rate)?;
+        writer.write_u32::<LittleEndian>(self.sample_rate * channels as u32 * 2)?;
+        writer.write_u16::<LittleEndian>(channels * 2)?;
+        writer.write_u16::<LittleEndian>(16)?;
+
+        writer.write_all(b"data")?;
+        writer.write_u32::<LittleEndian>(data_size)?;
+        let mut data = Vec::with_capacity(data_size as usize);
+        for index in 0..self.len() {
+            for channel in &self.channels {
+                data.write_i16::<LittleEndian>(channel[index])?;
+            }
+        }
+        writer.write_all(&data)?;
+
+        if let Some(loop_start) = self.loop_start {
+            writer.write_all(b"smpl")?;
+            writer.write_u32::<LittleEndian>(smpl_size)?;
+            // Manufacturer, product
+            writer.write_u32::<LittleEndian>(0)?;
+            writer.write_u32::<LittleEndian>(0)?;
+            // Sample period in nanoseconds
+            writer.write_u32::<LittleEndian>(1_000_000_000 / self.sample_rate.max(1))?;
+            // MIDI unity note (middle C), pitch fraction
+            writer.write_u32::<LittleEndian>(60)?;
+            writer.write_u32::<LittleEndian>(0)?;
+            // SMPTE format and offset
+            writer.write_u32::<LittleEndian>(0)?;
+            writer.write_u32::<LittleEndian>(0)?;
+            // One loop, no sampler data
+            writer.write_u32::<LittleEndian>(1)?;
+            writer.write_u32::<LittleEndian>(0)?;
+
+            // Cue point ID, forward loop, first and last sample, fraction,
+            // endless
+            writer.write_u32::<LittleEndian>(0)?;
+            writer.write_u32::<LittleEndian>(0)?;
+            writer.write_u32::<LittleEndian>(loop_start as u32)?;
+            writer.write_u32::<LittleEndian>(self.len().saturating_sub(1) as u32)?;
+            writer.write_u32::<LittleEndian>(0)?;
+            writer.write_u32::<LittleEndian>(0)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pcm() {
+        assert_eq!(WaveType::Pcm8.decode(&[0x01, 0xFF]), [0x100, -0x100]);
+        assert_eq!(WaveType::Pcm16.decode(&[0x34, 0x12, 0xFF]), [0x1234]);
+        assert_eq!(WaveType::Pcm16.samples(5), 2);
+    }
+
+    #[test]
+    fn adpcm() {
+        // From 0 with a step of 7: up by 7 + 3 + 1, the step grows to 16, down
+        // by 16 / 8, then up by the smallest steps
+        let samples = decode_adpcm(&[0, 0, 0, 0, 0x87, 0x00]);
+        assert_eq!(samples, [11, 9, 10, 11]);
+        assert_eq!(WaveType::ImaAdpcm.samples(6), 4);
+        assert!(decode_adpcm(&[0, 0, 0]).is_empty());
+    }
+
+    #[test]
+    fn adpcm_clamping() {
+        let high = 0x7FF0i16.to_le_bytes();
+        assert_eq!(decode_adpcm(&[high[0], high[1], 88, 0, 0x77]), [0x7FFF; 2]);
+
+        let low = (-0x7FF0i16).to_le_bytes();
+        assert_eq!(decode_adpcm(&[low[0], low[1], 88, 0, 0xFF]), [-0x7FFF; 2]);
+    }
+
+    #[test]
+    fn wav_bytes() {
+        let wave = Wave {
+            sample_rate: 1000,
+            channels: vec![vec![1, 2, 3], vec![-1, -2, -3]],
+            loop_start: Some(1),
+        };
+        let mut bytes = vec![];
+        wave.write_wav(&mut bytes).unwrap();
+
+        let mut expected = vec![];
+        expected.extend_from_slice(b"RIFF");
+        expected.extend_from_slice(&(4 + 3 * 8 + 16 + 12 + 60u32).to_le_bytes());
+        expected.extend_from_slice(b"WAVEfmt \x10\0\0\0\x01\0\x02\0");
+        expected.extend_from_slice(&1000u32.to_le_bytes());
+        expected.extend_from_slice(&4000u32.to_le_bytes());
+        expected.extend_from_slice(b"\x04\0\x10\0data\x0C\0\0\0");
+        for sample in [1i16, -1, 2, -2, 3, -3] {
+            expected.extend_from_slice(&sample.to_le_bytes());
+        }
+        expected.extend_from_slice(b"smpl");
+        for value in [60, 0, 0, 1_000_000, 60, 0, 0, 0, 1, 0, 0, 0, 1, 2, 0, 0u32] {
+            expected.extend_from_slice(&value.to_le_bytes());
+        }
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn wav_without_loop() {
+        let wave = Wave {
+            sample_rate: 1000,
+            channels: vec![vec![1]],
+            loop_start: None,
+        };
+        let mut bytes = vec![];
+        wave.write_wav(&mut bytes).unwrap();
+
+        assert_eq!(bytes.len(), 8 + 4 + 8 + 16 + 8 + 2);
+        assert_eq!(bytes[4..8], (4 + 2 * 8 + 16 + 2u32).to_le_bytes());
+        assert_eq!(bytes[36..40], *b"data");
+    }
+}
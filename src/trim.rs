@@ -0,0 +1,159 @@
+//! Removing and restoring the padding after the used part of a ROM.
+//!
+//! Dumps are as large as the cartridge chip, everything after
+//! `total_used_rom_size` is padding, except for the RSA signature of download
+//! play games that directly follows the used area.
+
+use crate::{
+    cartridge_header::CartridgeHeader,
+    error::{Error, Result},
+};
+
+/// Size of the download play RSA signature
+pub const RSA_SIGNATURE_SIZE: usize = 0x88;
+
+/// Start of the download play RSA signature
+pub const RSA_SIGNATURE_MAGIC: [u8; 2] = *b"ac";
+
+/// The value cartridges return past the end of their data
+pub const PADDING: u8 = 0xFF;
+
+/// Where the RSA signature of a download play game has to be, directly after
+/// the used area including the DSi area
+pub fn rsa_signature_offset(header: &CartridgeHeader, rom: &[u8]) -> Result<usize> {
+    let mut used = header.total_used_rom_size.get() as usize;
+    if let Some(twl) = header.read_twl_header(rom)? {
+        used = used.max(twl.total_used_rom_size.get() as usize);
+    }
+    Ok(used)
+}
+
+/// The RSA signature at [rsa_signature_offset], if there is one
+pub fn rsa_signature<'lt>(header: &CartridgeHeader, rom: &'lt [u8]) -> Result<Option<&'lt [u8]>> {
+    let offset = rsa_signature_offset(header, rom)?;
+    Ok(rom
+        .get(offset..(offset + RSA_SIGNATURE_SIZE))
+        .filter(|signature| signature.starts_with(&RSA_SIGNATURE_MAGIC)))
+}
+
+/// Size of the used part of the ROM, including the DSi area and the RSA
+/// signature if there is one
+pub fn used_size(header: &CartridgeHeader, rom: &[u8]) -> Result<usize> {
+    let used = rsa_signature_offset(header, rom)?;
+    Ok(match rsa_signature(header, rom)? {
+        Some(signature) => used + signature.len(),
+        None => used,
+    })
+}
+
+/// Cuts off everything after [used_size] and returns the number of removed
+/// bytes.
+///
+/// Fails without changing `rom` if the removed part contains anything but
+/// padding.
+pub fn trim(header: &CartridgeHeader, rom: &mut Vec<u8>) -> Result<usize> {
+    let used = used_size(header, rom)?;
+    let Some(tail) = rom.get(used..) else {
+        return Ok(0);
+    };
+
+    if let Some(position) = tail.iter().position(|&byte| byte != PADDING) {
+        return Err(Error::UnexpectedData {
+            offset: used + position,
+        });
+    }
+
+    let removed = tail.len();
+    rom.truncate(used);
+    Ok(removed)
+}
+
+/// Pads `rom` with [PADDING] up to the device capacity and returns the number
+/// of added bytes
+pub fn pad(header: &CartridgeHeader, rom: &mut Vec<u8>) -> Result<usize> {
+    let capacity = header
+        .capacity()
+        .size()
+        .ok_or(Error::UnknownDeviceCapacity {
+            raw: header.device_capacity_raw,
+        })?
+        .get_bytes() as usize;
+
+    let added = capacity.saturating_sub(rom.len());
+    rom.resize(rom.len() + added, PADDING);
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zerocopy::{AsBytes, FromBytes};
+
+    const USED: usize = 0x1000;
+
+    /// A 128 KiB dump with `USED` bytes of data, an RSA signature and padding
+    fn rom() -> (CartridgeHeader, Vec<u8>) {
+        let mut header = CartridgeHeader::new_zeroed();
+        header.total_used_rom_size = (USED as u32).into();
+        header.device_capacity_raw = 0;
+
+        let mut rom = vec![0; USED];
+        rom[..std::mem::size_of::<CartridgeHeader>()].copy_from_slice(header.as_bytes());
+        rom.extend_from_slice(&RSA_SIGNATURE_MAGIC);
+        rom.resize(USED + RSA_SIGNATURE_SIZE, 0x5A);
+        rom.resize(128 * 1024, PADDING);
+        (header, rom)
+    }
+
+    #[test]
+    fn trim_and_pad() {
+        let (header, original) = rom();
+        let mut rom = original.clone();
+
+        let removed = trim(&header, &mut rom).unwrap();
+        assert_eq!(removed, original.len() - USED - RSA_SIGNATURE_SIZE);
+        assert_eq!(rom, original[..(USED + RSA_SIGNATURE_SIZE)]);
+        assert_eq!(rsa_signature(&header, &rom).unwrap(), Some(&rom[USED..]));
+
+        let added = pad(&header, &mut rom).unwrap();
+        assert_eq!(added, removed);
+        assert_eq!(rom, original);
+    }
+
+    #[test]
+    fn without_signature() {
+        let (header, mut rom) = rom();
+        rom[USED..].fill(PADDING);
+
+        assert_eq!(rsa_signature(&header, &rom).unwrap(), None);
+        trim(&header, &mut rom).unwrap();
+        assert_eq!(rom.len(), USED);
+    }
+
+    #[test]
+    fn data_after_used_area() {
+        let (header, original) = rom();
+        for value in [0x00, 0x12] {
+            let mut rom = original.clone();
+            let offset = USED + RSA_SIGNATURE_SIZE + 0x100;
+            rom[offset] = value;
+
+            assert!(matches!(
+                trim(&header, &mut rom),
+                Err(Error::UnexpectedData { offset: actual }) if actual == offset
+            ));
+            assert_eq!(rom.len(), original.len());
+        }
+    }
+
+    #[test]
+    fn pad_unknown_capacity() {
+        let (mut header, mut rom) = rom();
+        header.device_capacity_raw = 0x80;
+
+        assert!(matches!(
+            pad(&header, &mut rom),
+            Err(Error::UnknownDeviceCapacity { raw: 0x80 })
+        ));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::{
+    byte_types::int::U32,
+    cartridge_header::{default_array, CartridgeHeader, OffsetAndSize},
+    error::{Error, Result, Table},
+};
+use byteorder::LittleEndian;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+/// Where the extended header lies in the ROM
+pub const TWL_HEADER_RANGE: Range<usize> = 0x180..0x1000;
+
+/// The extended header of DSi-enhanced and DSi-exclusive ROMs, which follows
+/// the [CartridgeHeader]
+#[repr(C)]
+#[derive(Copy, Clone, FromBytes, AsBytes, Unaligned, Derivative, Serialize, Deserialize)]
+#[derivative(Debug)]
+pub struct TwlHeader {
+    /// MBK1 to MBK5, the WRAM slots
+    pub global_mbk_settings: [U32<LittleEndian>; 5],
+    /// MBK6 to MBK8, the WRAM areas of the ARM9
+    pub arm9_mbk_settings: [U32<LittleEndian>; 3],
+    /// MBK6 to MBK8, the WRAM areas of the ARM7
+    pub arm7_mbk_settings: [U32<LittleEndian>; 3],
+    /// MBK9, the WRAM slot write protection
+    pub mbk9_setting: [u8; 3],
+    pub wramcnt_setting: u8,
+
+    pub region_flags: U32<LittleEndian>,
+    pub access_control: U32<LittleEndian>,
+    pub arm7_scfg_ext: U32<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _reserved_0: [u8; 3],
+    /// Bit 0: touchscreen and sound in DSi mode, bit 1: requires the EULA,
+    /// bit 2: uses banner.sav, bit 6: the header has an RSA signature
+    pub flags: u8,
+
+    pub arm9i_rom_offset: U32<LittleEndian>,
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _reserved_1: [u8; 4],
+    pub arm9i_ram_address: U32<LittleEndian>,
+    pub arm9i_size: U32<LittleEndian>,
+
+    pub arm7i_rom_offset: U32<LittleEndian>,
+    /// RAM address of the SD/MMC device list for the ARM7
+    pub device_list_ram_address: U32<LittleEndian>,
+    pub arm7i_ram_address: U32<LittleEndian>,
+    pub arm7i_size: U32<LittleEndian>,
+
+    /// The DS part of the ROM covered by the sector hashes
+    pub digest_ntr_region: OffsetAndSize,
+    /// The DSi part of the ROM covered by the sector hashes
+    pub digest_twl_region: OffsetAndSize,
+    pub digest_sector_hashtable: OffsetAndSize,
+    pub digest_block_hashtable: OffsetAndSize,
+    pub digest_sector_size: U32<LittleEndian>,
+    pub digest_block_sector_count: U32<LittleEndian>,
+
+    pub banner_size: U32<LittleEndian>,
+    pub shared2_0000_size: U32<LittleEndian>,
+    /// Including the DSi area, unlike
+    /// [CartridgeHeader::total_used_rom_size](CartridgeHeader)
+    pub total_used_rom_size: U32<LittleEndian>,
+    pub shared2_0001_size: U32<LittleEndian>,
+    pub eula_version: U32<LittleEndian>,
+    pub use_ratings: U32<LittleEndian>,
+
+    /// Areas encrypted with AES-CTR, usually ARM9i and ARM7i
+    pub modcrypt_area_1: OffsetAndSize,
+    pub modcrypt_area_2: OffsetAndSize,
+
+    /// Low word: the game code, high word: the title type
+    pub title_id: [U32<LittleEndian>; 2],
+    /// Save sizes of DSiWare titles
+    pub public_save_size: U32<LittleEndian>,
+    pub private_save_size: U32<LittleEndian>,
+
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _reserved_2: [u8; 0xB0],
+
+    /// One byte per rating organization, bit 7 marks the rating as valid
+    pub age_ratings: [u8; 0x10],
+
+    /// SHA1-HMACs over the ARM9 binary including the secure area, the ARM7
+    /// binary, the digest master hash, the banner, and the decrypted ARM9i and
+    /// ARM7i binaries
+    #[derivative(Debug = "ignore")]
+    #[serde(with = "crate::byte_types::hex_array")]
+    pub arm9_hmac: [u8; 0x14],
+    #[derivative(Debug = "ignore")]
+    #[serde(with = "crate::byte_types::hex_array")]
+    pub arm7_hmac: [u8; 0x14],
+    #[derivative(Debug = "ignore")]
+    #[serde(with = "crate::byte_types::hex_array")]
+    pub digest_master_hmac: [u8; 0x14],
+    #[derivative(Debug = "ignore")]
+    #[serde(with = "crate::byte_types::hex_array")]
+    pub banner_hmac: [u8; 0x14],
+    #[derivative(Debug = "ignore")]
+    #[serde(with = "crate::byte_types::hex_array")]
+    pub arm9i_hmac: [u8; 0x14],
+    #[derivative(Debug = "ignore")]
+    #[serde(with = "crate::byte_types::hex_array")]
+    pub arm7i_hmac: [u8; 0x14],
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _reserved_3: [u8; 0x28],
+    #[derivative(Debug = "ignore")]
+    #[serde(with = "crate::byte_types::hex_array")]
+    pub arm9_without_secure_area_hmac: [u8; 0x14],
+
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _reserved_4: [u8; 0xA4C],
+    #[derivative(Debug = "ignore")]
+    #[serde(skip, default = "default_array")]
+    pub _debug_arguments: [u8; 0x180],
+
+    /// RSA-SHA1 signature over the first 0xE00 bytes of the ROM
+    #[derivative(Debug = "ignore")]
+    #[serde(with = "crate::byte_types::hex_array")]
+    pub rsa_signature: [u8; 0x80],
+}
+
+impl TwlHeader {
+    /// The ARM9i binary as stored in the ROM, possibly encrypted with modcrypt
+    pub fn read_arm9i<'lt>(&self, rom: &'lt [u8]) -> Result<&'lt [u8]> {
+        Self::read_binary(
+            rom,
+            self.arm9i_rom_offset.get(),
+            self.arm9i_size.get(),
+            Table::Arm9i,
+        )
+    }
+
+    /// The ARM7i binary as stored in the ROM, possibly encrypted with modcrypt
+    pub fn read_arm7i<'lt>(&self, rom: &'lt [u8]) -> Result<&'lt [u8]> {
+        Self::read_binary(
+            rom,
+            self.arm7i_rom_offset.get(),
+            self.arm7i_size.get(),
+            Table::Arm7i,
+        )
+    }
+
+    fn read_binary(rom: &[u8], offset: u32, size: u32, table: Table) -> Result<&[u8]> {
+        let (offset, size) = (offset as usize, size as usize);
+        rom.get(offset..(offset + size)).ok_or(Error::OutOfBounds {
+            table,
+            offset,
+            expected: size,
+            actual: rom.len().saturating_sub(offset),
+        })
+    }
+
+    /// The title ID as a single number, e.g. 0x00030004_4b504e41
+    pub fn title_id(&self) -> u64 {
+        ((self.title_id[1].get() as u64) << 32) | self.title_id[0].get() as u64
+    }
+
+    pub fn has_rsa_signature(&self) -> bool {
+        self.flags & 0x40 != 0
+    }
+
+    pub fn is_modcrypted(&self) -> bool {
+        self.modcrypt_area_1.size.get() != 0 || self.modcrypt_area_2.size.get() != 0
+    }
+}
+
+impl CartridgeHeader {
+    /// Whether `unit_code` marks the ROM as DSi-enhanced or DSi-exclusive
+    pub fn is_twl(&self) -> bool {
+        self.unit_code & 0x02 != 0
+    }
+
+    /// Reads the extended header if this is a DSi ROM
+    pub fn read_twl_header(&self, rom: &[u8]) -> Result<Option<TwlHeader>> {
+        if !self.is_twl() {
+            return Ok(None);
+        }
+
+        let raw = rom.get(TWL_HEADER_RANGE).ok_or(Error::OutOfBounds {
+            table: Table::TwlHeader,
+            offset: TWL_HEADER_RANGE.start,
+            expected: TWL_HEADER_RANGE.len(),
+            actual: rom.len().saturating_sub(TWL_HEADER_RANGE.start),
+        })?;
+
+        let header = LayoutVerified::<_, TwlHeader>::new(raw)
+            .expect("TWL_HEADER_RANGE has the size of the header");
+        Ok(Some(*header))
+    }
+}